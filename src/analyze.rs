@@ -0,0 +1,462 @@
+use std::{collections::HashSet, ops::Range};
+
+use super::{
+    datum::Datum,
+    expression::{
+        binding::{Binding, Let, LetKind, Set},
+        call::{Call, Primitive},
+        conditional::{self, Clause, If},
+        immediate::{Immediate, FIXNUM_MAX, FIXNUM_MIN},
+        procedure::{Application, Code, Label, LabelCall, Labels, Lambda},
+        program::{Form, Program},
+        Expression,
+    },
+    parse::{ParseError, ParseErrorKind},
+};
+
+enum Operator {
+    Primitive(Primitive),
+    If,
+    And,
+    Or,
+    When,
+    Unless,
+    Begin,
+    LabelCall(String),
+    Set(String),
+    Application(Expression),
+}
+
+/// Analyzes a single expression, optionally preceded by definitions.
+pub fn expression(data: Vec<Datum>) -> Result<Expression, ParseError> {
+    let end = data.last().map_or(0, |datum| datum.span().end);
+    let mut labels = Vec::new();
+    let mut data = data.into_iter();
+
+    while let Some(datum) = data.next() {
+        if is_definition(&datum) {
+            labels.push(definition(datum)?);
+            continue;
+        }
+
+        // Labels cannot be nested, so the expression after definitions may
+        // not be one.
+        let expression = analyze(datum, labels.is_empty())?;
+        if let Some(extra) = data.next() {
+            return Err(unexpected("end of input", &extra));
+        }
+
+        return Ok(if labels.is_empty() {
+            expression
+        } else {
+            Expression::Labels(Labels::new(labels, expression))
+        });
+    }
+
+    let expected = if labels.is_empty() {
+        "expression"
+    } else {
+        "definition or expression"
+    };
+    Err(ParseError::new(
+        ParseErrorKind::UnexpectedToken {
+            expected,
+            found: String::from("end of input"),
+        },
+        end..end,
+    ))
+}
+
+/// Analyzes any number of top-level definitions and expressions.
+pub fn program(data: Vec<Datum>) -> Result<Program, ParseError> {
+    let mut forms = Vec::new();
+    let mut defined = HashSet::new();
+
+    for datum in data {
+        if !is_definition(&datum) {
+            forms.push(Form::Expression(analyze(datum, true)?));
+            continue;
+        }
+
+        let span = defined_name(&datum).map_or_else(|| datum.span(), Datum::span);
+        let label = definition(datum)?;
+        if !defined.insert(label.name.clone()) {
+            return Err(invalid("procedure is already defined", span));
+        }
+        forms.push(Form::Definition(label));
+    }
+
+    Ok(Program::new(forms))
+}
+
+fn is_definition(datum: &Datum) -> bool {
+    matches!(datum, Datum::List(items, _) if items.first().and_then(Datum::symbol) == Some("define"))
+}
+
+// The name in the header of a definition.
+fn defined_name(datum: &Datum) -> Option<&Datum> {
+    match datum {
+        Datum::List(items, _) => match items.get(1) {
+            Some(Datum::List(header, _)) => header.first(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Only `labels` forms at `top` level are allowed.
+fn analyze(datum: Datum, top: bool) -> Result<Expression, ParseError> {
+    match datum {
+        Datum::Boolean(boolean, _) => Ok(Expression::Immediate(Immediate::Boolean(boolean))),
+        Datum::Character(character, _) => {
+            Ok(Expression::Immediate(Immediate::Character(character)))
+        }
+        Datum::Integer(integer, _) if !(FIXNUM_MIN..=FIXNUM_MAX).contains(&integer) => {
+            Ok(Expression::Bignum(integer.into()))
+        }
+        Datum::Integer(integer, _) => Ok(Expression::Immediate(Immediate::Integer(integer))),
+        Datum::Bignum(bignum, _) => Ok(Expression::Bignum(bignum)),
+        Datum::Flonum(flonum, _) => Ok(Expression::Flonum(flonum)),
+        Datum::Rational(numerator, denominator, _) => {
+            Ok(Expression::Rational(numerator, denominator))
+        }
+        Datum::String(string, _) => Ok(Expression::String(string)),
+        Datum::Symbol(name, _) => Ok(Expression::Variable(name)),
+        Datum::List(items, _) if items.is_empty() => Ok(Expression::Immediate(Immediate::Null)),
+        Datum::List(items, span) => form(items, span, top),
+        Datum::Vector(items, _) => items
+            .into_iter()
+            .map(|item| {
+                let span = item.span();
+                match analyze(item, false)? {
+                    element if element.is_literal() => Ok(element),
+                    _ => Err(invalid("vector literals may only contain constants", span)),
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(Expression::Vector),
+        Datum::Pair(_, _, span) => Err(ParseError::new(malformed(), span)),
+    }
+}
+
+fn form(items: Vec<Datum>, span: Range<usize>, top: bool) -> Result<Expression, ParseError> {
+    let operator = match &items[0] {
+        Datum::Symbol(name, _) => match name.as_str() {
+            "cond" => return cond(items, span),
+            "labels" => return labels(items, span, top),
+            "define" => return Err(invalid("`define` is only allowed at top level", span)),
+            "lambda" => return lambda(items, span),
+            "labelcall" => match items.get(1) {
+                Some(Datum::Symbol(label, _)) => Some(Operator::LabelCall(label.clone())),
+                Some(label) => return Err(unexpected("label name", label)),
+                None => return Err(missing("label name", &span)),
+            },
+            "set!" => match items.get(1) {
+                Some(Datum::Symbol(name, _)) => Some(Operator::Set(name.clone())),
+                Some(name) => return Err(unexpected("variable name", name)),
+                None => return Err(missing("variable name", &span)),
+            },
+            name => match name.parse() {
+                Ok(kind) => return binding(kind, items, span),
+                Err(_) => Some(operator(name)),
+            },
+        },
+        Datum::List(head, _) if !head.is_empty() => None,
+        head => return Err(unexpected("operator", head)),
+    };
+
+    let mut items = items.into_iter();
+    let head = items.next().unwrap();
+    let operator = match operator {
+        Some(operator @ (Operator::LabelCall(_) | Operator::Set(_))) => {
+            items.next();
+            operator
+        }
+        Some(operator) => operator,
+        None => Operator::Application(analyze(head, false)?),
+    };
+
+    let operands = items
+        .map(|item| analyze(item, false))
+        .collect::<Result<_, _>>()?;
+
+    apply(operator, operands).map_err(|kind| ParseError::new(kind, span))
+}
+
+fn binding(kind: LetKind, items: Vec<Datum>, span: Range<usize>) -> Result<Expression, ParseError> {
+    shape(&items, &["`(`", "expression"], &span)?;
+    let mut items = items.into_iter().skip(1);
+
+    let bindings = match items.next().unwrap() {
+        Datum::List(bindings, _) => bindings
+            .into_iter()
+            .map(|binding| match binding {
+                Datum::List(binding, span) if !binding.is_empty() => {
+                    shape(&binding, &["expression"], &span)?;
+                    let mut binding = binding.into_iter();
+                    match binding.next().unwrap() {
+                        Datum::Symbol(name, _) => {
+                            Ok(Binding::new(name, analyze(binding.next().unwrap(), false)?))
+                        }
+                        name => Err(unexpected("variable name", &name)),
+                    }
+                }
+                binding => Err(unexpected("binding or `)`", &binding)),
+            })
+            .collect::<Result<_, _>>()?,
+        bindings => return Err(unexpected("`(`", &bindings)),
+    };
+
+    let body = analyze(items.next().unwrap(), false)?;
+    Ok(Expression::Let(Let::new(kind, bindings, body)))
+}
+
+fn cond(items: Vec<Datum>, span: Range<usize>) -> Result<Expression, ParseError> {
+    let clauses = items
+        .into_iter()
+        .skip(1)
+        .map(|clause| match clause {
+            Datum::List(clause, span) if !clause.is_empty() => {
+                let expressions = clause
+                    .into_iter()
+                    .map(|item| analyze(item, false))
+                    .collect::<Result<_, _>>()?;
+                self::clause(expressions).map_err(|kind| ParseError::new(kind, span))
+            }
+            clause => Err(unexpected("`cond` clause or `)`", &clause)),
+        })
+        .collect::<Result<_, _>>()?;
+
+    conditional::cond(clauses).ok_or_else(|| invalid("`else` clause must come last", span))
+}
+
+fn clause(expressions: Vec<Expression>) -> Result<Clause, ParseErrorKind> {
+    let mut expressions = expressions.into_iter();
+
+    match (expressions.next(), expressions.next(), expressions.next()) {
+        (Some(Expression::Variable(name)), body, None) if name == "else" => body
+            .map(Clause::Else)
+            .ok_or(ParseErrorKind::Invalid("`else` clause has no body")),
+        (Some(test), None, None) => Ok(Clause::Test(test)),
+        (Some(test), Some(body), None) => Ok(Clause::Consequent(test, body)),
+        _ => Err(ParseErrorKind::Invalid("malformed `cond` clause")),
+    }
+}
+
+fn lambda(items: Vec<Datum>, span: Range<usize>) -> Result<Expression, ParseError> {
+    let Code {
+        parameters, body, ..
+    } = code(items, span)?;
+    Ok(Expression::Lambda(Lambda::new(parameters, *body)))
+}
+
+// Reads the parameters and body of a `lambda` or `code` form.
+fn code(items: Vec<Datum>, span: Range<usize>) -> Result<Code, ParseError> {
+    shape(&items, &["`(`", "expression"], &span)?;
+    let mut items = items.into_iter().skip(1);
+
+    let parameters = match items.next().unwrap() {
+        Datum::List(parameters, _) => parameters
+            .into_iter()
+            .map(|parameter| match parameter {
+                Datum::Symbol(name, _) => Ok(name),
+                parameter => Err(unexpected("parameter name or `)`", &parameter)),
+            })
+            .collect::<Result<_, _>>()?,
+        parameters => return Err(unexpected("`(`", &parameters)),
+    };
+
+    let body = analyze(items.next().unwrap(), false)?;
+    Ok(Code::new(parameters, body))
+}
+
+fn labels(items: Vec<Datum>, span: Range<usize>, top: bool) -> Result<Expression, ParseError> {
+    shape(&items, &["`(`", "expression"], &span)?;
+    let mut items = items.into_iter().skip(1);
+
+    let labels = match items.next().unwrap() {
+        Datum::List(labels, _) => labels
+            .into_iter()
+            .map(|label| match label {
+                Datum::List(label, span) if !label.is_empty() => {
+                    shape(&label, &["`(code`"], &span)?;
+                    let mut label = label.into_iter();
+                    let name = match label.next().unwrap() {
+                        Datum::Symbol(name, _) => name,
+                        name => return Err(unexpected("label name", &name)),
+                    };
+                    match label.next().unwrap() {
+                        Datum::List(code, span)
+                            if code.first().and_then(Datum::symbol) == Some("code") =>
+                        {
+                            Ok(Label::new(name, self::code(code, span)?))
+                        }
+                        Datum::List(code, _) if !code.is_empty() => {
+                            Err(unexpected("`code`", &code[0]))
+                        }
+                        code => Err(unexpected("`(code`", &code)),
+                    }
+                }
+                label => Err(unexpected("label binding or `)`", &label)),
+            })
+            .collect::<Result<_, _>>()?,
+        labels => return Err(unexpected("`(`", &labels)),
+    };
+
+    let body = analyze(items.next().unwrap(), false)?;
+    if !top {
+        return Err(invalid("`labels` is only allowed at top level", span));
+    }
+    Ok(Expression::Labels(Labels::new(labels, body)))
+}
+
+fn definition(datum: Datum) -> Result<Label, ParseError> {
+    let (items, span) = match datum {
+        Datum::List(items, span) => (items, span),
+        datum => return Err(unexpected("definition", &datum)),
+    };
+    shape(&items, &["procedure header", "expression"], &span)?;
+    let mut items = items.into_iter().skip(1);
+
+    let (name, parameters) = match items.next().unwrap() {
+        Datum::List(header, span) if header.is_empty() => {
+            return Err(invalid("expected procedure name", span))
+        }
+        Datum::List(header, _) => {
+            let mut names = header.into_iter().map(|name| match name {
+                Datum::Symbol(name, _) => Ok(name),
+                name => Err(unexpected("parameter name or `)`", &name)),
+            });
+            let name = names.next().unwrap()?;
+            (name, names.collect::<Result<_, _>>()?)
+        }
+        header => return Err(unexpected("procedure header", &header)),
+    };
+
+    let body = analyze(items.next().unwrap(), false)?;
+    Ok(Label::new(name, Code::new(parameters, body)))
+}
+
+fn apply(operator: Operator, operands: Vec<Expression>) -> Result<Expression, ParseErrorKind> {
+    let mut operands = operands.into_iter();
+
+    let expression = match (operator, operands.len()) {
+        (Operator::Primitive(primitive), n) if n == primitive.arity() => {
+            Expression::Call(Call::new(primitive, operands.collect()))
+        }
+        (Operator::If, 2) | (Operator::When, 2) => {
+            conditional::when(operands.next().unwrap(), operands.next().unwrap())
+        }
+        (Operator::If, 3) => Expression::If(If::new(
+            operands.next().unwrap(),
+            operands.next().unwrap(),
+            operands.next().unwrap(),
+        )),
+        (Operator::Unless, 2) => {
+            conditional::unless(operands.next().unwrap(), operands.next().unwrap())
+        }
+        (Operator::Begin, n) if n > 0 => Expression::Begin(operands.collect()),
+        (Operator::And, _) => conditional::and(operands.collect()),
+        (Operator::Or, _) => conditional::or(operands.collect()),
+        (Operator::LabelCall(label), _) => {
+            Expression::LabelCall(LabelCall::new(label, operands.collect()))
+        }
+        (Operator::Set(name), 1) => Expression::Set(Set::new(name, operands.next().unwrap())),
+        (Operator::Application(function), _) => {
+            Expression::Application(Application::new(function, operands.collect()))
+        }
+        (operator, found) => {
+            let (operator, expected) = match operator {
+                Operator::Primitive(primitive) => {
+                    let expected = match primitive.arity() {
+                        1 => "1 argument",
+                        2 => "2 arguments",
+                        _ => "3 arguments",
+                    };
+                    (primitive.to_string(), expected)
+                }
+                Operator::If => (String::from("if"), "2 or 3 arguments"),
+                Operator::When => (String::from("when"), "2 arguments"),
+                Operator::Unless => (String::from("unless"), "2 arguments"),
+                Operator::Begin => (String::from("begin"), "at least 1 argument"),
+                Operator::Set(_) => {
+                    return Err(ParseErrorKind::WrongArity {
+                        operator: String::from("set!"),
+                        expected: "2 arguments",
+                        found: found + 1,
+                    })
+                }
+                _ => return Err(malformed()),
+            };
+
+            return Err(ParseErrorKind::WrongArity {
+                operator,
+                expected,
+                found,
+            });
+        }
+    };
+
+    Ok(expression)
+}
+
+fn operator(symbol: &str) -> Operator {
+    match symbol {
+        "if" => Operator::If,
+        "and" => Operator::And,
+        "or" => Operator::Or,
+        "when" => Operator::When,
+        "unless" => Operator::Unless,
+        "begin" => Operator::Begin,
+        _ => match symbol.parse() {
+            Ok(primitive) => Operator::Primitive(primitive),
+            Err(_) => Operator::Application(Expression::Variable(symbol.to_string())),
+        },
+    }
+}
+
+// Checks that a form has one item after its keyword for each of `expected`,
+// which describe them, and reports the first missing or extra one.
+fn shape(
+    items: &[Datum],
+    expected: &[&'static str],
+    span: &Range<usize>,
+) -> Result<(), ParseError> {
+    match items.get(expected.len() + 1) {
+        _ if items.len() <= expected.len() => Err(missing(expected[items.len() - 1], span)),
+        Some(extra) => Err(unexpected("`)`", extra)),
+        None => Ok(()),
+    }
+}
+
+fn malformed() -> ParseErrorKind {
+    ParseErrorKind::Invalid("malformed expression")
+}
+
+fn invalid(message: &'static str, span: Range<usize>) -> ParseError {
+    ParseError::new(ParseErrorKind::Invalid(message), span)
+}
+
+// Reports `datum` where `expected` should have been, by its first token.
+fn unexpected(expected: &'static str, datum: &Datum) -> ParseError {
+    let span = datum.span();
+    let (found, span) = match datum {
+        Datum::List(items, _) | Datum::Pair(items, _, _) if !items.is_empty() => {
+            (String::from("`(`"), span.start..span.start + 1)
+        }
+        Datum::Vector(..) => (String::from("`#(`"), span.start..span.start + 2),
+        datum => (format!("`{}`", datum), span),
+    };
+
+    ParseError::new(ParseErrorKind::UnexpectedToken { expected, found }, span)
+}
+
+// Reports the end of the list spanning `span` where `expected` should have
+// been.
+fn missing(expected: &'static str, span: &Range<usize>) -> ParseError {
+    let kind = ParseErrorKind::UnexpectedToken {
+        expected,
+        found: String::from("`)`"),
+    };
+    ParseError::new(kind, span.end - 1..span.end)
+}
@@ -0,0 +1,311 @@
+use super::expression::{
+    binding::{Binding, Let, LetKind, Set},
+    call::{BinaryPrimitive, Call, UnaryPrimitive},
+    conditional::If,
+    procedure::{Application, Code, Label, LabelCall, Labels, Lambda},
+    Expression,
+};
+
+/// Returns the variables that are the target of a `set!` in `expression`, in
+/// order of first occurrence.
+pub fn assigned_variables(expression: &Expression) -> Vec<String> {
+    let mut assigned = Vec::new();
+    collect(expression, &mut assigned);
+    assigned
+}
+
+fn collect(expression: &Expression, assigned: &mut Vec<String>) {
+    match expression {
+        Expression::Application(Application {
+            function,
+            arguments,
+        }) => {
+            collect(function, assigned);
+            for argument in arguments {
+                collect(argument, assigned);
+            }
+        }
+        Expression::Begin(expressions)
+        | Expression::Call(Call {
+            arguments: expressions,
+            ..
+        })
+        | Expression::LabelCall(LabelCall {
+            arguments: expressions,
+            ..
+        })
+        | Expression::Vector(expressions) => {
+            for expression in expressions {
+                collect(expression, assigned);
+            }
+        }
+        Expression::If(If {
+            condition,
+            consequent,
+            alternative,
+        }) => {
+            collect(condition, assigned);
+            collect(consequent, assigned);
+            collect(alternative, assigned);
+        }
+        Expression::Labels(Labels { labels, body }) => {
+            for Label { code, .. } in labels {
+                collect(&code.body, assigned);
+            }
+            collect(body, assigned);
+        }
+        Expression::Lambda(Lambda { body, .. }) => collect(body, assigned),
+        Expression::Let(Let { bindings, body, .. }) => {
+            for Binding { expression, .. } in bindings {
+                collect(expression, assigned);
+            }
+            collect(body, assigned);
+        }
+        Expression::Set(Set { name, value }) => {
+            if !assigned.contains(name) {
+                assigned.push(name.clone());
+            }
+            collect(value, assigned);
+        }
+        Expression::Bignum(_)
+        | Expression::Closure(_)
+        | Expression::Flonum(_)
+        | Expression::Immediate(_)
+        | Expression::Rational(..)
+        | Expression::String(_)
+        | Expression::Variable(_) => {}
+    }
+}
+
+/// Moves every local variable that is the target of a `set!` into a box on
+/// the heap. The variable is bound to the box instead of its value, reads
+/// become `unbox` and assignments become `set-box!`, so closures that capture
+/// the variable share the box and see each other's assignments.
+///
+/// Every binding of an assigned name is boxed, whether or not that binding is
+/// itself assigned. Assignments to names that are not bound locally are left
+/// as they are.
+pub fn convert(expression: Expression) -> Expression {
+    let assigned = assigned_variables(&expression);
+
+    if assigned.is_empty() {
+        return expression;
+    }
+
+    rewrite(expression, &assigned, &mut Vec::new())
+}
+
+fn rewrite(expression: Expression, assigned: &[String], bound: &mut Vec<String>) -> Expression {
+    match expression {
+        Expression::Application(Application {
+            function,
+            arguments,
+        }) => Expression::Application(Application::new(
+            rewrite(*function, assigned, bound),
+            rewrite_all(arguments, assigned, bound),
+        )),
+        Expression::Begin(expressions) => {
+            Expression::Begin(rewrite_all(expressions, assigned, bound))
+        }
+        Expression::Call(Call {
+            primitive,
+            arguments,
+        }) => Expression::Call(Call::new(
+            primitive,
+            rewrite_all(arguments, assigned, bound),
+        )),
+        Expression::If(If {
+            condition,
+            consequent,
+            alternative,
+        }) => Expression::If(If::new(
+            rewrite(*condition, assigned, bound),
+            rewrite(*consequent, assigned, bound),
+            rewrite(*alternative, assigned, bound),
+        )),
+        Expression::LabelCall(LabelCall { label, arguments }) => Expression::LabelCall(
+            LabelCall::new(label, rewrite_all(arguments, assigned, bound)),
+        ),
+        Expression::Labels(Labels { labels, body }) => {
+            let labels = labels
+                .into_iter()
+                .map(|Label { name, code }| {
+                    let Code {
+                        parameters,
+                        free,
+                        body,
+                    } = code;
+                    let body = rewrite_procedure(&parameters, *body, assigned, bound);
+
+                    Label::new(
+                        name,
+                        Code {
+                            parameters,
+                            free,
+                            body: Box::new(body),
+                        },
+                    )
+                })
+                .collect();
+            let body = rewrite(*body, assigned, bound);
+
+            Expression::Labels(Labels::new(labels, body))
+        }
+        Expression::Lambda(Lambda { parameters, body }) => {
+            let body = rewrite_procedure(&parameters, *body, assigned, bound);
+            Expression::Lambda(Lambda::new(parameters, body))
+        }
+        Expression::Let(Let {
+            kind,
+            bindings,
+            body,
+        }) => {
+            let depth = bound.len();
+            let mut converted = Vec::new();
+            for Binding { name, expression } in bindings {
+                let mut expression = match kind {
+                    LetKind::Let => {
+                        let inner = bound.split_off(depth);
+                        let expression = rewrite(expression, assigned, bound);
+                        bound.extend(inner);
+                        expression
+                    }
+                    LetKind::LetStar => rewrite(expression, assigned, bound),
+                };
+                if assigned.contains(&name) {
+                    expression = Expression::Call(Call::new(UnaryPrimitive::Box, vec![expression]));
+                }
+                bound.push(name.clone());
+                converted.push(Binding::new(name, expression));
+            }
+            let body = rewrite(*body, assigned, bound);
+            bound.truncate(depth);
+
+            Expression::Let(Let::new(kind, converted, body))
+        }
+        Expression::Set(Set { name, value }) => {
+            let value = rewrite(*value, assigned, bound);
+
+            if bound.contains(&name) {
+                Expression::Call(Call::new(
+                    BinaryPrimitive::SetBox,
+                    vec![Expression::Variable(name), value],
+                ))
+            } else {
+                Expression::Set(Set::new(name, value))
+            }
+        }
+        Expression::Variable(name) if bound.contains(&name) && assigned.contains(&name) => {
+            Expression::Call(Call::new(
+                UnaryPrimitive::Unbox,
+                vec![Expression::Variable(name)],
+            ))
+        }
+        Expression::Vector(elements) => Expression::Vector(rewrite_all(elements, assigned, bound)),
+        expression @ (Expression::Bignum(_)
+        | Expression::Closure(_)
+        | Expression::Flonum(_)
+        | Expression::Immediate(_)
+        | Expression::Rational(..)
+        | Expression::String(_)
+        | Expression::Variable(_)) => expression,
+    }
+}
+
+// Parameters are passed by value, so assigned ones are copied into boxes of
+// the same name on entry.
+fn rewrite_procedure(
+    parameters: &[String],
+    body: Expression,
+    assigned: &[String],
+    bound: &mut Vec<String>,
+) -> Expression {
+    let depth = bound.len();
+    bound.extend(parameters.iter().cloned());
+    let body = rewrite(body, assigned, bound);
+    bound.truncate(depth);
+
+    let boxes: Vec<Binding> = parameters
+        .iter()
+        .filter(|parameter| assigned.contains(parameter))
+        .map(|parameter| {
+            Binding::new(
+                parameter,
+                Expression::Call(Call::new(
+                    UnaryPrimitive::Box,
+                    vec![Expression::Variable(parameter.clone())],
+                )),
+            )
+        })
+        .collect();
+
+    if boxes.is_empty() {
+        body
+    } else {
+        Expression::Let(Let::new(LetKind::Let, boxes, body))
+    }
+}
+
+fn rewrite_all(
+    expressions: Vec<Expression>,
+    assigned: &[String],
+    bound: &mut Vec<String>,
+) -> Vec<Expression> {
+    expressions
+        .into_iter()
+        .map(|expression| rewrite(expression, assigned, bound))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assigned() {
+        let cases: [(&str, &[&str]); 5] = [
+            ("1", &[]),
+            ("(set! x 1)", &["x"]),
+            (
+                "(let ((x 1)) (begin (set! x 2) (set! y x) (set! x 3)))",
+                &["x", "y"],
+            ),
+            ("(lambda (x) (set! x (lambda () (set! y 1))))", &["x", "y"]),
+            ("(define (f x) (set! x 1)) (f 1)", &["x"]),
+        ];
+
+        for (source, expected) in &cases {
+            let expression = source.parse::<Expression>().unwrap();
+            assert_eq!(assigned_variables(&expression), *expected);
+        }
+    }
+
+    #[test]
+    fn conversion() {
+        let cases = [
+            ("(let ((x 1)) x)", "(let ((x 1)) x)"),
+            (
+                "(let ((x 1)) (begin (set! x 2) x))",
+                "(let ((x (box 1))) (begin (set-box! x 2) (unbox x)))",
+            ),
+            (
+                "(lambda (x y) (begin (set! x y) x))",
+                "(lambda (x y) (let ((x (box x))) (begin (set-box! x y) (unbox x))))",
+            ),
+            (
+                "(let ((x 1)) (let ((x (fx+ x 1))) (set! x x)))",
+                "(let ((x (box 1))) (let ((x (box (fx+ (unbox x) 1)))) (set-box! x (unbox x))))",
+            ),
+            (
+                "(define (f x) (set! x 1)) (f 1)",
+                "(labels ((f (code (x) () (let ((x (box x))) (set-box! x 1))))) (f 1))",
+            ),
+            ("(set! x 1)", "(set! x 1)"),
+        ];
+
+        for (source, expected) in &cases {
+            let expression = source.parse::<Expression>().unwrap();
+            assert_eq!(convert(expression).to_string(), *expected);
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use super::expression::{
+    binding::{Binding, Let, LetKind, Set},
+    call::Call,
+    conditional::If,
+    procedure::{Application, Closure, Code, Label, LabelCall, Labels, Lambda},
+    Expression,
+};
+
+/// Returns the variables referenced but not bound by `expression`, in order
+/// of first occurrence.
+pub fn free_variables(expression: &Expression) -> Vec<String> {
+    let mut free = Vec::new();
+    collect(expression, &mut Vec::new(), &mut free);
+    free
+}
+
+fn collect(expression: &Expression, bound: &mut Vec<String>, free: &mut Vec<String>) {
+    match expression {
+        Expression::Application(Application {
+            function,
+            arguments,
+        }) => {
+            collect(function, bound, free);
+            for argument in arguments {
+                collect(argument, bound, free);
+            }
+        }
+        Expression::Call(Call { arguments, .. })
+        | Expression::LabelCall(LabelCall { arguments, .. }) => {
+            for argument in arguments {
+                collect(argument, bound, free);
+            }
+        }
+        Expression::Closure(Closure { free: names, .. }) => {
+            for name in names {
+                reference(name, bound, free);
+            }
+        }
+        Expression::If(If {
+            condition,
+            consequent,
+            alternative,
+        }) => {
+            collect(condition, bound, free);
+            collect(consequent, bound, free);
+            collect(alternative, bound, free);
+        }
+        Expression::Bignum(_)
+        | Expression::Flonum(_)
+        | Expression::Immediate(_)
+        | Expression::Rational(..)
+        | Expression::String(_) => {}
+        Expression::Labels(Labels { labels, body }) => {
+            for Label { code, .. } in labels {
+                let depth = bound.len();
+                bound.extend(code.parameters.iter().cloned());
+                collect(&code.body, bound, free);
+                bound.truncate(depth);
+            }
+            collect(body, bound, free);
+        }
+        Expression::Lambda(Lambda { parameters, body }) => {
+            let depth = bound.len();
+            bound.extend(parameters.iter().cloned());
+            collect(body, bound, free);
+            bound.truncate(depth);
+        }
+        Expression::Let(Let {
+            kind,
+            bindings,
+            body,
+        }) => {
+            let depth = bound.len();
+            for Binding { name, expression } in bindings {
+                match kind {
+                    LetKind::Let => {
+                        let inner = bound.split_off(depth);
+                        collect(expression, bound, free);
+                        bound.extend(inner);
+                    }
+                    LetKind::LetStar => collect(expression, bound, free),
+                }
+                bound.push(name.clone());
+            }
+            collect(body, bound, free);
+            bound.truncate(depth);
+        }
+        Expression::Set(Set { name, value }) => {
+            reference(name, bound, free);
+            collect(value, bound, free);
+        }
+        Expression::Variable(name) => reference(name, bound, free),
+        Expression::Begin(expressions) | Expression::Vector(expressions) => {
+            for expression in expressions {
+                collect(expression, bound, free);
+            }
+        }
+    }
+}
+
+fn reference(name: &str, bound: &[String], free: &mut Vec<String>) {
+    if !bound.iter().any(|bound| bound == name) && !free.iter().any(|free| free == name) {
+        free.push(name.to_string());
+    }
+}
+
+/// Lifts every `lambda` in the program into a top-level label, leaving a
+/// `closure` form that captures its free variables in its place.
+///
+/// Names that are not bound locally at the point of a `lambda` refer to
+/// labels, so they are not captured.
+pub fn convert(expression: Expression) -> Labels {
+    let (labels, body) = match expression {
+        Expression::Labels(Labels { labels, body }) => (labels, *body),
+        expression => (Vec::new(), expression),
+    };
+
+    let mut lifted = Vec::new();
+    let mut converted = Vec::new();
+
+    for Label { name, code } in labels {
+        let Code {
+            parameters,
+            free,
+            body,
+        } = code;
+        let mut bound = parameters.clone();
+        bound.extend(free.iter().cloned());
+        let body = lift(*body, &mut bound, &mut lifted);
+
+        converted.push(Label::new(
+            name,
+            Code {
+                parameters,
+                free,
+                body: Box::new(body),
+            },
+        ));
+    }
+
+    let body = lift(body, &mut Vec::new(), &mut lifted);
+    converted.extend(lifted);
+
+    Labels::new(converted, body)
+}
+
+fn lift(expression: Expression, bound: &mut Vec<String>, lifted: &mut Vec<Label>) -> Expression {
+    match expression {
+        Expression::Application(Application {
+            function,
+            arguments,
+        }) => Expression::Application(Application::new(
+            lift(*function, bound, lifted),
+            lift_all(arguments, bound, lifted),
+        )),
+        Expression::Begin(expressions) => Expression::Begin(lift_all(expressions, bound, lifted)),
+        Expression::Call(Call {
+            primitive,
+            arguments,
+        }) => Expression::Call(Call::new(primitive, lift_all(arguments, bound, lifted))),
+        Expression::If(If {
+            condition,
+            consequent,
+            alternative,
+        }) => Expression::If(If::new(
+            lift(*condition, bound, lifted),
+            lift(*consequent, bound, lifted),
+            lift(*alternative, bound, lifted),
+        )),
+        Expression::LabelCall(LabelCall { label, arguments }) => {
+            Expression::LabelCall(LabelCall::new(label, lift_all(arguments, bound, lifted)))
+        }
+        Expression::Lambda(Lambda { parameters, body }) => {
+            let mut free = Vec::new();
+            collect(&body, &mut parameters.clone(), &mut free);
+            free.retain(|name| bound.contains(name));
+
+            let mut inner = parameters.clone();
+            inner.extend(free.iter().cloned());
+            let body = lift(*body, &mut inner, lifted);
+
+            let name = format!("lambda {}", lifted.len());
+            lifted.push(Label::new(
+                &name,
+                Code {
+                    parameters,
+                    free: free.clone(),
+                    body: Box::new(body),
+                },
+            ));
+
+            Expression::Closure(Closure::new(name, free))
+        }
+        Expression::Let(Let {
+            kind,
+            bindings,
+            body,
+        }) => {
+            let depth = bound.len();
+            let mut converted = Vec::new();
+            for Binding { name, expression } in bindings {
+                let expression = match kind {
+                    LetKind::Let => {
+                        let inner = bound.split_off(depth);
+                        let expression = lift(expression, bound, lifted);
+                        bound.extend(inner);
+                        expression
+                    }
+                    LetKind::LetStar => lift(expression, bound, lifted),
+                };
+                bound.push(name.clone());
+                converted.push(Binding::new(name, expression));
+            }
+            let body = lift(*body, bound, lifted);
+            bound.truncate(depth);
+
+            Expression::Let(Let::new(kind, converted, body))
+        }
+        Expression::Set(Set { name, value }) => {
+            Expression::Set(Set::new(name, lift(*value, bound, lifted)))
+        }
+        Expression::Vector(elements) => Expression::Vector(lift_all(elements, bound, lifted)),
+        expression => expression,
+    }
+}
+
+fn lift_all(
+    expressions: Vec<Expression>,
+    bound: &mut Vec<String>,
+    lifted: &mut Vec<Label>,
+) -> Vec<Expression> {
+    expressions
+        .into_iter()
+        .map(|expression| lift(expression, bound, lifted))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn free() {
+        let cases: [(&str, &[&str]); 8] = [
+            ("1", &[]),
+            ("x", &["x"]),
+            ("(fx+ x y)", &["x", "y"]),
+            ("(lambda (x) (fx+ x y))", &["y"]),
+            ("(let ((x 1)) (fx+ x y))", &["y"]),
+            ("(let ((x 1) (y x)) y)", &["x"]),
+            ("(let* ((x 1) (y x)) y)", &[]),
+            ("(f (lambda (f) (f x)) x)", &["f", "x"]),
+        ];
+
+        for (source, expected) in &cases {
+            let expression = source.parse::<Expression>().unwrap();
+            assert_eq!(free_variables(&expression), *expected);
+        }
+    }
+
+    #[test]
+    fn conversion() {
+        let cases = [
+            ("(f 1)", "(labels () (f 1))"),
+            (
+                "(lambda (x) x)",
+                "(labels ((lambda 0 (code (x) () x))) (closure lambda 0))",
+            ),
+            (
+                "(let ((y 1)) (lambda (x) (fx+ x y)))",
+                "(labels ((lambda 0 (code (x) (y) (fx+ x y)))) (let ((y 1)) (closure lambda 0 y)))",
+            ),
+            (
+                "(let ((y 1)) (lambda (x) (lambda () (fx+ x y))))",
+                "(labels ((lambda 0 (code () (x y) (fx+ x y))) (lambda 1 (code (x) (y) (closure lambda 0 x y)))) (let ((y 1)) (closure lambda 1 y)))",
+            ),
+            (
+                "(define (f x) (lambda () (f x))) (f 1)",
+                "(labels ((f (code (x) () (closure lambda 0 x))) (lambda 0 (code () (x) (f x)))) (f 1))",
+            ),
+        ];
+
+        for (source, expected) in &cases {
+            let expression = source.parse::<Expression>().unwrap();
+            assert_eq!(convert(expression).to_string(), *expected);
+        }
+    }
+}
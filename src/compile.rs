@@ -1,143 +1,1814 @@
-use std::fmt::Write;
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    error::Error,
+    fmt::{self, Write},
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use super::{
+    assignment, closure, diagnostic,
     expression::{
-        call::{Call, UnaryPrimitive},
+        bignum::Bignum,
+        binding::{Let, LetKind, Set},
+        call::{BinaryPrimitive, Call, Primitive, TernaryPrimitive, UnaryPrimitive},
+        conditional::If,
         immediate::{
             Immediate, BOOLEAN_BIT, BOOLEAN_FALSE, BOOLEAN_MASK, CHARACTER_MASK, CHARACTER_SHIFT,
-            CHARACTER_TAG, INTEGER_MASK, INTEGER_SHIFT, INTEGER_TAG, NULL,
+            CHARACTER_TAG, FIXNUM_MAX, FIXNUM_MIN, INTEGER_MASK, INTEGER_SHIFT, INTEGER_TAG, NULL,
         },
+        procedure::{Application, Closure, Code, Label, LabelCall, Labels},
+        program::Program,
         Expression,
     },
-    parse::ParseError,
+    parse::{ParseError, ParseErrorKind},
+    syntax,
+    target::Target,
 };
 
 #[derive(Debug)]
 pub enum CompilationError {
-    LexicalError,
-    ParseError,
+    LexicalError(Range<usize>),
+    ParseError(ParseError),
+    UnboundVariable(String),
+    ArityMismatch(String),
+    InvalidAssignment(String),
     Other,
 }
 
+impl CompilationError {
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            CompilationError::LexicalError(span) => Some(span.clone()),
+            CompilationError::ParseError(error) => Some(error.span.clone()),
+            _ => None,
+        }
+    }
+
+    /// Formats the error for display, quoting the part of `source` it refers
+    /// to.
+    pub fn render(&self, source: &str) -> String {
+        diagnostic::render(source, &self.to_string(), self.span())
+    }
+}
+
 impl From<ParseError> for CompilationError {
-    fn from(_value: ParseError) -> Self {
-        CompilationError::ParseError
+    fn from(error: ParseError) -> Self {
+        match error.kind {
+            ParseErrorKind::UnrecognizedToken => CompilationError::LexicalError(error.span),
+            _ => CompilationError::ParseError(error),
+        }
     }
 }
 
-pub fn compile(expression: Expression) -> Result<String, CompilationError> {
+impl fmt::Display for CompilationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompilationError::LexicalError(_) => f.write_str("unrecognized token"),
+            CompilationError::ParseError(error) => write!(f, "{}", error),
+            CompilationError::UnboundVariable(name) => write!(f, "unbound variable `{}`", name),
+            CompilationError::ArityMismatch(name) => {
+                write!(f, "wrong number of arguments to `{}`", name)
+            }
+            CompilationError::InvalidAssignment(name) => {
+                write!(f, "cannot assign to procedure `{}`", name)
+            }
+            CompilationError::Other => f.write_str("unsupported expression"),
+        }
+    }
+}
+
+impl Error for CompilationError {}
+
+/// Whether generated code checks the types of the arguments of primitives,
+/// the number of arguments procedures are applied to and the indices of
+/// vectors and strings. Safe code reports a violation and exits; unsafe code
+/// is faster and does whatever the machine does with it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Safety {
+    Safe,
+    Unsafe,
+}
+
+const WORD_SIZE: i32 = 8;
+
+// The size of the heap the runtime allocates, which the allocation pointer
+// counts up to.
+const HEAP_SIZE: i32 = 64 * 1024 * 1024;
+
+const VALUE_SIZE: i32 = 8;
+// The size a fixnum index counts in, which is that of the word it is
+// shifted into.
+const FIXNUM_SIZE: i32 = 1 << INTEGER_SHIFT;
+
+const OBJECT_MASK: i32 = 0x07;
+const OBJECT_ALIGNMENT: i32 = 8;
+
+const PAIR_TAG: i32 = 0x01;
+const PAIR_SIZE: i32 = 16;
+const CAR_OFFSET: i32 = 0;
+const CDR_OFFSET: i32 = 8;
+
+const VECTOR_TAG: i32 = 0x05;
+const STRING_TAG: i32 = 0x06;
+const LENGTH_OFFSET: i32 = 0;
+const DATA_OFFSET: i32 = 8;
+// Strings hold characters as 32-bit code points, which are as large as the
+// size a fixnum index counts in, so the index is their offset.
+const CHARACTER_SIZE: i32 = 4;
+
+// Numbers other than fixnums share a tag, and the low byte of their header
+// says which kind they are. A bignum has its number of limbs, negated for
+// negative numbers, in the rest of the header, and the limbs of its magnitude
+// after it. A flonum is an IEEE double after the header, and a ratnum is an
+// integer numerator and a denominator greater than one, in lowest terms.
+const NUMBER_TAG: i32 = 0x03;
+const HEADER_OFFSET: i32 = 0;
+const KIND_SHIFT: i32 = 8;
+const BIGNUM_KIND: i32 = 0x01;
+const FLONUM_KIND: i32 = 0x02;
+const LIMB_SIZE: i32 = 4;
+const FLONUM_SIZE: i32 = 16;
+
+// Boxes are never values of the program, so they share the pair tag.
+const BOX_TAG: i32 = PAIR_TAG;
+const BOX_SIZE: i32 = 8;
+
+const CLOSURE_TAG: i32 = 0x02;
+const CODE_OFFSET: i32 = 0;
+const FREE_OFFSET: i32 = 8;
+
+// Slot of the closure being executed, relative to the frame.
+const CLOSURE_INDEX: i32 = -WORD_SIZE;
+
+// The functions of the runtime that generated code calls.
+const RUNTIME_FUNCTIONS: &[&str] = &[
+    "scheme_add",
+    "scheme_subtract",
+    "scheme_multiply",
+    "scheme_divide",
+    "scheme_quotient",
+    "scheme_remainder",
+    "scheme_inexact",
+    "scheme_exact",
+    "scheme_numerator",
+    "scheme_denominator",
+    "scheme_type_error",
+    "scheme_index_error",
+    "scheme_arity_error",
+    "scheme_heap_error",
+];
+
+#[derive(Clone)]
+enum Location {
+    Stack(i32),
+    Free(i32),
+    Label(String, usize),
+}
+
+type Environment = HashMap<String, Location>;
+
+static LABEL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn unique_label() -> String {
+    format!("L{}", LABEL_COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+pub fn compile(
+    expression: Expression,
+    target: Target,
+    safety: Safety,
+) -> Result<String, CompilationError> {
     let mut output = String::new();
+    let mut environment = Environment::new();
+
+    let Labels { labels, body } = closure::convert(assignment::convert(expression));
+    let labels: Vec<(String, Label)> = labels
+        .into_iter()
+        .map(|label| (unique_label(), label))
+        .collect();
 
+    for (code_label, label) in &labels {
+        environment.insert(
+            label.name.clone(),
+            Location::Label(code_label.clone(), label.code.parameters.len()),
+        );
+    }
+
+    target.emit_text_section(&mut output);
+    for (code_label, label) in labels {
+        emit_code(&mut output, &code_label, &environment, safety, label.code)?;
+    }
+
+    // Runtime functions are called through local labels, which keep the
+    // target's names for C symbols out of the rest of the code.
+    for function in RUNTIME_FUNCTIONS {
+        write!(
+            output,
+            "L{}:\n\
+            \tjmp {}\n",
+            function,
+            target.symbol(function)
+        )
+        .unwrap();
+    }
+
+    // Heap values are offsets from the heap base in %r12, and %r13
+    // holds the offset of the next free byte. Both registers are
+    // callee-saved, so the caller's values are kept on the stack.
+    let entry = target.symbol("scheme_entry");
+    target.emit_function_start(&mut output, &entry);
     write!(
         output,
-        ".globl _scheme_entry\n\
-        _scheme_entry:\n"
+        "\tpushq %r12\n\
+        \tpushq %r13\n\
+        \tmovq %rdi, %r12\n\
+        \txorq %r13, %r13\n"
     )
     .unwrap();
-    emit_expression(&mut output, expression)?;
-    writeln!(output, "\tret").unwrap();
+    emit_expression(&mut output, -WORD_SIZE, &environment, safety, *body)?;
+    write!(
+        output,
+        "\tpopq %r13\n\
+        \tpopq %r12\n\
+        \tret\n"
+    )
+    .unwrap();
+    target.emit_function_end(&mut output, &entry);
+    target.emit_end(&mut output);
 
     Ok(output)
 }
 
-pub fn run(source: &str) -> Result<String, CompilationError> {
-    source
-        .parse()
-        .map_err(CompilationError::from)
-        .and_then(compile)
+/// Compiles a whole program; `scheme_entry` returns the value of its last
+/// expression.
+pub fn compile_program(
+    program: Program,
+    target: Target,
+    safety: Safety,
+) -> Result<String, CompilationError> {
+    compile(program.into_expression(), target, safety)
 }
 
-fn emit_expression(output: &mut String, expression: Expression) -> Result<(), CompilationError> {
+pub fn run(source: &str, target: Target, safety: Safety) -> Result<String, CompilationError> {
+    let program = syntax::expand(source).map_err(CompilationError::from)?;
+    compile_program(program, target, safety)
+}
+
+fn emit_code(
+    output: &mut String,
+    label: &str,
+    environment: &Environment,
+    safety: Safety,
+    Code {
+        parameters,
+        free,
+        body,
+    }: Code,
+) -> Result<(), CompilationError> {
+    let mut environment = environment.clone();
+    let mut stack_index = CLOSURE_INDEX - WORD_SIZE;
+    let arity = parameters.len();
+
+    for (i, name) in free.into_iter().enumerate() {
+        environment.insert(name, Location::Free(i as i32));
+    }
+
+    for parameter in parameters {
+        environment.insert(parameter, Location::Stack(stack_index));
+        stack_index -= WORD_SIZE;
+    }
+
+    writeln!(output, "{}:", label).unwrap();
+    if safety == Safety::Safe {
+        emit_arity_check(output, stack_index, arity);
+    }
+    emit_tail_expression(output, stack_index, &environment, safety, *body)
+}
+
+// Expressions in tail position are the body of a procedure and, recursively,
+// the branches of a conditional or the body of a `let` in tail position. Calls
+// there replace the current frame instead of pushing a new one, so loops
+// written as tail recursion run in constant stack space.
+fn emit_tail_expression(
+    output: &mut String,
+    stack_index: i32,
+    environment: &Environment,
+    safety: Safety,
+    expression: Expression,
+) -> Result<(), CompilationError> {
+    match expression {
+        Expression::Begin(expressions) => {
+            emit_begin(output, stack_index, environment, safety, expressions, true)
+        }
+        Expression::If(conditional) => {
+            emit_if(output, stack_index, environment, safety, conditional, true)
+        }
+        Expression::Application(application) => {
+            emit_application(output, stack_index, environment, safety, application, true)
+        }
+        Expression::LabelCall(call) => {
+            emit_label_call(output, stack_index, environment, safety, call, true)
+        }
+        Expression::Let(binding) => {
+            emit_let(output, stack_index, environment, safety, binding, true)
+        }
+        expression => {
+            emit_expression(output, stack_index, environment, safety, expression)?;
+            writeln!(output, "\tret").unwrap();
+            Ok(())
+        }
+    }
+}
+
+fn emit_expression(
+    output: &mut String,
+    stack_index: i32,
+    environment: &Environment,
+    safety: Safety,
+    expression: Expression,
+) -> Result<(), CompilationError> {
     match expression {
         Expression::Call(Call {
             primitive,
-            expression,
-        }) => {
-            use UnaryPrimitive::*;
+            arguments,
+        }) => match primitive {
+            Primitive::Unary(primitive) => emit_unary_call(
+                output,
+                stack_index,
+                environment,
+                safety,
+                primitive,
+                arguments,
+            )?,
+            Primitive::Binary(primitive) => emit_binary_call(
+                output,
+                stack_index,
+                environment,
+                safety,
+                primitive,
+                arguments,
+            )?,
+            Primitive::Ternary(primitive) => emit_ternary_call(
+                output,
+                stack_index,
+                environment,
+                safety,
+                primitive,
+                arguments,
+            )?,
+        },
+        Expression::Application(application) => {
+            emit_application(output, stack_index, environment, safety, application, false)?
+        }
+        Expression::Begin(expressions) => {
+            emit_begin(output, stack_index, environment, safety, expressions, false)?
+        }
+        Expression::Bignum(bignum) => emit_bignum(output, bignum),
+        Expression::Flonum(flonum) => {
+            write!(
+                output,
+                "\tmovabsq ${}, %rax\n\
+                \tmovq %rax, %xmm0\n",
+                flonum.to_bits() as i64
+            )
+            .unwrap();
+            emit_flonum(output);
+        }
+        Expression::Closure(closure) => {
+            emit_closure(output, stack_index, environment, safety, closure)?
+        }
+        Expression::If(conditional) => {
+            emit_if(output, stack_index, environment, safety, conditional, false)?
+        }
+        // Only immediates that fit in 32 bits can be moved as they are.
+        Expression::Immediate(immediate) => match i64::from(immediate) {
+            value if i32::try_from(value).is_ok() => {
+                writeln!(output, "\tmovq ${}, %rax", value).unwrap()
+            }
+            value => writeln!(output, "\tmovabsq ${}, %rax", value).unwrap(),
+        },
+        Expression::LabelCall(call) => {
+            emit_label_call(output, stack_index, environment, safety, call, false)?
+        }
+        Expression::Rational(numerator, denominator) => emit_binary_call(
+            output,
+            stack_index,
+            environment,
+            safety,
+            BinaryPrimitive::Divide,
+            vec![integer(numerator), integer(denominator)],
+        )?,
+        Expression::Labels(_) | Expression::Lambda(_) => return Err(CompilationError::Other),
+        Expression::Let(binding) => {
+            emit_let(output, stack_index, environment, safety, binding, false)?
+        }
+        // Assignments to local variables have been converted to boxes.
+        Expression::Set(Set { name, .. }) => match environment.get(&name) {
+            Some(Location::Label(..)) => return Err(CompilationError::InvalidAssignment(name)),
+            Some(_) => return Err(CompilationError::Other),
+            None => return Err(CompilationError::UnboundVariable(name)),
+        },
+        Expression::String(string) => emit_string(output, string),
+        Expression::Variable(name) => match environment.get(&name) {
+            Some(Location::Stack(index)) => {
+                writeln!(output, "\tmovq {}(%rsp), %rax", index).unwrap()
+            }
+            Some(Location::Free(index)) => write!(
+                output,
+                "\tmovq {}(%rsp), %rax\n\
+                \tmovq {}(%r12,%rax), %rax\n",
+                CLOSURE_INDEX,
+                FREE_OFFSET + index * VALUE_SIZE - CLOSURE_TAG
+            )
+            .unwrap(),
+            Some(Location::Label(..)) => emit_closure(
+                output,
+                stack_index,
+                environment,
+                safety,
+                Closure::new(name, Vec::new()),
+            )?,
+            None => return Err(CompilationError::UnboundVariable(name)),
+        },
+        Expression::Vector(elements) => {
+            emit_vector(output, stack_index, environment, safety, elements)?
+        }
+    }
 
-            emit_expression(output, *expression)?;
+    Ok(())
+}
 
-            let one = i32::from(Immediate::Integer(1));
+fn integer(bignum: Bignum) -> Expression {
+    match bignum.to_i64() {
+        Some(integer) if (FIXNUM_MIN..=FIXNUM_MAX).contains(&integer) => {
+            Expression::Immediate(Immediate::Integer(integer))
+        }
+        _ => Expression::Bignum(bignum),
+    }
+}
 
-            match primitive {
-                FxAdd1 => writeln!(output, "\taddl ${}, %eax", one).unwrap(),
-                FxSub1 => writeln!(output, "\tsubl ${}, %eax", one).unwrap(),
-                FxLogNot => write!(
-                    output,
-                    "\tshr ${}, %eax\n\
-                    \tnot %eax\n
-                    \tshl ${}, %eax\n",
-                    INTEGER_SHIFT, INTEGER_SHIFT
-                )
-                .unwrap(),
-                FixnumToChar => write!(
+fn aligned(size: i32) -> i32 {
+    (size + OBJECT_ALIGNMENT - 1) & -OBJECT_ALIGNMENT
+}
+
+fn emit_string(output: &mut String, string: String) {
+    let length = i32::try_from(string.chars().count()).unwrap();
+
+    writeln!(
+        output,
+        "\tmovq ${}, {}(%r12,%r13)",
+        i64::from(Immediate::Integer(length.into())),
+        LENGTH_OFFSET
+    )
+    .unwrap();
+    for (i, character) in string.chars().enumerate() {
+        writeln!(
+            output,
+            "\tmovl ${}, {}(%r12,%r13)",
+            u32::from(character),
+            DATA_OFFSET + i as i32 * CHARACTER_SIZE
+        )
+        .unwrap();
+    }
+    write!(
+        output,
+        "\tmovq %r13, %rax\n\
+        \torq ${}, %rax\n\
+        \taddq ${}, %r13\n",
+        STRING_TAG,
+        aligned(DATA_OFFSET + length * CHARACTER_SIZE)
+    )
+    .unwrap();
+}
+
+fn emit_bignum(output: &mut String, Bignum { negative, limbs }: Bignum) {
+    let length = i32::try_from(limbs.len()).unwrap();
+    let signed = if negative { -length } else { length };
+
+    writeln!(
+        output,
+        "\tmovq ${}, {}(%r12,%r13)",
+        signed << KIND_SHIFT | BIGNUM_KIND,
+        HEADER_OFFSET
+    )
+    .unwrap();
+    for (i, limb) in limbs.into_iter().enumerate() {
+        writeln!(
+            output,
+            "\tmovl ${}, {}(%r12,%r13)",
+            limb,
+            DATA_OFFSET + i as i32 * LIMB_SIZE
+        )
+        .unwrap();
+    }
+    write!(
+        output,
+        "\tmovq %r13, %rax\n\
+        \torq ${}, %rax\n\
+        \taddq ${}, %r13\n",
+        NUMBER_TAG,
+        aligned(DATA_OFFSET + length * LIMB_SIZE)
+    )
+    .unwrap();
+}
+
+// Allocates a flonum holding %xmm0.
+fn emit_flonum(output: &mut String) {
+    write!(
+        output,
+        "\tmovq ${}, {}(%r12,%r13)\n\
+        \tmovsd %xmm0, {}(%r12,%r13)\n\
+        \tmovq %r13, %rax\n\
+        \torq ${}, %rax\n\
+        \taddq ${}, %r13\n",
+        FLONUM_KIND, HEADER_OFFSET, DATA_OFFSET, NUMBER_TAG, FLONUM_SIZE
+    )
+    .unwrap();
+}
+
+fn emit_vector(
+    output: &mut String,
+    stack_index: i32,
+    environment: &Environment,
+    safety: Safety,
+    elements: Vec<Expression>,
+) -> Result<(), CompilationError> {
+    let length = i32::try_from(elements.len()).unwrap();
+
+    write!(
+        output,
+        "\tmovq ${}, {}(%r12,%r13)\n\
+        \tmovq %r13, %rax\n\
+        \torq ${}, %rax\n\
+        \taddq ${}, %r13\n\
+        \tmovq %rax, {}(%rsp)\n",
+        i64::from(Immediate::Integer(length.into())),
+        LENGTH_OFFSET,
+        VECTOR_TAG,
+        aligned(DATA_OFFSET + length * VALUE_SIZE),
+        stack_index
+    )
+    .unwrap();
+    for (i, element) in elements.into_iter().enumerate() {
+        emit_expression(
+            output,
+            stack_index - WORD_SIZE,
+            environment,
+            safety,
+            element,
+        )?;
+        write!(
+            output,
+            "\tmovq {}(%rsp), %rcx\n\
+            \tmovq %rax, {}(%r12,%rcx)\n",
+            stack_index,
+            DATA_OFFSET + i as i32 * VALUE_SIZE - VECTOR_TAG
+        )
+        .unwrap();
+    }
+    writeln!(output, "\tmovq {}(%rsp), %rax", stack_index).unwrap();
+
+    Ok(())
+}
+
+// Takes a negative length in %rax as zero, so that allocating an object of
+// that length never moves the allocation pointer back over live objects. The
+// mask is all ones unless the sign bit is set.
+fn emit_length(output: &mut String) {
+    write!(
+        output,
+        "\tmovq %rax, %rcx\n\
+        \tsarq $63, %rcx\n\
+        \tnotq %rcx\n\
+        \tandq %rcx, %rax\n"
+    )
+    .unwrap();
+}
+
+fn emit_allocation(output: &mut String, tag: i32) {
+    write!(
+        output,
+        "\tmovq %r13, %rax\n\
+        \torq ${}, %rax\n\
+        \taddq ${}, %rdx\n\
+        \tandq ${}, %rdx\n\
+        \taddq %rdx, %r13\n",
+        tag,
+        DATA_OFFSET + OBJECT_ALIGNMENT - 1,
+        -OBJECT_ALIGNMENT
+    )
+    .unwrap();
+}
+
+fn emit_if(
+    output: &mut String,
+    stack_index: i32,
+    environment: &Environment,
+    safety: Safety,
+    If {
+        condition,
+        consequent,
+        alternative,
+    }: If,
+    tail: bool,
+) -> Result<(), CompilationError> {
+    let alternative_label = unique_label();
+    let end_label = unique_label();
+
+    emit_expression(output, stack_index, environment, safety, *condition)?;
+    write!(
+        output,
+        "\tcmpq ${}, %rax\n\
+        \tje {}\n",
+        BOOLEAN_FALSE, alternative_label
+    )
+    .unwrap();
+    emit_body(output, stack_index, environment, safety, *consequent, tail)?;
+
+    // Both branches of a conditional in tail position end in a return or a
+    // jump, so there is nothing to rejoin.
+    if tail {
+        writeln!(output, "{}:", alternative_label).unwrap();
+        return emit_body(output, stack_index, environment, safety, *alternative, tail);
+    }
+
+    write!(
+        output,
+        "\tjmp {}\n\
+        {}:\n",
+        end_label, alternative_label
+    )
+    .unwrap();
+    emit_body(output, stack_index, environment, safety, *alternative, tail)?;
+    writeln!(output, "{}:", end_label).unwrap();
+
+    Ok(())
+}
+
+fn emit_let(
+    output: &mut String,
+    mut stack_index: i32,
+    environment: &Environment,
+    safety: Safety,
+    Let {
+        kind,
+        bindings,
+        body,
+    }: Let,
+    tail: bool,
+) -> Result<(), CompilationError> {
+    let mut extended = environment.clone();
+
+    for binding in bindings {
+        let scope = match kind {
+            LetKind::Let => environment,
+            LetKind::LetStar => &extended,
+        };
+
+        emit_expression(output, stack_index, scope, safety, binding.expression)?;
+        writeln!(output, "\tmovq %rax, {}(%rsp)", stack_index).unwrap();
+        extended.insert(binding.name, Location::Stack(stack_index));
+        stack_index -= WORD_SIZE;
+    }
+
+    emit_body(output, stack_index, &extended, safety, *body, tail)
+}
+
+// The values of all but the last expression are discarded.
+fn emit_begin(
+    output: &mut String,
+    stack_index: i32,
+    environment: &Environment,
+    safety: Safety,
+    mut expressions: Vec<Expression>,
+    tail: bool,
+) -> Result<(), CompilationError> {
+    let last = expressions.pop().ok_or(CompilationError::Other)?;
+
+    for expression in expressions {
+        emit_expression(output, stack_index, environment, safety, expression)?;
+    }
+
+    emit_body(output, stack_index, environment, safety, last, tail)
+}
+
+fn emit_body(
+    output: &mut String,
+    stack_index: i32,
+    environment: &Environment,
+    safety: Safety,
+    expression: Expression,
+    tail: bool,
+) -> Result<(), CompilationError> {
+    if tail {
+        emit_tail_expression(output, stack_index, environment, safety, expression)
+    } else {
+        emit_expression(output, stack_index, environment, safety, expression)
+    }
+}
+
+// A frame holds the return address, then the closure being called, then the
+// arguments. Callers build the frame below their own live slots; a call in
+// tail position instead moves it over the current frame and jumps, reusing
+// the caller's return address. Safe code also passes the number of arguments
+// in %rcx.
+fn emit_label_call(
+    output: &mut String,
+    stack_index: i32,
+    environment: &Environment,
+    safety: Safety,
+    LabelCall { label, arguments }: LabelCall,
+    tail: bool,
+) -> Result<(), CompilationError> {
+    let (target, arity) = match environment.get(&label) {
+        Some(Location::Label(target, arity)) => (target.clone(), *arity),
+        _ => return Err(CompilationError::UnboundVariable(label)),
+    };
+
+    if arguments.len() != arity {
+        return Err(CompilationError::ArityMismatch(label));
+    }
+
+    let count = arguments.len() as i32;
+
+    emit_arguments(output, stack_index, environment, safety, arguments)?;
+    if safety == Safety::Safe {
+        writeln!(output, "\tmovq ${}, %rcx", count).unwrap();
+    }
+
+    if tail {
+        emit_frame_move(output, stack_index, 2, count + 1);
+        writeln!(output, "\tjmp {}", target).unwrap();
+    } else {
+        write!(
+            output,
+            "\taddq ${}, %rsp\n\
+            \tcall {}\n\
+            \tsubq ${}, %rsp\n",
+            stack_index + WORD_SIZE,
+            target,
+            stack_index + WORD_SIZE
+        )
+        .unwrap();
+    }
+
+    Ok(())
+}
+
+fn emit_application(
+    output: &mut String,
+    stack_index: i32,
+    environment: &Environment,
+    safety: Safety,
+    Application {
+        function,
+        arguments,
+    }: Application,
+    tail: bool,
+) -> Result<(), CompilationError> {
+    if let Expression::Variable(name) = &*function {
+        if let Some(Location::Label(..)) = environment.get(name) {
+            let call = LabelCall::new(name.clone(), arguments);
+            return emit_label_call(output, stack_index, environment, safety, call, tail);
+        }
+    }
+
+    let count = arguments.len() as i32;
+
+    emit_expression(
+        output,
+        stack_index - WORD_SIZE,
+        environment,
+        safety,
+        *function,
+    )?;
+    if safety == Safety::Safe {
+        emit_check(
+            output,
+            stack_index - WORD_SIZE,
+            "%rax",
+            Type::Procedure,
+            "application",
+        );
+    }
+    writeln!(output, "\tmovq %rax, {}(%rsp)", stack_index - WORD_SIZE).unwrap();
+    emit_arguments(output, stack_index, environment, safety, arguments)?;
+    if safety == Safety::Safe {
+        writeln!(output, "\tmovq ${}, %rcx", count).unwrap();
+    }
+
+    if tail {
+        emit_frame_move(output, stack_index, 1, count + 1);
+        write!(
+            output,
+            "\tmovq {}(%rsp), %rax\n\
+            \tmovq {}(%r12,%rax), %rax\n\
+            \tjmp *%rax\n",
+            CLOSURE_INDEX,
+            CODE_OFFSET - CLOSURE_TAG
+        )
+        .unwrap();
+    } else {
+        write!(
+            output,
+            "\tmovq {}(%rsp), %rax\n\
+            \tmovq {}(%r12,%rax), %rax\n\
+            \taddq ${}, %rsp\n\
+            \tcall *%rax\n\
+            \tsubq ${}, %rsp\n",
+            stack_index - WORD_SIZE,
+            CODE_OFFSET - CLOSURE_TAG,
+            stack_index + WORD_SIZE,
+            stack_index + WORD_SIZE
+        )
+        .unwrap();
+    }
+
+    Ok(())
+}
+
+fn emit_arguments(
+    output: &mut String,
+    stack_index: i32,
+    environment: &Environment,
+    safety: Safety,
+    arguments: Vec<Expression>,
+) -> Result<(), CompilationError> {
+    for (i, argument) in arguments.into_iter().enumerate() {
+        let index = stack_index - WORD_SIZE * (i as i32 + 2);
+
+        emit_expression(output, index, environment, safety, argument)?;
+        writeln!(output, "\tmovq %rax, {}(%rsp)", index).unwrap();
+    }
+
+    Ok(())
+}
+
+// Moves slots `first..=last` of the frame being built at `stack_index` to the
+// same slots of the current frame.
+fn emit_frame_move(output: &mut String, stack_index: i32, first: i32, last: i32) {
+    for i in first..=last {
+        write!(
+            output,
+            "\tmovq {}(%rsp), %rax\n\
+            \tmovq %rax, {}(%rsp)\n",
+            stack_index - WORD_SIZE * i,
+            -WORD_SIZE * i
+        )
+        .unwrap();
+    }
+}
+
+// Closures hold the address of their code followed by the values of their
+// free variables.
+fn emit_closure(
+    output: &mut String,
+    stack_index: i32,
+    environment: &Environment,
+    safety: Safety,
+    Closure { label, free }: Closure,
+) -> Result<(), CompilationError> {
+    let target = match environment.get(&label) {
+        Some(Location::Label(target, _)) => target.clone(),
+        _ => return Err(CompilationError::UnboundVariable(label)),
+    };
+    let count = free.len() as i32;
+
+    write!(
+        output,
+        "\tmovq %r13, %rax\n\
+        \torq ${}, %rax\n\
+        \taddq ${}, %r13\n\
+        \tleaq {}(%rip), %rdx\n\
+        \tmovq %rdx, {}(%r12,%rax)\n\
+        \tmovq %rax, {}(%rsp)\n",
+        CLOSURE_TAG,
+        aligned(FREE_OFFSET + count * VALUE_SIZE),
+        target,
+        CODE_OFFSET - CLOSURE_TAG,
+        stack_index
+    )
+    .unwrap();
+    for (i, name) in free.into_iter().enumerate() {
+        emit_expression(
+            output,
+            stack_index - WORD_SIZE,
+            environment,
+            safety,
+            Expression::Variable(name),
+        )?;
+        write!(
+            output,
+            "\tmovq {}(%rsp), %rcx\n\
+            \tmovq %rax, {}(%r12,%rcx)\n",
+            stack_index,
+            FREE_OFFSET + i as i32 * VALUE_SIZE - CLOSURE_TAG
+        )
+        .unwrap();
+    }
+    writeln!(output, "\tmovq {}(%rsp), %rax", stack_index).unwrap();
+
+    Ok(())
+}
+
+fn emit_unary_call(
+    output: &mut String,
+    stack_index: i32,
+    environment: &Environment,
+    safety: Safety,
+    primitive: UnaryPrimitive,
+    arguments: Vec<Expression>,
+) -> Result<(), CompilationError> {
+    use UnaryPrimitive::*;
+
+    for argument in arguments {
+        emit_expression(output, stack_index, environment, safety, argument)?;
+    }
+
+    let expected = match primitive {
+        FxAdd1 | FxSub1 | FxLogNot | FixnumIsZero => Some(Type::Fixnum),
+        FixnumToChar => Some(Type::ScalarValue),
+        MakeVector | MakeString => Some(Type::Length),
+        CharToFixnum => Some(Type::Character),
+        Car | Cdr => Some(Type::Pair),
+        VectorLength => Some(Type::Vector),
+        StringLength => Some(Type::String),
+        FlSqrt => Some(Type::Flonum),
+        _ => None,
+    };
+    if let (Safety::Safe, Some(expected)) = (safety, expected) {
+        emit_check(
+            output,
+            stack_index,
+            "%rax",
+            expected,
+            &primitive.to_string(),
+        );
+    }
+
+    let one = i64::from(Immediate::Integer(1));
+
+    match primitive {
+        FxAdd1 => {
+            emit_operands(output, "%rax", &format!("${}", one));
+            emit_arithmetic(output, stack_index, BinaryPrimitive::FxAdd);
+        }
+        FxSub1 => {
+            emit_operands(output, "%rax", &format!("${}", one));
+            emit_arithmetic(output, stack_index, BinaryPrimitive::FxSub);
+        }
+        FxLogNot => write!(
+            output,
+            "\tshrq ${}, %rax\n\
+            \tnotq %rax\n\
+            \tshlq ${}, %rax\n",
+            INTEGER_SHIFT, INTEGER_SHIFT
+        )
+        .unwrap(),
+        FixnumToChar => write!(
+            output,
+            "\tshlq ${}, %rax\n\
+            \torq ${}, %rax\n",
+            CHARACTER_SHIFT - INTEGER_SHIFT,
+            CHARACTER_TAG
+        )
+        .unwrap(),
+        CharToFixnum => {
+            writeln!(output, "\tshrq ${}, %rax", CHARACTER_SHIFT - INTEGER_SHIFT).unwrap()
+        }
+        FixnumIsZero => {
+            writeln!(output, "\tcmp ${}, %rax", INTEGER_TAG).unwrap();
+            emit_comparison(output, "e");
+        }
+        IsNull => {
+            writeln!(output, "\tcmp ${}, %al", NULL).unwrap();
+            emit_comparison(output, "e");
+        }
+        Not => {
+            writeln!(output, "\tcmp ${}, %al", BOOLEAN_FALSE).unwrap();
+            emit_comparison(output, "e");
+        }
+        IsFixnum => {
+            write!(
+                output,
+                "\tand ${}, %al\n\
+                \tcmp ${}, %al\n",
+                INTEGER_MASK, INTEGER_TAG
+            )
+            .unwrap();
+            emit_comparison(output, "e");
+        }
+        IsBoolean => {
+            write!(
+                output,
+                "\tand ${}, %al\n\
+                \tcmp ${}, %al\n",
+                BOOLEAN_MASK, BOOLEAN_FALSE
+            )
+            .unwrap();
+            emit_comparison(output, "e");
+        }
+        IsCharacter => {
+            write!(
+                output,
+                "\tand ${}, %al\n\
+                \tcmp ${}, %al\n",
+                CHARACTER_MASK, CHARACTER_TAG
+            )
+            .unwrap();
+            emit_comparison(output, "e");
+        }
+        IsPair => {
+            write!(
+                output,
+                "\tand ${}, %al\n\
+                \tcmp ${}, %al\n",
+                OBJECT_MASK, PAIR_TAG
+            )
+            .unwrap();
+            emit_comparison(output, "e");
+        }
+        Car => writeln!(output, "\tmovq {}(%r12,%rax), %rax", CAR_OFFSET - PAIR_TAG).unwrap(),
+        Cdr => writeln!(output, "\tmovq {}(%r12,%rax), %rax", CDR_OFFSET - PAIR_TAG).unwrap(),
+        MakeVector => {
+            emit_length(output);
+            if safety == Safety::Safe {
+                emit_heap_check(output, stack_index, VALUE_SIZE / FIXNUM_SIZE, "make-vector");
+            }
+            write!(
+                output,
+                "\tmovq %rax, {}(%r12,%r13)\n\
+                \timulq ${}, %rax, %rdx\n",
+                LENGTH_OFFSET,
+                VALUE_SIZE / FIXNUM_SIZE
+            )
+            .unwrap();
+            emit_allocation(output, VECTOR_TAG);
+        }
+        VectorLength => writeln!(
+            output,
+            "\tmovq {}(%r12,%rax), %rax",
+            LENGTH_OFFSET - VECTOR_TAG
+        )
+        .unwrap(),
+        IsVector => {
+            write!(
+                output,
+                "\tand ${}, %al\n\
+                \tcmp ${}, %al\n",
+                OBJECT_MASK, VECTOR_TAG
+            )
+            .unwrap();
+            emit_comparison(output, "e");
+        }
+        MakeString => {
+            emit_length(output);
+            if safety == Safety::Safe {
+                emit_heap_check(
                     output,
-                    "\tshll ${}, %eax\n\
-                    \torl ${}, %eax\n",
-                    CHARACTER_SHIFT - INTEGER_SHIFT,
-                    CHARACTER_TAG
-                )
-                .unwrap(),
-                CharToFixnum => {
-                    writeln!(output, "\tshrl ${}, %eax", CHARACTER_SHIFT - INTEGER_SHIFT).unwrap()
-                }
-                FixnumIsZero => {
-                    writeln!(output, "\tcmp ${}, %rax", INTEGER_TAG).unwrap();
-                    emit_comparison(output);
-                }
-                IsNull => {
-                    writeln!(output, "\tcmp ${}, %al", NULL).unwrap();
-                    emit_comparison(output);
-                }
-                Not => {
-                    writeln!(output, "\tcmp ${}, %al", BOOLEAN_FALSE).unwrap();
-                    emit_comparison(output);
-                }
-                IsFixnum => {
-                    write!(
-                        output,
-                        "\tand ${}, %al\n\
-                        \tcmp ${}, %al\n",
-                        INTEGER_MASK, INTEGER_TAG
-                    )
-                    .unwrap();
-                    emit_comparison(output);
-                }
-                IsBoolean => {
-                    write!(
-                        output,
-                        "\tand ${}, %al\n\
-                        \tcmp ${}, %al\n",
-                        BOOLEAN_MASK, BOOLEAN_FALSE
-                    )
-                    .unwrap();
-                    emit_comparison(output);
-                }
-                IsCharacter => {
-                    write!(
-                        output,
-                        "\tand ${}, %al\n\
-                        \tcmp ${}, %al\n",
-                        CHARACTER_MASK, CHARACTER_TAG
-                    )
-                    .unwrap();
-                    emit_comparison(output);
-                }
+                    stack_index,
+                    CHARACTER_SIZE / FIXNUM_SIZE,
+                    "make-string",
+                );
             }
+            write!(
+                output,
+                "\tmovq %rax, {}(%r12,%r13)\n\
+                \tmovq %rax, %rdx\n",
+                LENGTH_OFFSET
+            )
+            .unwrap();
+            emit_allocation(output, STRING_TAG);
+        }
+        StringLength => writeln!(
+            output,
+            "\tmovq {}(%r12,%rax), %rax",
+            LENGTH_OFFSET - STRING_TAG
+        )
+        .unwrap(),
+        IsString => {
+            write!(
+                output,
+                "\tand ${}, %al\n\
+                \tcmp ${}, %al\n",
+                OBJECT_MASK, STRING_TAG
+            )
+            .unwrap();
+            emit_comparison(output, "e");
+        }
+        FlSqrt => {
+            writeln!(
+                output,
+                "\tsqrtsd {}(%r12,%rax), %xmm0",
+                DATA_OFFSET - NUMBER_TAG
+            )
+            .unwrap();
+            emit_flonum(output);
         }
-        Expression::Immediate(immediate) => {
-            writeln!(output, "\tmovl ${}, %eax", i32::from(immediate)).unwrap()
+        // A value that is not a number fails the first comparison and skips
+        // the second.
+        IsFlonum => {
+            let end = unique_label();
+            write!(
+                output,
+                "\tmovq %rax, %rcx\n\
+                \tandq ${}, %rcx\n\
+                \tcmpq ${}, %rcx\n\
+                \tjne {}\n\
+                \tcmpb ${}, {}(%r12,%rax)\n\
+                {}:\n",
+                OBJECT_MASK,
+                NUMBER_TAG,
+                end,
+                FLONUM_KIND,
+                HEADER_OFFSET - NUMBER_TAG,
+                end
+            )
+            .unwrap();
+            emit_comparison(output, "e");
+        }
+        ExactToInexact => {
+            writeln!(output, "\tmovq %rax, %rdi").unwrap();
+            emit_runtime_call(output, stack_index, "scheme_inexact");
+        }
+        InexactToExact => {
+            writeln!(output, "\tmovq %rax, %rdi").unwrap();
+            emit_runtime_call(output, stack_index, "scheme_exact");
+        }
+        Numerator => {
+            writeln!(output, "\tmovq %rax, %rdi").unwrap();
+            emit_runtime_call(output, stack_index, "scheme_numerator");
+        }
+        Denominator => {
+            writeln!(output, "\tmovq %rax, %rdi").unwrap();
+            emit_runtime_call(output, stack_index, "scheme_denominator");
+        }
+        Box => write!(
+            output,
+            "\tmovq %rax, (%r12,%r13)\n\
+            \tmovq %r13, %rax\n\
+            \torq ${}, %rax\n\
+            \taddq ${}, %r13\n",
+            BOX_TAG, BOX_SIZE
+        )
+        .unwrap(),
+        Unbox => writeln!(output, "\tmovq {}(%r12,%rax), %rax", -BOX_TAG).unwrap(),
+    }
+
+    Ok(())
+}
+
+fn emit_binary_call(
+    output: &mut String,
+    stack_index: i32,
+    environment: &Environment,
+    safety: Safety,
+    primitive: BinaryPrimitive,
+    arguments: Vec<Expression>,
+) -> Result<(), CompilationError> {
+    use BinaryPrimitive::*;
+
+    let [left, right] =
+        <[Expression; 2]>::try_from(arguments).map_err(|_| CompilationError::Other)?;
+
+    emit_expression(output, stack_index, environment, safety, right)?;
+    writeln!(output, "\tmovq %rax, {}(%rsp)", stack_index).unwrap();
+    emit_expression(output, stack_index - WORD_SIZE, environment, safety, left)?;
+
+    let expected = match primitive {
+        FxAdd | FxSub | FxMul | FxLogAnd | FxLogOr | FxEqual | FxLess | FxLessOrEqual
+        | FxGreater | FxGreaterOrEqual => [Some(Type::Fixnum), Some(Type::Fixnum)],
+        CharEqual | CharLess | CharLessOrEqual | CharGreater | CharGreaterOrEqual => {
+            [Some(Type::Character), Some(Type::Character)]
+        }
+        FlAdd | FlSub | FlMul | FlDiv | FlEqual | FlLess | FlLessOrEqual | FlGreater
+        | FlGreaterOrEqual => [Some(Type::Flonum), Some(Type::Flonum)],
+        SetCar | SetCdr => [Some(Type::Pair), None],
+        VectorRef => [Some(Type::Vector), Some(Type::Fixnum)],
+        StringRef => [Some(Type::String), Some(Type::Fixnum)],
+        _ => [None, None],
+    };
+    let operands = [String::from("%rax"), format!("{}(%rsp)", stack_index)];
+    if safety == Safety::Safe {
+        let who = primitive.to_string();
+        emit_checks(output, stack_index, &operands, &expected, &who);
+        match primitive {
+            VectorRef => emit_bounds_check(output, stack_index, VECTOR_TAG, &operands[1], &who),
+            StringRef => emit_bounds_check(output, stack_index, STRING_TAG, &operands[1], &who),
+            _ => {}
+        }
+    }
+
+    match primitive {
+        FxAdd | FxSub | FxMul | Add | Subtract | Multiply | Quotient | Remainder => {
+            emit_operands(output, "%rax", &format!("{}(%rsp)", stack_index));
+            emit_arithmetic(output, stack_index, primitive);
         }
+        // Division is exact unless an operand is a flonum, so its result is
+        // seldom a fixnum and the runtime always computes it.
+        Divide => {
+            emit_operands(output, "%rax", &format!("{}(%rsp)", stack_index));
+            emit_runtime_call(output, stack_index, "scheme_divide");
+        }
+        FxLogAnd => writeln!(output, "\tandq {}(%rsp), %rax", stack_index).unwrap(),
+        FxLogOr => writeln!(output, "\torq {}(%rsp), %rax", stack_index).unwrap(),
+        FxEqual | CharEqual => {
+            writeln!(output, "\tcmpq {}(%rsp), %rax", stack_index).unwrap();
+            emit_comparison(output, "e");
+        }
+        FxLess | CharLess => {
+            writeln!(output, "\tcmpq {}(%rsp), %rax", stack_index).unwrap();
+            emit_comparison(output, "l");
+        }
+        FxLessOrEqual | CharLessOrEqual => {
+            writeln!(output, "\tcmpq {}(%rsp), %rax", stack_index).unwrap();
+            emit_comparison(output, "le");
+        }
+        FxGreater | CharGreater => {
+            writeln!(output, "\tcmpq {}(%rsp), %rax", stack_index).unwrap();
+            emit_comparison(output, "g");
+        }
+        FxGreaterOrEqual | CharGreaterOrEqual => {
+            writeln!(output, "\tcmpq {}(%rsp), %rax", stack_index).unwrap();
+            emit_comparison(output, "ge");
+        }
+        FlAdd | FlSub | FlMul | FlDiv => {
+            let instruction = match primitive {
+                FlAdd => "addsd",
+                FlSub => "subsd",
+                FlMul => "mulsd",
+                _ => "divsd",
+            };
+            write!(
+                output,
+                "\tmovsd {}(%r12,%rax), %xmm0\n\
+                \tmovq {}(%rsp), %rcx\n\
+                \t{} {}(%r12,%rcx), %xmm0\n",
+                DATA_OFFSET - NUMBER_TAG,
+                stack_index,
+                instruction,
+                DATA_OFFSET - NUMBER_TAG
+            )
+            .unwrap();
+            emit_flonum(output);
+        }
+        // Comparisons with NaN are unordered, which sets the carry, zero and
+        // parity flags, so only conditions that are false then are used.
+        FlEqual => {
+            emit_flonum_comparison(output, stack_index, false);
+            write!(
+                output,
+                "\tsetnp %cl\n\
+                \tsete %al\n\
+                \tandb %cl, %al\n"
+            )
+            .unwrap();
+            emit_boolean(output);
+        }
+        FlLess => {
+            emit_flonum_comparison(output, stack_index, true);
+            emit_comparison(output, "a");
+        }
+        FlLessOrEqual => {
+            emit_flonum_comparison(output, stack_index, true);
+            emit_comparison(output, "ae");
+        }
+        FlGreater => {
+            emit_flonum_comparison(output, stack_index, false);
+            emit_comparison(output, "a");
+        }
+        FlGreaterOrEqual => {
+            emit_flonum_comparison(output, stack_index, false);
+            emit_comparison(output, "ae");
+        }
+        Cons => write!(
+            output,
+            "\tmovq %rax, {}(%r12,%r13)\n\
+            \tmovq {}(%rsp), %rax\n\
+            \tmovq %rax, {}(%r12,%r13)\n\
+            \tmovq %r13, %rax\n\
+            \torq ${}, %rax\n\
+            \taddq ${}, %r13\n",
+            CAR_OFFSET, stack_index, CDR_OFFSET, PAIR_TAG, PAIR_SIZE
+        )
+        .unwrap(),
+        SetCar => write!(
+            output,
+            "\tmovq {}(%rsp), %rcx\n\
+            \tmovq %rcx, {}(%r12,%rax)\n",
+            stack_index,
+            CAR_OFFSET - PAIR_TAG
+        )
+        .unwrap(),
+        SetCdr => write!(
+            output,
+            "\tmovq {}(%rsp), %rcx\n\
+            \tmovq %rcx, {}(%r12,%rax)\n",
+            stack_index,
+            CDR_OFFSET - PAIR_TAG
+        )
+        .unwrap(),
+        SetBox => write!(
+            output,
+            "\tmovq {}(%rsp), %rcx\n\
+            \tmovq %rcx, {}(%r12,%rax)\n\
+            \tmovq %rcx, %rax\n",
+            stack_index, -BOX_TAG
+        )
+        .unwrap(),
+        VectorRef => write!(
+            output,
+            "\tmovq {}(%rsp), %rcx\n\
+            \tleaq (%rax,%rcx,{}), %rax\n\
+            \tmovq {}(%r12,%rax), %rax\n",
+            stack_index,
+            VALUE_SIZE / FIXNUM_SIZE,
+            DATA_OFFSET - VECTOR_TAG
+        )
+        .unwrap(),
+        StringRef => write!(
+            output,
+            "\tmovq {}(%rsp), %rcx\n\
+            \taddq %rcx, %rax\n\
+            \tmovl {}(%r12,%rax), %eax\n\
+            \tshlq ${}, %rax\n\
+            \torq ${}, %rax\n",
+            stack_index,
+            DATA_OFFSET - STRING_TAG,
+            CHARACTER_SHIFT,
+            CHARACTER_TAG
+        )
+        .unwrap(),
+    }
+
+    Ok(())
+}
+
+fn emit_ternary_call(
+    output: &mut String,
+    stack_index: i32,
+    environment: &Environment,
+    safety: Safety,
+    primitive: TernaryPrimitive,
+    arguments: Vec<Expression>,
+) -> Result<(), CompilationError> {
+    use TernaryPrimitive::*;
+
+    let [first, second, third] =
+        <[Expression; 3]>::try_from(arguments).map_err(|_| CompilationError::Other)?;
+
+    emit_expression(output, stack_index, environment, safety, third)?;
+    writeln!(output, "\tmovq %rax, {}(%rsp)", stack_index).unwrap();
+    emit_expression(output, stack_index - WORD_SIZE, environment, safety, second)?;
+    writeln!(output, "\tmovq %rax, {}(%rsp)", stack_index - WORD_SIZE).unwrap();
+    emit_expression(
+        output,
+        stack_index - 2 * WORD_SIZE,
+        environment,
+        safety,
+        first,
+    )?;
+
+    let expected = match primitive {
+        VectorSet => [Some(Type::Vector), Some(Type::Fixnum), None],
+        StringSet => [
+            Some(Type::String),
+            Some(Type::Fixnum),
+            Some(Type::Character),
+        ],
+    };
+    let operands = [
+        String::from("%rax"),
+        format!("{}(%rsp)", stack_index - WORD_SIZE),
+        format!("{}(%rsp)", stack_index),
+    ];
+    if safety == Safety::Safe {
+        let who = primitive.to_string();
+        emit_checks(output, stack_index, &operands, &expected, &who);
+        let tag = match primitive {
+            VectorSet => VECTOR_TAG,
+            StringSet => STRING_TAG,
+        };
+        emit_bounds_check(output, stack_index, tag, &operands[1], &who);
+    }
+
+    match primitive {
+        VectorSet => write!(
+            output,
+            "\tmovq {}(%rsp), %rcx\n\
+            \tleaq (%rax,%rcx,{}), %rcx\n\
+            \tmovq {}(%rsp), %rdx\n\
+            \tmovq %rdx, {}(%r12,%rcx)\n",
+            stack_index - WORD_SIZE,
+            VALUE_SIZE / FIXNUM_SIZE,
+            stack_index,
+            DATA_OFFSET - VECTOR_TAG
+        )
+        .unwrap(),
+        StringSet => write!(
+            output,
+            "\tmovq {}(%rsp), %rcx\n\
+            \taddq %rax, %rcx\n\
+            \tmovq {}(%rsp), %rdx\n\
+            \tshrq ${}, %rdx\n\
+            \tmovl %edx, {}(%r12,%rcx)\n",
+            stack_index - WORD_SIZE,
+            stack_index,
+            CHARACTER_SHIFT,
+            DATA_OFFSET - STRING_TAG
+        )
+        .unwrap(),
     }
 
     Ok(())
 }
 
-fn emit_comparison(output: &mut String) {
+// The types that primitives check their arguments for, and applications the
+// values they apply. Generic arithmetic leaves checking numbers to the runtime,
+// which dispatches on them anyway.
+#[derive(Clone, Copy)]
+enum Type {
+    Fixnum,
+    Length,
+    ScalarValue,
+    Character,
+    Pair,
+    Vector,
+    String,
+    Flonum,
+    Procedure,
+}
+
+impl Type {
+    fn name(self) -> &'static str {
+        match self {
+            Type::Fixnum => "fixnum",
+            Type::Length => "non-negative fixnum",
+            Type::ScalarValue => "Unicode scalar value",
+            Type::Character => "character",
+            Type::Pair => "pair",
+            Type::Vector => "vector",
+            Type::String => "string",
+            Type::Flonum => "flonum",
+            Type::Procedure => "procedure",
+        }
+    }
+}
+
+// Checks that the argument of `who` in `operand` is of the `expected` type,
+// and otherwise has the runtime report it.
+fn emit_check(output: &mut String, stack_index: i32, operand: &str, expected: Type, who: &str) {
+    let error = unique_label();
+    let end = unique_label();
+
+    match expected {
+        Type::Fixnum => write!(
+            output,
+            "\ttestq ${}, {}\n\
+            \tjz {}\n",
+            INTEGER_MASK, operand, end
+        ),
+        // A length has neither tag bits nor the sign bit set.
+        Type::Length => write!(
+            output,
+            "\tmovabsq ${}, %rcx\n\
+            \ttestq %rcx, {}\n\
+            \tjz {}\n",
+            i64::MIN | i64::from(INTEGER_MASK),
+            operand,
+            end
+        ),
+        // Code points up to 0x10ffff other than the surrogates, which are
+        // 0xd800 to 0xdfff. Negative fixnums compare above either bound
+        // unsigned.
+        Type::ScalarValue => write!(
+            output,
+            "\tmovq {}, %rcx\n\
+            \ttestq ${}, %rcx\n\
+            \tjnz {}\n\
+            \tsarq ${}, %rcx\n\
+            \tcmpq ${}, %rcx\n\
+            \tja {}\n\
+            \tsubq ${}, %rcx\n\
+            \tcmpq ${}, %rcx\n\
+            \tjae {}\n",
+            operand, INTEGER_MASK, error, INTEGER_SHIFT, 0x10ffff, error, 0xd800, 0x800, end
+        ),
+        // A value that is not a number fails the first comparison and skips
+        // the second, which would read its header.
+        Type::Flonum => write!(
+            output,
+            "\tmovq {}, %rcx\n\
+            \tmovq %rcx, %rdx\n\
+            \tandq ${}, %rdx\n\
+            \tcmpq ${}, %rdx\n\
+            \tjne {}\n\
+            \tcmpb ${}, {}(%r12,%rcx)\n\
+            \tje {}\n",
+            operand,
+            OBJECT_MASK,
+            NUMBER_TAG,
+            error,
+            FLONUM_KIND,
+            HEADER_OFFSET - NUMBER_TAG,
+            end
+        ),
+        _ => {
+            let (mask, tag) = match expected {
+                Type::Character => (CHARACTER_MASK, CHARACTER_TAG),
+                Type::Pair => (OBJECT_MASK, PAIR_TAG),
+                Type::Vector => (OBJECT_MASK, VECTOR_TAG),
+                Type::String => (OBJECT_MASK, STRING_TAG),
+                _ => (OBJECT_MASK, CLOSURE_TAG),
+            };
+            write!(
+                output,
+                "\tmovq {}, %rcx\n\
+                \tandq ${}, %rcx\n\
+                \tcmpq ${}, %rcx\n\
+                \tje {}\n",
+                operand, mask, tag, end
+            )
+        }
+    }
+    .unwrap();
+
+    write!(
+        output,
+        "{}:\n\
+        \tmovq {}, %rdx\n",
+        error, operand
+    )
+    .unwrap();
+    emit_error_call(
+        output,
+        stack_index,
+        "scheme_type_error",
+        &[who, expected.name()],
+    );
+    writeln!(output, "{}:", end).unwrap();
+}
+
+// Checks the arguments of `who` in `operands` that have an expected type, from
+// left to right.
+fn emit_checks(
+    output: &mut String,
+    stack_index: i32,
+    operands: &[String],
+    expected: &[Option<Type>],
+    who: &str,
+) {
+    for (operand, expected) in operands.iter().zip(expected) {
+        if let Some(expected) = expected {
+            emit_check(output, stack_index, operand, *expected, who);
+        }
+    }
+}
+
+// Checks that the fixnum index in `index` is less than the length of the
+// vector or string in %rax, which has `tag`. Compared unsigned, negative
+// indices are greater than any length.
+fn emit_bounds_check(output: &mut String, stack_index: i32, tag: i32, index: &str, who: &str) {
+    let end = unique_label();
+
+    write!(
+        output,
+        "\tmovq {}, %rdx\n\
+        \tcmpq {}(%r12,%rax), %rdx\n\
+        \tjb {}\n\
+        \tmovq %rax, %rsi\n",
+        index,
+        LENGTH_OFFSET - tag,
+        end
+    )
+    .unwrap();
+    emit_error_call(output, stack_index, "scheme_index_error", &[who]);
+    writeln!(output, "{}:", end).unwrap();
+}
+
+// Checks that an object with the fixnum length in %rax, which is not
+// negative, fits in what is left of the heap when each element takes `scale`
+// times the length's unit. The space left is divided by `scale` rather than
+// the length multiplied, so that no length overflows.
+fn emit_heap_check(output: &mut String, stack_index: i32, scale: i32, who: &str) {
+    let end = unique_label();
+
+    write!(
+        output,
+        "\tmovq ${}, %rcx\n\
+        \tsubq %r13, %rcx\n\
+        \tsarq ${}, %rcx\n\
+        \tcmpq %rcx, %rax\n\
+        \tjle {}\n\
+        \tmovq %rax, %rsi\n",
+        HEAP_SIZE - DATA_OFFSET,
+        scale.trailing_zeros(),
+        end
+    )
+    .unwrap();
+    emit_error_call(output, stack_index, "scheme_heap_error", &[who]);
+    writeln!(output, "{}:", end).unwrap();
+}
+
+// Checks that the procedure starting here was applied to its `arity`
+// arguments. Callers pass the number of arguments in %rcx.
+fn emit_arity_check(output: &mut String, stack_index: i32, arity: usize) {
+    let end = unique_label();
+
+    write!(
+        output,
+        "\tcmpq ${}, %rcx\n\
+        \tje {}\n\
+        \tmovq ${}, %rdi\n\
+        \tmovq %rcx, %rsi\n",
+        arity, end, arity
+    )
+    .unwrap();
+    emit_error_call(output, stack_index, "scheme_arity_error", &[]);
+    writeln!(output, "{}:", end).unwrap();
+}
+
+// Calls `function` in the runtime to report an error, with the addresses of
+// `strings` as its first arguments and the others already in registers. The
+// runtime exits instead of returning, so the stack pointer only needs
+// aligning. The strings are kept in the data section next to the call.
+fn emit_error_call(output: &mut String, stack_index: i32, function: &str, strings: &[&str]) {
+    let labels: Vec<String> = strings.iter().map(|_| unique_label()).collect();
+
+    for (label, register) in labels.iter().zip(&["%rdi", "%rsi"]) {
+        writeln!(output, "\tleaq {}(%rip), {}", label, register).unwrap();
+    }
+    write!(
+        output,
+        "\tleaq {}(%rsp), %rsp\n\
+        \tandq $-16, %rsp\n\
+        \tcall L{}\n\
+        \t.data\n",
+        stack_index, function
+    )
+    .unwrap();
+    for (label, string) in labels.iter().zip(strings) {
+        write!(output, "{}:\n\t.asciz \"{}\"\n", label, string).unwrap();
+    }
+    writeln!(output, "\t.text").unwrap();
+}
+
+// Moves the operands of arithmetic to where `emit_arithmetic` and the runtime
+// expect them.
+fn emit_operands(output: &mut String, left: &str, right: &str) {
+    write!(
+        output,
+        "\tmovq {}, %rdi\n\
+        \tmovq {}, %rsi\n",
+        left, right
+    )
+    .unwrap();
+}
+
+// Integer arithmetic on the operands in %rdi and %rsi is done inline while
+// they and the result are fixnums. Bignum operands, overflow and division by
+// zero go to the runtime instead, which promotes results that do not fit in a
+// fixnum to bignums. The `fx` primitives assume their operands are fixnums.
+fn emit_arithmetic(output: &mut String, stack_index: i32, primitive: BinaryPrimitive) {
+    use BinaryPrimitive::*;
+
+    let slow = unique_label();
+    let end = unique_label();
+
+    // Both operands are fixnums if the tag bits of neither are set.
+    if !matches!(primitive, FxAdd | FxSub | FxMul) {
+        write!(
+            output,
+            "\tmovq %rdi, %rcx\n\
+            \torq %rsi, %rcx\n\
+            \ttestq ${}, %rcx\n\
+            \tjnz {}\n",
+            INTEGER_MASK, slow
+        )
+        .unwrap();
+    }
+
+    writeln!(output, "\tmovq %rdi, %rax").unwrap();
+    let function = match primitive {
+        FxAdd | Add => {
+            write!(output, "\taddq %rsi, %rax\n\tjo {}\n", slow).unwrap();
+            "scheme_add"
+        }
+        FxSub | Subtract => {
+            write!(output, "\tsubq %rsi, %rax\n\tjo {}\n", slow).unwrap();
+            "scheme_subtract"
+        }
+        FxMul | Multiply => {
+            write!(
+                output,
+                "\tsarq ${}, %rax\n\
+                \timulq %rsi, %rax\n\
+                \tjo {}\n",
+                INTEGER_SHIFT, slow
+            )
+            .unwrap();
+            "scheme_multiply"
+        }
+        // Dividing shifted fixnums gives the quotient unshifted and the
+        // remainder shifted.
+        Quotient => {
+            write!(
+                output,
+                "\ttestq %rsi, %rsi\n\
+                \tjz {}\n\
+                \tcqto\n\
+                \tidivq %rsi\n\
+                \timulq ${}, %rax, %rax\n\
+                \tjo {}\n",
+                slow, FIXNUM_SIZE, slow
+            )
+            .unwrap();
+            "scheme_quotient"
+        }
+        Remainder => {
+            write!(
+                output,
+                "\ttestq %rsi, %rsi\n\
+                \tjz {}\n\
+                \tcqto\n\
+                \tidivq %rsi\n\
+                \tmovq %rdx, %rax\n",
+                slow
+            )
+            .unwrap();
+            "scheme_remainder"
+        }
+        _ => unreachable!(),
+    };
+
+    write!(
+        output,
+        "\tjmp {}\n\
+        {}:\n",
+        end, slow
+    )
+    .unwrap();
+    emit_runtime_call(output, stack_index, function);
+    writeln!(output, "{}:", end).unwrap();
+}
+
+// Calls `function` in the runtime with the arguments in %rdi and %rsi. C
+// code needs the stack pointer below the live slots and aligned to 16 bytes,
+// so the old one is saved on the new stack. The runtime allocates from the
+// offset passed in %rdx, and returns the value in %rax and the new offset in
+// %rdx.
+fn emit_runtime_call(output: &mut String, stack_index: i32, function: &str) {
+    write!(
+        output,
+        "\tmovq %r13, %rdx\n\
+        \tmovq %rsp, %rax\n\
+        \tleaq {}(%rsp), %rsp\n\
+        \tandq $-16, %rsp\n\
+        \tsubq ${}, %rsp\n\
+        \tpushq %rax\n\
+        \tcall L{}\n\
+        \tmovq (%rsp), %rsp\n\
+        \tmovq %rdx, %r13\n",
+        stack_index, WORD_SIZE, function
+    )
+    .unwrap();
+}
+
+// Compares the flonum in %rax with the one in the slot at `stack_index`, or
+// the other way around if `swapped`.
+fn emit_flonum_comparison(output: &mut String, stack_index: i32, swapped: bool) {
+    let (first, second) = if swapped {
+        ("%rcx", "%rax")
+    } else {
+        ("%rax", "%rcx")
+    };
+
+    write!(
+        output,
+        "\tmovq {}(%rsp), %rcx\n\
+        \tmovsd {}(%r12,{}), %xmm0\n\
+        \tucomisd {}(%r12,{}), %xmm0\n",
+        stack_index,
+        DATA_OFFSET - NUMBER_TAG,
+        first,
+        DATA_OFFSET - NUMBER_TAG,
+        second
+    )
+    .unwrap();
+}
+
+fn emit_comparison(output: &mut String, condition: &str) {
+    writeln!(output, "\tset{} %al", condition).unwrap();
+    emit_boolean(output);
+}
+
+// Turns the 0 or 1 in %al into a boolean.
+fn emit_boolean(output: &mut String) {
     write!(
         output,
-        "\tsete %al\n\
-        \tmovzbl %al, %eax\n\
+        "\tmovzbl %al, %eax\n\
         \tsal ${}, %al\n\
         \tor ${}, %al\n",
         BOOLEAN_BIT, BOOLEAN_FALSE
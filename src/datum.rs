@@ -0,0 +1,304 @@
+use std::{fmt, ops::Range};
+
+use super::{
+    expression::bignum::Bignum,
+    lex::{write_character, write_flonum, Token},
+    parse::{ParseError, ParseErrorKind},
+};
+
+/// An S-expression as written in the source, with the span it was read from.
+///
+/// The empty list is a `List` without items. A `Pair` is a dotted list: its
+/// items followed by a tail that is not a list.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Datum {
+    Boolean(bool, Range<usize>),
+    Character(char, Range<usize>),
+    Integer(i64, Range<usize>),
+    Bignum(Bignum, Range<usize>),
+    Flonum(f64, Range<usize>),
+    Rational(Bignum, Bignum, Range<usize>),
+    String(String, Range<usize>),
+    Symbol(String, Range<usize>),
+    List(Vec<Datum>, Range<usize>),
+    Pair(Vec<Datum>, Box<Datum>, Range<usize>),
+    Vector(Vec<Datum>, Range<usize>),
+}
+
+impl Datum {
+    /// Makes the list of `items` followed by `tail`, which is proper if
+    /// `tail` is a list, and is `tail` itself if there are no items.
+    pub fn pair(mut items: Vec<Datum>, tail: Datum, span: Range<usize>) -> Datum {
+        match tail {
+            tail if items.is_empty() => tail,
+            Datum::List(mut rest, _) => {
+                items.append(&mut rest);
+                Datum::List(items, span)
+            }
+            Datum::Pair(mut rest, tail, _) => {
+                items.append(&mut rest);
+                Datum::Pair(items, tail, span)
+            }
+            tail => Datum::Pair(items, Box::new(tail), span),
+        }
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Datum::Boolean(_, span)
+            | Datum::Character(_, span)
+            | Datum::Integer(_, span)
+            | Datum::Bignum(_, span)
+            | Datum::Flonum(_, span)
+            | Datum::Rational(_, _, span)
+            | Datum::String(_, span)
+            | Datum::Symbol(_, span)
+            | Datum::List(_, span)
+            | Datum::Pair(_, _, span)
+            | Datum::Vector(_, span) => span.clone(),
+        }
+    }
+
+    pub fn span_mut(&mut self) -> &mut Range<usize> {
+        match self {
+            Datum::Boolean(_, span)
+            | Datum::Character(_, span)
+            | Datum::Integer(_, span)
+            | Datum::Bignum(_, span)
+            | Datum::Flonum(_, span)
+            | Datum::Rational(_, _, span)
+            | Datum::String(_, span)
+            | Datum::Symbol(_, span)
+            | Datum::List(_, span)
+            | Datum::Pair(_, _, span)
+            | Datum::Vector(_, span) => span,
+        }
+    }
+
+    pub fn symbol(&self) -> Option<&str> {
+        match self {
+            Datum::Symbol(name, _) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Datum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let items = |f: &mut fmt::Formatter, items: &[Datum]| {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(" ")?;
+                }
+                write!(f, "{}", item)?;
+            }
+            Ok(())
+        };
+
+        match self {
+            Datum::Boolean(boolean, _) => write!(f, "{}", Token::Boolean(*boolean)),
+            Datum::Character(character, _) => write_character(f, *character),
+            Datum::Integer(integer, _) => write!(f, "{}", integer),
+            Datum::Bignum(bignum, _) => write!(f, "{}", bignum),
+            Datum::Flonum(flonum, _) => write_flonum(f, *flonum),
+            Datum::Rational(numerator, denominator, _) => {
+                write!(f, "{}/{}", numerator, denominator)
+            }
+            Datum::String(string, _) => write!(f, "{:?}", string),
+            Datum::Symbol(name, _) => f.write_str(name),
+            Datum::List(list, _) => {
+                f.write_str("(")?;
+                items(f, list)?;
+                f.write_str(")")
+            }
+            Datum::Pair(list, tail, _) => {
+                f.write_str("(")?;
+                items(f, list)?;
+                write!(f, " . {})", tail)
+            }
+            Datum::Vector(vector, _) => {
+                f.write_str("#(")?;
+                items(f, vector)?;
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+/// Reads every datum in `tokens`.
+pub fn read<'a>(
+    tokens: &mut impl Iterator<Item = (Token<'a>, Range<usize>)>,
+) -> Result<Vec<Datum>, ParseError> {
+    // Each open list, with whether it is a vector, where it starts and the
+    // data read in it so far.
+    let mut open: Vec<(bool, usize, Vec<Datum>)> = Vec::new();
+    let mut data = Vec::new();
+    let mut end = 0;
+    // How many of the next data are commented out with `#;`, at the top
+    // level and in each open list.
+    let mut skips = vec![0];
+
+    for (token, span) in tokens {
+        end = span.end;
+
+        let datum = match token {
+            Token::LeftParenthesis | Token::HashParenthesis => {
+                open.push((token == Token::HashParenthesis, span.start, Vec::new()));
+                skips.push(0);
+                continue;
+            }
+            Token::DatumComment => {
+                *skips.last_mut().unwrap() += 1;
+                continue;
+            }
+            // A list cannot end while a datum in it is still commented out.
+            // At the top level, the count popped is that of the program, which
+            // ends with an error either way.
+            Token::RightParenthesis => {
+                if skips.pop() > Some(0) {
+                    return Err(unexpected("expression", "`)`", span));
+                }
+                match open.pop() {
+                    Some((true, start, items)) => {
+                        if let Some(dot) = items.iter().find(|item| item.symbol() == Some(".")) {
+                            return Err(unexpected("expression", "`.`", dot.span()));
+                        }
+                        Datum::Vector(items, start..span.end)
+                    }
+                    Some((false, start, items)) => list(items, start..span.end)?,
+                    None => return Err(unexpected("expression", "`)`", span)),
+                }
+            }
+            Token::Null => Datum::List(Vec::new(), span),
+            Token::Boolean(boolean) => Datum::Boolean(boolean, span),
+            Token::Character(character) => Datum::Character(character, span),
+            Token::Integer(integer) => Datum::Integer(integer, span),
+            Token::Bignum(bignum) => Datum::Bignum(bignum, span),
+            Token::Flonum(flonum) => Datum::Flonum(flonum, span),
+            Token::Rational(numerator, denominator) => {
+                Datum::Rational(numerator, denominator, span)
+            }
+            Token::String(string) => Datum::String(string, span),
+            Token::Symbol(name) => Datum::Symbol(name.to_string(), span),
+            Token::Unrecognized => {
+                return Err(ParseError::new(ParseErrorKind::UnrecognizedToken, span))
+            }
+        };
+
+        let skip = skips.last_mut().unwrap();
+        if *skip > 0 {
+            *skip -= 1;
+            continue;
+        }
+
+        match open.last_mut() {
+            Some((_, _, items)) => items.push(datum),
+            None => data.push(datum),
+        }
+    }
+
+    if !open.is_empty() {
+        Err(unexpected("expression or `)`", "end of input", end..end))
+    } else if skips[0] > 0 {
+        Err(unexpected("expression", "end of input", end..end))
+    } else {
+        Ok(data)
+    }
+}
+
+// Makes a list of the items between parentheses, which is dotted if the
+// next to last item is a `.`.
+fn list(mut items: Vec<Datum>, span: Range<usize>) -> Result<Datum, ParseError> {
+    let dot = items.iter().position(|item| item.symbol() == Some("."));
+
+    match dot {
+        None => Ok(Datum::List(items, span)),
+        Some(i) if i > 0 && i + 2 == items.len() => {
+            let tail = items.pop().unwrap();
+            items.pop();
+            Ok(Datum::pair(items, tail, span))
+        }
+        Some(i) => Err(unexpected("expression", "`.`", items[i].span())),
+    }
+}
+
+fn unexpected(expected: &'static str, found: &str, span: Range<usize>) -> ParseError {
+    let kind = ParseErrorKind::UnexpectedToken {
+        expected,
+        found: found.to_string(),
+    };
+    ParseError::new(kind, span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex::lex;
+
+    #[test]
+    fn data() {
+        let cases = [
+            ("", ""),
+            ("1 #t #\\a \"b\" c ()", "1 #t #\\a \"b\" c ()"),
+            ("(-12345678901234567890)", "(-12345678901234567890)"),
+            (
+                "(1.5 -.25 1e3 1e100 +inf.0 -nan.0)",
+                "(1.5 -0.25 1000.0 1e100 +inf.0 +nan.0)",
+            ),
+            ("(a (b c) #(1 2))", "(a (b c) #(1 2))"),
+            ("(a . b)", "(a . b)"),
+            ("(a b . (c d))", "(a b c d)"),
+            ("(a . (b . c))", "(a b . c)"),
+            ("(a . ())", "(a)"),
+            ("(... x ...)", "(... x ...)"),
+            ("#;a b (c #;(d e) f #;g) #(#;#;1 2 3)", "b (c f) #(3)"),
+            ("(a . #;b c) ; d", "(a . c)"),
+        ];
+
+        for (source, expected) in &cases {
+            let data = read(&mut lex(source)).unwrap();
+            let actual: Vec<_> = data.iter().map(Datum::to_string).collect();
+            assert_eq!(actual.join(" "), *expected);
+        }
+    }
+
+    #[test]
+    fn spans() {
+        let data = read(&mut lex("(a . 1) #(x)")).unwrap();
+        assert_eq!(
+            data,
+            [
+                Datum::Pair(
+                    vec![Datum::Symbol(String::from("a"), 1..2)],
+                    Box::new(Datum::Integer(1, 5..6)),
+                    0..7
+                ),
+                Datum::Vector(vec![Datum::Symbol(String::from("x"), 10..11)], 8..12),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            ("(a", "expected expression or `)`, found end of input", 2..2),
+            ("a)", "expected expression, found `)`", 1..2),
+            ("(. a)", "expected expression, found `.`", 1..2),
+            ("(a . b c)", "expected expression, found `.`", 3..4),
+            ("(a .)", "expected expression, found `.`", 3..4),
+            ("#(a . b)", "expected expression, found `.`", 4..5),
+            ("(a #c)", "unrecognized token", 3..5),
+            ("(a #;)", "expected expression, found `)`", 5..6),
+            ("a #;", "expected expression, found end of input", 4..4),
+            ("#; )", "expected expression, found `)`", 3..4),
+            ("(#;(a) b #;)", "expected expression, found `)`", 11..12),
+            ("((a #;) b)", "expected expression, found `)`", 6..7),
+        ];
+
+        for (source, message, span) in &cases {
+            let error = read(&mut lex(source)).unwrap_err();
+            assert_eq!(error.to_string(), *message);
+            assert_eq!(error.span, *span);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::{fmt::Write, ops::Range};
+
+/// Formats `message` as an error. When a `span` into `source` is given, the
+/// first line it covers is printed below the message with the span
+/// underlined:
+///
+/// ```text
+/// error: expected expression or `)`, found end of input
+///  --> 1:10
+///   |
+/// 1 | (fxadd1 1
+///   |          ^
+/// ```
+pub fn render(source: &str, message: &str, span: Option<Range<usize>>) -> String {
+    let mut output = format!("error: {}\n", message);
+
+    let span = match span {
+        Some(span) => span,
+        None => return output,
+    };
+
+    let start = span.start.min(source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    let line = &source[line_start..line_end];
+    let number = source[..line_start].matches('\n').count() + 1;
+    let column = source[line_start..start].chars().count();
+    let width = source[start..span.end.clamp(start, line_end)]
+        .chars()
+        .count()
+        .max(1);
+    let gutter = " ".repeat(number.to_string().len());
+
+    write!(
+        output,
+        "{}--> {}:{}\n\
+        {} |\n\
+        {} | {}\n\
+        {} | {}{}\n",
+        gutter,
+        number,
+        column + 1,
+        gutter,
+        number,
+        line,
+        gutter,
+        " ".repeat(column),
+        "^".repeat(width)
+    )
+    .unwrap();
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rendering() {
+        let cases = [
+            ("x", "oops", None, "error: oops\n"),
+            (
+                "(fxadd1 )",
+                "expected expression, found `)`",
+                Some(8..9),
+                "error: expected expression, found `)`\n \
+                 --> 1:9\n  \
+                 |\n\
+                 1 | (fxadd1 )\n  \
+                 |         ^\n",
+            ),
+            (
+                "(let ((x 1))\n  (fx+ x #c))",
+                "unrecognized token",
+                Some(22..24),
+                "error: unrecognized token\n \
+                 --> 2:10\n  \
+                 |\n\
+                 2 |   (fx+ x #c))\n  \
+                 |          ^^\n",
+            ),
+            (
+                "(fxadd1 1",
+                "expected expression or `)`, found end of input",
+                Some(9..9),
+                "error: expected expression or `)`, found end of input\n \
+                 --> 1:10\n  \
+                 |\n\
+                 1 | (fxadd1 1\n  \
+                 |          ^\n",
+            ),
+            (
+                "(if\n#t)",
+                "`if` expects 2 or 3 arguments, found 1",
+                Some(0..7),
+                "error: `if` expects 2 or 3 arguments, found 1\n \
+                 --> 1:1\n  \
+                 |\n\
+                 1 | (if\n  \
+                 | ^^^\n",
+            ),
+        ];
+
+        for (source, message, span, expected) in &cases {
+            assert_eq!(render(source, message, span.clone()), *expected);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    env,
+    error::Error,
+    fmt, fs, io,
+    path::Path,
+    process::{Command, ExitStatus},
+    str::FromStr,
+};
+
+use super::{
+    compile::{run, CompilationError, Safety},
+    target::Target,
+    temporary_directory::TemporaryDirectory,
+};
+
+/// The C runtime that executables are linked against.
+pub const RUNTIME: &str = include_str!("runtime.c");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Emit {
+    Assembly,
+    Object,
+    Executable,
+}
+
+impl Emit {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Emit::Assembly => "s",
+            Emit::Object => "o",
+            Emit::Executable => "",
+        }
+    }
+}
+
+pub struct ParseEmitError;
+
+impl FromStr for Emit {
+    type Err = ParseEmitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asm" => Ok(Emit::Assembly),
+            "obj" => Ok(Emit::Object),
+            "exe" => Ok(Emit::Executable),
+            _ => Err(ParseEmitError),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BuildError {
+    Compilation(CompilationError),
+    Io(io::Error),
+    Tool(String, ExitStatus),
+    /// A compiled program failed with an error message from the runtime.
+    Runtime(String),
+}
+
+impl From<CompilationError> for BuildError {
+    fn from(error: CompilationError) -> Self {
+        BuildError::Compilation(error)
+    }
+}
+
+impl From<io::Error> for BuildError {
+    fn from(error: io::Error) -> Self {
+        BuildError::Io(error)
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::Compilation(error) => write!(f, "{}", error),
+            BuildError::Io(error) => write!(f, "{}", error),
+            BuildError::Tool(program, status) => write!(f, "`{}` failed: {}", program, status),
+            BuildError::Runtime(message) => f.write_str(message),
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+/// Compiles `source` and writes the artifact selected by `emit` to `output`.
+pub fn build(
+    source: &str,
+    target: Target,
+    safety: Safety,
+    emit: Emit,
+    output: &Path,
+) -> Result<(), BuildError> {
+    link(&run(source, target, safety)?, emit, output)
+}
+
+/// Writes `assembly` to `output` as the artifact selected by `emit`. Objects
+/// and executables are produced by the C compiler named by the `CC`
+/// environment variable, or `cc`.
+pub fn link(assembly: &str, emit: Emit, output: &Path) -> Result<(), BuildError> {
+    if emit == Emit::Assembly {
+        fs::write(output, assembly)?;
+        return Ok(());
+    }
+
+    let directory = TemporaryDirectory::create()?;
+    let program = directory.0.join("program.s");
+    fs::write(&program, assembly)?;
+
+    let compiler = env::var("CC").unwrap_or_else(|_| String::from("cc"));
+    let mut command = Command::new(&compiler);
+    command.arg(&program);
+
+    if emit == Emit::Object {
+        command.arg("-c");
+    } else {
+        let runtime = directory.0.join("runtime.c");
+        fs::write(&runtime, RUNTIME)?;
+        command.arg(&runtime).arg("-lm");
+    }
+
+    let status = command.arg("-o").arg(output).status()?;
+    if !status.success() {
+        return Err(BuildError::Tool(compiler, status));
+    }
+
+    Ok(())
+}
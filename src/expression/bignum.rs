@@ -0,0 +1,167 @@
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+/// An integer of any size, as its sign and its magnitude in 32-bit limbs,
+/// least significant first and without leading zero limbs. Zero has no limbs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Bignum {
+    pub negative: bool,
+    pub limbs: Vec<u32>,
+}
+
+impl Bignum {
+    pub fn to_i64(&self) -> Option<i64> {
+        let magnitude = match self.limbs[..] {
+            [] => 0,
+            [low] => u64::from(low),
+            [low, high] => u64::from(high) << 32 | u64::from(low),
+            _ => return None,
+        };
+
+        if self.negative {
+            0i64.checked_sub_unsigned(magnitude)
+        } else {
+            i64::try_from(magnitude).ok()
+        }
+    }
+
+    // Multiplies the magnitude by `factor` and adds `addend`.
+    fn multiply_add(&mut self, factor: u32, addend: u32) {
+        let mut carry = u64::from(addend);
+        for limb in &mut self.limbs {
+            let product = u64::from(*limb) * u64::from(factor) + carry;
+            *limb = product as u32;
+            carry = product >> 32;
+        }
+        if carry > 0 {
+            self.limbs.push(carry as u32);
+        }
+    }
+
+    // Divides the magnitude by `divisor` and returns the remainder.
+    fn divide(&mut self, divisor: u32) -> u32 {
+        let mut remainder = 0;
+        for limb in self.limbs.iter_mut().rev() {
+            let dividend = remainder << 32 | u64::from(*limb);
+            *limb = (dividend / u64::from(divisor)) as u32;
+            remainder = dividend % u64::from(divisor);
+        }
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        remainder as u32
+    }
+}
+
+impl From<i64> for Bignum {
+    fn from(integer: i64) -> Self {
+        let magnitude = integer.unsigned_abs();
+        let limbs = [magnitude as u32, (magnitude >> 32) as u32];
+        let length = limbs
+            .iter()
+            .rposition(|&limb| limb != 0)
+            .map_or(0, |i| i + 1);
+
+        Bignum {
+            negative: integer < 0,
+            limbs: limbs[..length].to_vec(),
+        }
+    }
+}
+
+pub struct ParseBignumError;
+
+impl FromStr for Bignum {
+    type Err = ParseBignumError;
+
+    /// Reads a decimal integer with an optional sign.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+
+        if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(ParseBignumError);
+        }
+
+        let mut bignum = Bignum {
+            negative: false,
+            limbs: Vec::new(),
+        };
+        for digit in digits.bytes() {
+            bignum.multiply_add(10, u32::from(digit - b'0'));
+        }
+        bignum.negative = negative && !bignum.limbs.is_empty();
+
+        Ok(bignum)
+    }
+}
+
+impl fmt::Display for Bignum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Groups of nine digits, least significant first.
+        let mut groups = Vec::new();
+        let mut magnitude = self.clone();
+        while !magnitude.limbs.is_empty() {
+            groups.push(magnitude.divide(1_000_000_000));
+        }
+
+        if self.negative {
+            f.write_str("-")?;
+        }
+        match groups.split_last() {
+            None => f.write_str("0"),
+            Some((first, rest)) => {
+                write!(f, "{}", first)?;
+                for group in rest.iter().rev() {
+                    write!(f, "{:09}", group)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimal() {
+        let cases = [
+            "0",
+            "1",
+            "-1",
+            "4294967296",
+            "9223372036854775808",
+            "-123456789012345678901234567890",
+            "1000000000000000000000000000000000000000",
+        ];
+
+        for case in &cases {
+            let bignum: Bignum = case.parse().ok().unwrap();
+            assert_eq!(bignum.to_string(), *case);
+        }
+        assert_eq!("+007".parse::<Bignum>().ok().unwrap().to_string(), "7");
+        assert_eq!("-0".parse::<Bignum>().ok().unwrap().to_string(), "0");
+        assert!("1a".parse::<Bignum>().is_err());
+        assert!("-".parse::<Bignum>().is_err());
+    }
+
+    #[test]
+    fn limbs() {
+        assert_eq!(Bignum::from(0).limbs, []);
+        assert_eq!(Bignum::from(-1).limbs, [1]);
+        assert_eq!(Bignum::from(1 << 32).limbs, [0, 1]);
+        assert_eq!(Bignum::from(i64::MIN).limbs, [0, 0x8000_0000]);
+
+        for &integer in &[0, 1, -1, 1 << 40, i64::MAX, i64::MIN] {
+            assert_eq!(Bignum::from(integer).to_i64(), Some(integer));
+        }
+        let big: Bignum = "9223372036854775808".parse().ok().unwrap();
+        assert_eq!(big.to_i64(), None);
+        let small: Bignum = "-9223372036854775809".parse().ok().unwrap();
+        assert_eq!(small.to_i64(), None);
+    }
+}
@@ -0,0 +1,99 @@
+use std::{fmt, str::FromStr};
+
+use super::Expression;
+
+#[derive(Debug, PartialEq)]
+pub struct Let {
+    pub kind: LetKind,
+    pub bindings: Vec<Binding>,
+    pub body: Box<Expression>,
+}
+
+impl Let {
+    pub fn new(kind: LetKind, bindings: Vec<Binding>, body: Expression) -> Self {
+        Self {
+            kind,
+            bindings,
+            body: Box::new(body),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Binding {
+    pub name: String,
+    pub expression: Expression,
+}
+
+impl Binding {
+    pub fn new(name: impl Into<String>, expression: Expression) -> Self {
+        Self {
+            name: name.into(),
+            expression,
+        }
+    }
+}
+
+/// An assignment `(set! name value)`.
+#[derive(Debug, PartialEq)]
+pub struct Set {
+    pub name: String,
+    pub value: Box<Expression>,
+}
+
+impl Set {
+    pub fn new(name: impl Into<String>, value: Expression) -> Self {
+        Self {
+            name: name.into(),
+            value: Box::new(value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LetKind {
+    Let,
+    LetStar,
+}
+
+pub struct ParseLetKindError;
+
+impl FromStr for LetKind {
+    type Err = ParseLetKindError;
+
+    fn from_str(symbol: &str) -> Result<Self, Self::Err> {
+        match symbol {
+            "let" => Ok(LetKind::Let),
+            "let*" => Ok(LetKind::LetStar),
+            _ => Err(ParseLetKindError),
+        }
+    }
+}
+
+impl fmt::Display for LetKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LetKind::Let => f.write_str("let"),
+            LetKind::LetStar => f.write_str("let*"),
+        }
+    }
+}
+
+impl fmt::Display for Let {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({} (", self.kind)?;
+        for (i, binding) in self.bindings.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "({} {})", binding.name, binding.expression)?;
+        }
+        write!(f, ") {})", self.body)
+    }
+}
+
+impl fmt::Display for Set {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(set! {} {})", self.name, self.value)
+    }
+}
@@ -4,19 +4,54 @@ use super::Expression;
 
 #[derive(Debug, PartialEq)]
 pub struct Call {
-    pub primitive: UnaryPrimitive,
-    pub expression: Box<Expression>,
+    pub primitive: Primitive,
+    pub arguments: Vec<Expression>,
 }
 
 impl Call {
-    pub fn new(primitive: UnaryPrimitive, expression: Expression) -> Self {
+    pub fn new(primitive: impl Into<Primitive>, arguments: Vec<Expression>) -> Self {
         Self {
-            primitive,
-            expression: Box::new(expression),
+            primitive: primitive.into(),
+            arguments,
         }
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub enum Primitive {
+    Unary(UnaryPrimitive),
+    Binary(BinaryPrimitive),
+    Ternary(TernaryPrimitive),
+}
+
+impl Primitive {
+    pub fn arity(&self) -> usize {
+        match self {
+            Primitive::Unary(_) => 1,
+            Primitive::Binary(_) => 2,
+            Primitive::Ternary(_) => 3,
+        }
+    }
+}
+
+impl From<UnaryPrimitive> for Primitive {
+    fn from(primitive: UnaryPrimitive) -> Self {
+        Primitive::Unary(primitive)
+    }
+}
+
+impl From<BinaryPrimitive> for Primitive {
+    fn from(primitive: BinaryPrimitive) -> Self {
+        Primitive::Binary(primitive)
+    }
+}
+
+impl From<TernaryPrimitive> for Primitive {
+    fn from(primitive: TernaryPrimitive) -> Self {
+        Primitive::Ternary(primitive)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum UnaryPrimitive {
     FxAdd1,
@@ -30,12 +65,90 @@ pub enum UnaryPrimitive {
     IsFixnum,
     IsBoolean,
     IsCharacter,
+    IsPair,
+    Car,
+    Cdr,
+    MakeVector,
+    VectorLength,
+    IsVector,
+    MakeString,
+    StringLength,
+    IsString,
+    FlSqrt,
+    IsFlonum,
+    ExactToInexact,
+    InexactToExact,
+    Numerator,
+    Denominator,
+    // Boxes hold assigned variables. They are introduced by assignment
+    // conversion and cannot be named in source.
+    Box,
+    Unbox,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BinaryPrimitive {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Quotient,
+    Remainder,
+    FxAdd,
+    FxSub,
+    FxMul,
+    FxLogAnd,
+    FxLogOr,
+    FxEqual,
+    FxLess,
+    FxLessOrEqual,
+    FxGreater,
+    FxGreaterOrEqual,
+    CharEqual,
+    CharLess,
+    CharLessOrEqual,
+    CharGreater,
+    CharGreaterOrEqual,
+    FlAdd,
+    FlSub,
+    FlMul,
+    FlDiv,
+    FlEqual,
+    FlLess,
+    FlLessOrEqual,
+    FlGreater,
+    FlGreaterOrEqual,
+    Cons,
+    SetCar,
+    SetCdr,
+    VectorRef,
+    StringRef,
+    // See `UnaryPrimitive::Box`.
+    SetBox,
 }
 
-pub struct ParseUnaryPrimitiveError;
+#[derive(Debug, PartialEq)]
+pub enum TernaryPrimitive {
+    VectorSet,
+    StringSet,
+}
+
+pub struct ParsePrimitiveError;
+
+impl FromStr for Primitive {
+    type Err = ParsePrimitiveError;
+
+    fn from_str(symbol: &str) -> Result<Self, Self::Err> {
+        symbol
+            .parse()
+            .map(Primitive::Unary)
+            .or_else(|_| symbol.parse().map(Primitive::Binary))
+            .or_else(|_| symbol.parse().map(Primitive::Ternary))
+    }
+}
 
 impl FromStr for UnaryPrimitive {
-    type Err = ParseUnaryPrimitiveError;
+    type Err = ParsePrimitiveError;
 
     fn from_str(symbol: &str) -> Result<Self, Self::Err> {
         use UnaryPrimitive::*;
@@ -52,7 +165,103 @@ impl FromStr for UnaryPrimitive {
             "fixnum?" => Ok(IsFixnum),
             "boolean?" => Ok(IsBoolean),
             "char?" => Ok(IsCharacter),
-            _ => Err(ParseUnaryPrimitiveError),
+            "pair?" => Ok(IsPair),
+            "car" => Ok(Car),
+            "cdr" => Ok(Cdr),
+            "make-vector" => Ok(MakeVector),
+            "vector-length" => Ok(VectorLength),
+            "vector?" => Ok(IsVector),
+            "make-string" => Ok(MakeString),
+            "string-length" => Ok(StringLength),
+            "string?" => Ok(IsString),
+            "flsqrt" => Ok(FlSqrt),
+            "flonum?" => Ok(IsFlonum),
+            "exact->inexact" => Ok(ExactToInexact),
+            "inexact->exact" => Ok(InexactToExact),
+            "numerator" => Ok(Numerator),
+            "denominator" => Ok(Denominator),
+            _ => Err(ParsePrimitiveError),
+        }
+    }
+}
+
+impl FromStr for BinaryPrimitive {
+    type Err = ParsePrimitiveError;
+
+    fn from_str(symbol: &str) -> Result<Self, Self::Err> {
+        use BinaryPrimitive::*;
+
+        match symbol {
+            "+" => Ok(Add),
+            "-" => Ok(Subtract),
+            "*" => Ok(Multiply),
+            "/" => Ok(Divide),
+            "quotient" => Ok(Quotient),
+            "remainder" => Ok(Remainder),
+            "fx+" => Ok(FxAdd),
+            "fx-" => Ok(FxSub),
+            "fx*" => Ok(FxMul),
+            "fxlogand" => Ok(FxLogAnd),
+            "fxlogor" => Ok(FxLogOr),
+            "fx=" => Ok(FxEqual),
+            "fx<" => Ok(FxLess),
+            "fx<=" => Ok(FxLessOrEqual),
+            "fx>" => Ok(FxGreater),
+            "fx>=" => Ok(FxGreaterOrEqual),
+            "char=?" => Ok(CharEqual),
+            "char<?" => Ok(CharLess),
+            "char<=?" => Ok(CharLessOrEqual),
+            "char>?" => Ok(CharGreater),
+            "char>=?" => Ok(CharGreaterOrEqual),
+            "fl+" => Ok(FlAdd),
+            "fl-" => Ok(FlSub),
+            "fl*" => Ok(FlMul),
+            "fl/" => Ok(FlDiv),
+            "fl=" => Ok(FlEqual),
+            "fl<" => Ok(FlLess),
+            "fl<=" => Ok(FlLessOrEqual),
+            "fl>" => Ok(FlGreater),
+            "fl>=" => Ok(FlGreaterOrEqual),
+            "cons" => Ok(Cons),
+            "set-car!" => Ok(SetCar),
+            "set-cdr!" => Ok(SetCdr),
+            "vector-ref" => Ok(VectorRef),
+            "string-ref" => Ok(StringRef),
+            _ => Err(ParsePrimitiveError),
+        }
+    }
+}
+
+impl FromStr for TernaryPrimitive {
+    type Err = ParsePrimitiveError;
+
+    fn from_str(symbol: &str) -> Result<Self, Self::Err> {
+        use TernaryPrimitive::*;
+
+        match symbol {
+            "vector-set!" => Ok(VectorSet),
+            "string-set!" => Ok(StringSet),
+            _ => Err(ParsePrimitiveError),
+        }
+    }
+}
+
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}", self.primitive)?;
+        for argument in &self.arguments {
+            write!(f, " {}", argument)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl fmt::Display for Primitive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Primitive::Unary(primitive) => write!(f, "{}", primitive),
+            Primitive::Binary(primitive) => write!(f, "{}", primitive),
+            Primitive::Ternary(primitive) => write!(f, "{}", primitive),
         }
     }
 }
@@ -73,6 +282,83 @@ impl fmt::Display for UnaryPrimitive {
             IsFixnum => "fixnum?",
             IsBoolean => "boolean?",
             IsCharacter => "char?",
+            IsPair => "pair?",
+            Car => "car",
+            Cdr => "cdr",
+            MakeVector => "make-vector",
+            VectorLength => "vector-length",
+            IsVector => "vector?",
+            MakeString => "make-string",
+            StringLength => "string-length",
+            IsString => "string?",
+            FlSqrt => "flsqrt",
+            IsFlonum => "flonum?",
+            ExactToInexact => "exact->inexact",
+            InexactToExact => "inexact->exact",
+            Numerator => "numerator",
+            Denominator => "denominator",
+            Box => "box",
+            Unbox => "unbox",
+        };
+
+        f.write_str(primitive)
+    }
+}
+
+impl fmt::Display for BinaryPrimitive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use BinaryPrimitive::*;
+
+        let primitive = match self {
+            Add => "+",
+            Subtract => "-",
+            Multiply => "*",
+            Divide => "/",
+            Quotient => "quotient",
+            Remainder => "remainder",
+            FxAdd => "fx+",
+            FxSub => "fx-",
+            FxMul => "fx*",
+            FxLogAnd => "fxlogand",
+            FxLogOr => "fxlogor",
+            FxEqual => "fx=",
+            FxLess => "fx<",
+            FxLessOrEqual => "fx<=",
+            FxGreater => "fx>",
+            FxGreaterOrEqual => "fx>=",
+            CharEqual => "char=?",
+            CharLess => "char<?",
+            CharLessOrEqual => "char<=?",
+            CharGreater => "char>?",
+            CharGreaterOrEqual => "char>=?",
+            FlAdd => "fl+",
+            FlSub => "fl-",
+            FlMul => "fl*",
+            FlDiv => "fl/",
+            FlEqual => "fl=",
+            FlLess => "fl<",
+            FlLessOrEqual => "fl<=",
+            FlGreater => "fl>",
+            FlGreaterOrEqual => "fl>=",
+            Cons => "cons",
+            SetCar => "set-car!",
+            SetCdr => "set-cdr!",
+            VectorRef => "vector-ref",
+            StringRef => "string-ref",
+            SetBox => "set-box!",
+        };
+
+        f.write_str(primitive)
+    }
+}
+
+impl fmt::Display for TernaryPrimitive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use TernaryPrimitive::*;
+
+        let primitive = match self {
+            VectorSet => "vector-set!",
+            StringSet => "string-set!",
         };
 
         f.write_str(primitive)
@@ -0,0 +1,128 @@
+use std::fmt;
+
+use super::{
+    binding::{Binding, Let, LetKind},
+    immediate::Immediate,
+    Expression,
+};
+
+#[derive(Debug, PartialEq)]
+pub struct If {
+    pub condition: Box<Expression>,
+    pub consequent: Box<Expression>,
+    pub alternative: Box<Expression>,
+}
+
+impl If {
+    pub fn new(condition: Expression, consequent: Expression, alternative: Expression) -> Self {
+        Self {
+            condition: Box::new(condition),
+            consequent: Box::new(consequent),
+            alternative: Box::new(alternative),
+        }
+    }
+}
+
+impl fmt::Display for If {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "(if {} {} {})",
+            self.condition, self.consequent, self.alternative
+        )
+    }
+}
+
+/// Name bound by the expansions of `or` and test-only `cond` clauses. It
+/// contains a space, so the lexer can never produce it as a symbol and it
+/// cannot capture a variable from the program.
+const TEMPORARY: &str = "or temporary";
+
+fn unspecified() -> Expression {
+    Expression::Immediate(Immediate::Boolean(false))
+}
+
+/// `(and e ...)` becomes `(if e (and ...) #f)`.
+pub fn and(mut expressions: Vec<Expression>) -> Expression {
+    let mut result = expressions
+        .pop()
+        .unwrap_or(Expression::Immediate(Immediate::Boolean(true)));
+
+    while let Some(expression) = expressions.pop() {
+        result = Expression::If(If::new(
+            expression,
+            result,
+            Expression::Immediate(Immediate::Boolean(false)),
+        ));
+    }
+
+    result
+}
+
+/// `(or e ...)` becomes `(let ((t e)) (if t t (or ...)))`.
+pub fn or(mut expressions: Vec<Expression>) -> Expression {
+    let mut result = expressions
+        .pop()
+        .unwrap_or(Expression::Immediate(Immediate::Boolean(false)));
+
+    while let Some(expression) = expressions.pop() {
+        result = either(expression, result);
+    }
+
+    result
+}
+
+fn either(expression: Expression, otherwise: Expression) -> Expression {
+    Expression::Let(Let::new(
+        LetKind::Let,
+        vec![Binding::new(TEMPORARY, expression)],
+        Expression::If(If::new(
+            Expression::Variable(String::from(TEMPORARY)),
+            Expression::Variable(String::from(TEMPORARY)),
+            otherwise,
+        )),
+    ))
+}
+
+pub fn when(condition: Expression, body: Expression) -> Expression {
+    Expression::If(If::new(condition, body, unspecified()))
+}
+
+pub fn unless(condition: Expression, body: Expression) -> Expression {
+    Expression::If(If::new(condition, unspecified(), body))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Clause {
+    Test(Expression),
+    Consequent(Expression, Expression),
+    Else(Expression),
+}
+
+/// Rewrites `cond` clauses into nested `if` expressions. An `else` clause is
+/// only accepted in the last position.
+pub fn cond(clauses: Vec<Clause>) -> Option<Expression> {
+    let mut clauses = clauses.into_iter().rev();
+    let mut result = match clauses.next() {
+        Some(Clause::Else(body)) => body,
+        Some(clause) => clause_or(clause, unspecified()),
+        None => unspecified(),
+    };
+
+    for clause in clauses {
+        if let Clause::Else(_) = clause {
+            return None;
+        }
+        result = clause_or(clause, result);
+    }
+
+    Some(result)
+}
+
+fn clause_or(clause: Clause, otherwise: Expression) -> Expression {
+    match clause {
+        Clause::Test(test) => either(test, otherwise),
+        Clause::Consequent(test, body) => Expression::If(If::new(test, body, otherwise)),
+        Clause::Else(body) => body,
+    }
+}
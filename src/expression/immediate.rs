@@ -1,5 +1,7 @@
 use std::fmt;
 
+use crate::lex::write_character;
+
 pub const BOOLEAN_BIT: i32 = 6;
 pub const BOOLEAN_MASK: i32 = 0xbf;
 pub const BOOLEAN_TRUE: i32 = 0x6f;
@@ -9,6 +11,10 @@ pub const INTEGER_MASK: i32 = 0x03;
 pub const INTEGER_TAG: i32 = 0x00;
 pub const INTEGER_SHIFT: i32 = 2;
 
+// Fixnums are the integers that fit in a word after shifting.
+pub const FIXNUM_MIN: i64 = i64::MIN >> INTEGER_SHIFT;
+pub const FIXNUM_MAX: i64 = i64::MAX >> INTEGER_SHIFT;
+
 pub const CHARACTER_MASK: i32 = 0x3f;
 pub const CHARACTER_TAG: i32 = 0x0f;
 pub const CHARACTER_SHIFT: i32 = 8;
@@ -19,20 +25,20 @@ pub const NULL: i32 = 0x3f;
 pub enum Immediate {
     Null,
     Boolean(bool),
-    Character(u8),
-    Integer(i32),
+    Character(char),
+    Integer(i64),
 }
 
-impl From<Immediate> for i32 {
+impl From<Immediate> for i64 {
     fn from(immediate: Immediate) -> Self {
         use Immediate::*;
 
         match immediate {
-            Boolean(true) => BOOLEAN_TRUE,
-            Boolean(false) => BOOLEAN_FALSE,
+            Boolean(true) => BOOLEAN_TRUE.into(),
+            Boolean(false) => BOOLEAN_FALSE.into(),
             Integer(n) => n << INTEGER_SHIFT,
-            Character(c) => (i32::from(c) << CHARACTER_SHIFT) | CHARACTER_TAG,
-            Null => NULL,
+            Character(c) => (i64::from(u32::from(c)) << CHARACTER_SHIFT) | i64::from(CHARACTER_TAG),
+            Null => NULL.into(),
         }
     }
 }
@@ -45,11 +51,7 @@ impl fmt::Display for Immediate {
             Null => f.write_str("()"),
             Boolean(true) => f.write_str("#t"),
             Boolean(false) => f.write_str("#f"),
-            Character(b'\n') => f.write_str("#\\newline"),
-            Character(b'\r') => f.write_str("#\\return"),
-            Character(b' ') => f.write_str("#\\space"),
-            Character(b'\t') => f.write_str("#\\tab"),
-            Character(character) => write!(f, "#\\{}", *character as char),
+            Character(character) => write_character(f, *character),
             Integer(integer) => write!(f, "{}", integer),
         }
     }
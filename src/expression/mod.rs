@@ -1,38 +1,58 @@
 use std::{fmt, str::FromStr};
 
 use super::{
-    lex::lex,
+    lex::{lex, write_flonum},
     parse::{parse, ParseError},
 };
 
+pub mod bignum;
+pub mod binding;
 pub mod call;
+pub mod conditional;
 pub mod immediate;
+pub mod procedure;
+pub mod program;
 
+use bignum::Bignum;
+use binding::{Let, Set};
 use call::Call;
+use conditional::If;
 use immediate::Immediate;
+use procedure::{Application, Closure, LabelCall, Labels, Lambda};
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Expression {
+    Application(Application),
+    Begin(Vec<Expression>),
+    Bignum(Bignum),
     Call(Call),
+    Closure(Closure),
+    Flonum(f64),
+    If(If),
     Immediate(Immediate),
+    LabelCall(LabelCall),
+    Labels(Labels),
+    Lambda(Lambda),
+    Let(Let),
+    // A fraction as written, which is reduced when it is evaluated.
+    Rational(Bignum, Bignum),
+    Set(Set),
+    String(String),
+    Variable(String),
+    Vector(Vec<Expression>),
 }
 
-impl PartialEq for Expression {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (
-                Expression::Call(Call {
-                    primitive: p1,
-                    expression: e1,
-                }),
-                Expression::Call(Call {
-                    primitive: p2,
-                    expression: e2,
-                }),
-            ) => p1 == p2 && e1 == e2,
-            (Expression::Immediate(i1), Expression::Immediate(i2)) => i1 == i2,
-            _ => false,
-        }
+impl Expression {
+    pub fn is_literal(&self) -> bool {
+        matches!(
+            self,
+            Expression::Bignum(_)
+                | Expression::Flonum(_)
+                | Expression::Immediate(_)
+                | Expression::Rational(..)
+                | Expression::String(_)
+                | Expression::Vector(_)
+        )
     }
 }
 
@@ -41,8 +61,51 @@ impl fmt::Display for Expression {
         use Expression::*;
 
         match self {
-            Call(call) => write!(f, "({} {})", call.primitive, call.expression),
+            Application(application) => write!(f, "{}", application),
+            Begin(expressions) => {
+                f.write_str("(begin")?;
+                for expression in expressions {
+                    write!(f, " {}", expression)?;
+                }
+                f.write_str(")")
+            }
+            Bignum(bignum) => write!(f, "{}", bignum),
+            Call(call) => write!(f, "{}", call),
+            Closure(closure) => write!(f, "{}", closure),
+            Flonum(flonum) => write_flonum(f, *flonum),
+            If(conditional) => write!(f, "{}", conditional),
             Immediate(immediate) => write!(f, "{}", immediate),
+            LabelCall(call) => write!(f, "{}", call),
+            Labels(labels) => write!(f, "{}", labels),
+            Lambda(lambda) => write!(f, "{}", lambda),
+            Let(binding) => write!(f, "{}", binding),
+            Rational(numerator, denominator) => write!(f, "{}/{}", numerator, denominator),
+            Set(assignment) => write!(f, "{}", assignment),
+            String(string) => {
+                f.write_str("\"")?;
+                for character in string.chars() {
+                    match character {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\t' => f.write_str("\\t")?,
+                        '\r' => f.write_str("\\r")?,
+                        _ => write!(f, "{}", character)?,
+                    }
+                }
+                f.write_str("\"")
+            }
+            Variable(name) => f.write_str(name),
+            Vector(elements) => {
+                f.write_str("#(")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                f.write_str(")")
+            }
         }
     }
 }
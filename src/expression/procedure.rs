@@ -0,0 +1,173 @@
+use std::fmt;
+
+use super::Expression;
+
+#[derive(Debug, PartialEq)]
+pub struct Labels {
+    pub labels: Vec<Label>,
+    pub body: Box<Expression>,
+}
+
+impl Labels {
+    pub fn new(labels: Vec<Label>, body: Expression) -> Self {
+        Self {
+            labels,
+            body: Box::new(body),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Label {
+    pub name: String,
+    pub code: Code,
+}
+
+impl Label {
+    pub fn new(name: impl Into<String>, code: Code) -> Self {
+        Self {
+            name: name.into(),
+            code,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Code {
+    pub parameters: Vec<String>,
+    pub free: Vec<String>,
+    pub body: Box<Expression>,
+}
+
+impl Code {
+    pub fn new(parameters: Vec<String>, body: Expression) -> Self {
+        Self {
+            parameters,
+            free: Vec::new(),
+            body: Box::new(body),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LabelCall {
+    pub label: String,
+    pub arguments: Vec<Expression>,
+}
+
+impl LabelCall {
+    pub fn new(label: impl Into<String>, arguments: Vec<Expression>) -> Self {
+        Self {
+            label: label.into(),
+            arguments,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Lambda {
+    pub parameters: Vec<String>,
+    pub body: Box<Expression>,
+}
+
+impl Lambda {
+    pub fn new(parameters: Vec<String>, body: Expression) -> Self {
+        Self {
+            parameters,
+            body: Box::new(body),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Application {
+    pub function: Box<Expression>,
+    pub arguments: Vec<Expression>,
+}
+
+impl Application {
+    pub fn new(function: Expression, arguments: Vec<Expression>) -> Self {
+        Self {
+            function: Box::new(function),
+            arguments,
+        }
+    }
+}
+
+/// A closure over the code bound to `label`, capturing the current values of
+/// the `free` variables. Produced by closure conversion.
+#[derive(Debug, PartialEq)]
+pub struct Closure {
+    pub label: String,
+    pub free: Vec<String>,
+}
+
+impl Closure {
+    pub fn new(label: impl Into<String>, free: Vec<String>) -> Self {
+        Self {
+            label: label.into(),
+            free,
+        }
+    }
+}
+
+impl fmt::Display for Labels {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("(labels (")?;
+        for (i, label) in self.labels.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "({} {})", label.name, label.code)?;
+        }
+        write!(f, ") {})", self.body)
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "(code ({}) ({}) {})",
+            self.parameters.join(" "),
+            self.free.join(" "),
+            self.body
+        )
+    }
+}
+
+impl fmt::Display for LabelCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(labelcall {}", self.label)?;
+        for argument in &self.arguments {
+            write!(f, " {}", argument)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl fmt::Display for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(lambda ({}) {})", self.parameters.join(" "), self.body)
+    }
+}
+
+impl fmt::Display for Application {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}", self.function)?;
+        for argument in &self.arguments {
+            write!(f, " {}", argument)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl fmt::Display for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(closure {}", self.label)?;
+        for name in &self.free {
+            write!(f, " {}", name)?;
+        }
+        f.write_str(")")
+    }
+}
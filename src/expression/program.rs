@@ -0,0 +1,94 @@
+use std::{fmt, str::FromStr};
+
+use super::{
+    immediate::Immediate,
+    procedure::{Label, Labels},
+    Expression,
+};
+use crate::{
+    lex::lex,
+    parse::{parse_program, ParseError},
+};
+
+#[derive(Debug, PartialEq)]
+pub enum Form {
+    Definition(Label),
+    Expression(Expression),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Program {
+    pub forms: Vec<Form>,
+}
+
+impl Program {
+    pub fn new(forms: Vec<Form>) -> Self {
+        Self { forms }
+    }
+
+    /// Gathers the definitions, including those of top-level `labels` forms,
+    /// into a single `labels` form whose body evaluates the remaining forms in
+    /// order. The value of the program is the value of its last expression,
+    /// or `#f` if it has none.
+    pub fn into_expression(self) -> Expression {
+        let mut labels = Vec::new();
+        let mut expressions = Vec::new();
+
+        for form in self.forms {
+            match form {
+                Form::Definition(label) => labels.push(label),
+                Form::Expression(Expression::Labels(Labels {
+                    labels: inner,
+                    body,
+                })) => {
+                    labels.extend(inner);
+                    expressions.push(*body);
+                }
+                Form::Expression(expression) => expressions.push(expression),
+            }
+        }
+
+        let body = match expressions.len() {
+            0 => Expression::Immediate(Immediate::Boolean(false)),
+            1 => expressions.pop().unwrap(),
+            _ => Expression::Begin(expressions),
+        };
+
+        Expression::Labels(Labels::new(labels, body))
+    }
+}
+
+impl fmt::Display for Form {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Form::Definition(Label { name, code }) => {
+                write!(f, "(define ({}", name)?;
+                for parameter in &code.parameters {
+                    write!(f, " {}", parameter)?;
+                }
+                write!(f, ") {})", code.body)
+            }
+            Form::Expression(expression) => write!(f, "{}", expression),
+        }
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, form) in self.forms.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{}", form)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Program {
+    type Err = ParseError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        parse_program(&mut lex(source))
+    }
+}
@@ -1,17 +1,91 @@
-use std::ops::Range;
+use std::{fmt, ops::Range};
 
-#[derive(Debug, Eq, PartialEq)]
+use crate::expression::bignum::Bignum;
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Token<'a> {
     LeftParenthesis,
     RightParenthesis,
+    HashParenthesis,
     Null,
     Boolean(bool),
-    Character(u8),
-    Integer(i32),
+    Character(char),
+    Integer(i64),
+    // An integer literal that does not fit in 64 bits.
+    Bignum(Bignum),
+    Flonum(f64),
+    // A numerator and a positive denominator, not necessarily in lowest terms.
+    Rational(Bignum, Bignum),
+    String(String),
     Symbol(&'a str),
+    DatumComment,
     Unrecognized,
 }
 
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::LeftParenthesis => f.write_str("("),
+            Token::RightParenthesis => f.write_str(")"),
+            Token::HashParenthesis => f.write_str("#("),
+            Token::Null => f.write_str("()"),
+            Token::Boolean(true) => f.write_str("#t"),
+            Token::Boolean(false) => f.write_str("#f"),
+            Token::Character(character) => write_character(f, *character),
+            Token::Integer(integer) => write!(f, "{}", integer),
+            Token::Bignum(bignum) => write!(f, "{}", bignum),
+            Token::Flonum(flonum) => write_flonum(f, *flonum),
+            Token::Rational(numerator, denominator) => {
+                write!(f, "{}/{}", numerator, denominator)
+            }
+            Token::String(string) => write!(f, "{:?}", string),
+            Token::Symbol(symbol) => f.write_str(symbol),
+            Token::DatumComment => f.write_str("#;"),
+            Token::Unrecognized => f.write_str("unrecognized token"),
+        }
+    }
+}
+
+// The characters written by name rather than as themselves.
+const CHARACTER_NAMES: [(&str, char); 9] = [
+    ("alarm", '\x07'),
+    ("backspace", '\x08'),
+    ("delete", '\x7f'),
+    ("escape", '\x1b'),
+    ("newline", '\n'),
+    ("null", '\0'),
+    ("return", '\r'),
+    ("space", ' '),
+    ("tab", '\t'),
+];
+
+/// Writes `character` the way it is read: by name if it has one, as a hex
+/// escape if it is otherwise invisible, and as itself if not.
+pub fn write_character(f: &mut fmt::Formatter, character: char) -> fmt::Result {
+    match CHARACTER_NAMES
+        .iter()
+        .find(|(_, named)| *named == character)
+    {
+        Some((name, _)) => write!(f, "#\\{}", name),
+        None if character.is_control() => {
+            write!(f, "#\\x{:x}", u32::from(character))
+        }
+        None => write!(f, "#\\{}", character),
+    }
+}
+
+/// Writes `flonum` the way the runtime prints it: with a decimal point or an
+/// exponent, so that it reads back as a flonum.
+pub fn write_flonum(f: &mut fmt::Formatter, flonum: f64) -> fmt::Result {
+    if flonum.is_nan() {
+        f.write_str("+nan.0")
+    } else if flonum.is_infinite() {
+        f.write_str(if flonum < 0.0 { "-inf.0" } else { "+inf.0" })
+    } else {
+        write!(f, "{:?}", flonum)
+    }
+}
+
 pub fn lex(source: &str) -> impl Iterator<Item = (Token<'_>, Range<usize>)> {
     Lexer { source, start: 0 }
 }
@@ -28,18 +102,26 @@ enum State {
     LeftParenthesis,
     Null,
     Hash,
+    HashParenthesis,
     Slash,
-    Newline,
-    Return,
-    Space,
-    Tab,
     Character,
     False,
     True,
     Sign,
-    Integer,
+    Number,
     Symbol,
+    String,
+    StringEscape,
+    StringEnd,
     Whitespace,
+    Comment,
+    // A block comment nested this deep, possibly just after a `|` or `#`
+    // that could start the delimiter of an inner or the current one.
+    BlockComment(usize),
+    BlockCommentBar(usize),
+    BlockCommentHash(usize),
+    BlockCommentEnd,
+    DatumComment,
 }
 
 impl<'a> Iterator for Lexer<'a> {
@@ -55,18 +137,12 @@ impl<'a> Iterator for Lexer<'a> {
                 let mut state = Start;
                 let mut end = self.start;
 
-                while let Some(next) = transition(&state, &self.source[end..]) {
-                    end += match next {
-                        Newline => 7,
-                        Return => 6,
-                        Space => 5,
-                        Tab => 3,
-                        _ => 1,
-                    };
+                while let Some(next) = transition(&state, &self.source.as_bytes()[end..]) {
+                    end += 1;
                     state = next;
                 }
 
-                if state == Whitespace {
+                if matches!(state, Whitespace | Comment | BlockCommentEnd) {
                     self.start = end;
                     continue;
                 } else {
@@ -74,8 +150,15 @@ impl<'a> Iterator for Lexer<'a> {
                     let slice = &self.source[start..end];
                     let token = accept(&state, slice);
 
-                    if token == Token::Unrecognized {
-                        end += 1;
+                    // A token that is complete but malformed ends where it
+                    // is; any other takes the byte that could not be lexed.
+                    if token == Token::Unrecognized
+                        && !matches!(state, Character | Number | StringEnd)
+                    {
+                        end = (end + 1).min(self.source.len());
+                        while !self.source.is_char_boundary(end) {
+                            end += 1;
+                        }
                     }
                     self.start = end;
 
@@ -100,59 +183,72 @@ const fn is_symbol(byte: u8) -> bool {
         | b'a'..=b'z'
         | b'|'
         | b'~'
+        | 0x80..=0xff
     )
 }
 
-fn transition(state: &State, source: &str) -> Option<State> {
+fn transition(state: &State, source: &[u8]) -> Option<State> {
     use State::*;
 
-    let byte = source.as_bytes().get(0)?;
+    let byte = source.first()?;
 
     match state {
         Start => match byte {
             b'(' => Some(LeftParenthesis),
             b')' => Some(RightParenthesis),
             b'#' => Some(Hash),
+            b'"' => Some(String),
+            b';' => Some(Comment),
             b'+' | b'-' => Some(Sign),
-            _ if byte.is_ascii_digit() => Some(Integer),
+            _ if byte.is_ascii_digit() => Some(Number),
             _ if is_symbol(*byte) => Some(Symbol),
             _ if byte.is_ascii_whitespace() => Some(Whitespace),
             _ => None,
         },
         LeftParenthesis if *byte == b')' => Some(Null),
         Hash => match byte {
+            b'(' => Some(HashParenthesis),
             b'\\' => Some(Slash),
             b'f' => Some(False),
             b't' => Some(True),
+            b'|' => Some(BlockComment(1)),
+            b';' => Some(DatumComment),
             _ => None,
         },
-        Slash => {
-            if source.starts_with("newline") {
-                Some(Newline)
-            } else if source.starts_with("return") {
-                Some(Return)
-            } else if source.starts_with("space") {
-                Some(Space)
-            } else if source.starts_with("tab") {
-                Some(Tab)
-            } else if byte.is_ascii_graphic() {
-                Some(Character)
-            } else {
-                None
-            }
-        }
+        Slash if byte.is_ascii_graphic() || !byte.is_ascii() => Some(Character),
+        // The rest of a name, a hex escape or a character that is not ASCII.
+        Character if is_symbol(*byte) => Some(Character),
         Sign => {
             if byte.is_ascii_digit() {
-                Some(Integer)
+                Some(Number)
             } else if is_symbol(*byte) {
                 Some(Symbol)
             } else {
                 None
             }
         }
-        Integer if byte.is_ascii_digit() => Some(Integer),
+        // Anything that could continue a symbol continues a number, so that
+        // `1x` is not a number followed by a symbol.
+        Number if is_symbol(*byte) => Some(Number),
         Symbol if is_symbol(*byte) => Some(Symbol),
+        String => match byte {
+            b'"' => Some(StringEnd),
+            b'\\' => Some(StringEscape),
+            _ => Some(String),
+        },
+        StringEscape => Some(String),
         Whitespace if byte.is_ascii_whitespace() => Some(Whitespace),
+        Comment if *byte != b'\n' => Some(Comment),
+        BlockComment(depth) | BlockCommentBar(depth) | BlockCommentHash(depth) => {
+            Some(match (state, byte) {
+                (BlockCommentBar(1), b'#') => BlockCommentEnd,
+                (BlockCommentBar(depth), b'#') => BlockComment(depth - 1),
+                (BlockCommentHash(depth), b'|') => BlockComment(depth + 1),
+                (_, b'|') => BlockCommentBar(*depth),
+                (_, b'#') => BlockCommentHash(*depth),
+                _ => BlockComment(*depth),
+            })
+        }
         _ => None,
     }
 }
@@ -164,49 +260,178 @@ fn accept<'a>(state: &State, slice: &'a str) -> Token<'a> {
         State::Null => Token::Null,
         State::False => Token::Boolean(false),
         State::True => Token::Boolean(true),
-        State::Newline => Token::Character(b'\n'),
-        State::Return => Token::Character(b'\r'),
-        State::Space => Token::Character(b' '),
-        State::Tab => Token::Character(b'\t'),
-        State::Character => Token::Character(slice.as_bytes()[2]),
-        State::Integer => {
-            if let Ok(integer) = slice.parse() {
-                Token::Integer(integer)
+        State::Character => match character(&slice[2..]) {
+            Some(character) => Token::Character(character),
+            None => Token::Unrecognized,
+        },
+        State::Number => number(slice).unwrap_or(Token::Unrecognized),
+        State::Sign | State::Symbol => number(slice).unwrap_or(Token::Symbol(slice)),
+        State::HashParenthesis => Token::HashParenthesis,
+        State::DatumComment => Token::DatumComment,
+        State::StringEnd => {
+            if let Some(string) = unescape(&slice[1..slice.len() - 1]) {
+                Token::String(string)
             } else {
                 Token::Unrecognized
             }
         }
-        State::Sign | State::Symbol => Token::Symbol(slice),
         _ => Token::Unrecognized,
     }
 }
 
+// Integers of any size, fractions like `-1/3`, and decimals with an optional
+// exponent, like `.5`, `-1.25` and `1e10`, which are flonums as are the
+// infinities and NaN.
+fn number(slice: &str) -> Option<Token<'static>> {
+    let is_decimal = |byte: u8| byte.is_ascii_digit() || b"+-.eE".contains(&byte);
+
+    match slice {
+        "+inf.0" => Some(Token::Flonum(f64::INFINITY)),
+        "-inf.0" => Some(Token::Flonum(f64::NEG_INFINITY)),
+        "+nan.0" | "-nan.0" => Some(Token::Flonum(f64::NAN)),
+        _ if slice.contains('/') => {
+            let (numerator, denominator) = slice.split_once('/')?;
+            if !denominator.bytes().all(|byte| byte.is_ascii_digit()) {
+                return None;
+            }
+            match (numerator.parse::<Bignum>(), denominator.parse::<Bignum>()) {
+                (Ok(numerator), Ok(denominator)) if !denominator.limbs.is_empty() => {
+                    Some(Token::Rational(numerator, denominator))
+                }
+                _ => None,
+            }
+        }
+        _ => match slice.parse::<Bignum>() {
+            Ok(bignum) => Some(match bignum.to_i64() {
+                Some(integer) => Token::Integer(integer),
+                None => Token::Bignum(bignum),
+            }),
+            Err(_) if slice.bytes().all(is_decimal) => slice.parse().ok().map(Token::Flonum),
+            Err(_) => None,
+        },
+    }
+}
+
+fn character(name: &str) -> Option<char> {
+    let mut characters = name.chars();
+    match (characters.next(), characters.next()) {
+        (Some(character), None) => return Some(character),
+        (Some('x'), Some(_)) if name[1..].bytes().all(|byte| byte.is_ascii_hexdigit()) => {
+            return u32::from_str_radix(&name[1..], 16)
+                .ok()
+                .and_then(char::from_u32);
+        }
+        _ => {}
+    }
+
+    CHARACTER_NAMES
+        .iter()
+        .find(|(named, _)| *named == name)
+        .map(|(_, character)| *character)
+}
+
+fn unescape(slice: &str) -> Option<String> {
+    let mut string = String::with_capacity(slice.len());
+    let mut characters = slice.chars();
+
+    while let Some(character) = characters.next() {
+        if character == '\\' {
+            string.push(match characters.next()? {
+                'a' => '\x07',
+                'b' => '\x08',
+                't' => '\t',
+                'n' => '\n',
+                'r' => '\r',
+                '"' => '"',
+                '\\' => '\\',
+                _ => return None,
+            });
+        } else {
+            string.push(character);
+        }
+    }
+
+    Some(string)
+}
+
 #[cfg(test)]
 mod tests {
     use super::Token::*;
     use super::*;
 
+    fn bignum(digits: &str) -> super::Bignum {
+        digits.parse().ok().unwrap()
+    }
+
     #[test]
     fn immediates() {
         let cases = [
             ("()", Null),
             ("#t", Boolean(true)),
             ("#f", Boolean(false)),
-            (r"#\a", Character(b'a')),
-            (r"#\z", Character(b'z')),
-            (r"#\A", Character(b'A')),
-            (r"#\Z", Character(b'Z')),
-            (r"#\newline", Character(b'\n')),
-            (r"#\return", Character(b'\r')),
-            (r"#\space", Character(b' ')),
-            (r"#\tab", Character(b'\t')),
-            (r"#\[", Character(b'[')),
-            (r"#\}", Character(b'}')),
+            (r"#\a", Character('a')),
+            (r"#\z", Character('z')),
+            (r"#\A", Character('A')),
+            (r"#\Z", Character('Z')),
+            (r"#\newline", Character('\n')),
+            (r"#\return", Character('\r')),
+            (r"#\space", Character(' ')),
+            (r"#\tab", Character('\t')),
+            (r"#\[", Character('[')),
+            (r"#\}", Character('}')),
+            (r"#\x", Character('x')),
+            (r"#\x3bb", Character('λ')),
+            (r"#\x41)", Character('A')),
+            (r"#\λ", Character('λ')),
+            (r"#\😀", Character('😀')),
+            (r"#\alarm", Character('\x07')),
+            (r"#\backspace", Character('\x08')),
+            (r"#\delete", Character('\x7f')),
+            (r"#\escape", Character('\x1b')),
+            (r"#\null", Character('\0')),
+            (r"#\(", Character('(')),
+            (r"#\bogus", Unrecognized),
+            (r"#\xd800", Unrecognized),
+            (r"#\x+41", Unrecognized),
+            ("λ", Symbol("λ")),
             ("5", Integer(5)),
             ("+128", Integer(128)),
             ("-128", Integer(-128)),
             ("2147483647", Integer(2147483647)),
             ("-2147483648", Integer(-2147483648)),
+            ("9223372036854775807", Integer(i64::MAX)),
+            ("-9223372036854775808", Integer(i64::MIN)),
+            (
+                "9223372036854775808",
+                Token::Bignum(bignum("9223372036854775808")),
+            ),
+            (
+                "-9223372036854775809",
+                Token::Bignum(bignum("-9223372036854775809")),
+            ),
+            ("2.75", Flonum(2.75)),
+            ("-.5", Flonum(-0.5)),
+            ("1.", Flonum(1.0)),
+            ("1e10", Flonum(1e10)),
+            ("+2.5E-3", Flonum(2.5e-3)),
+            ("+inf.0", Flonum(f64::INFINITY)),
+            ("-inf.0", Flonum(f64::NEG_INFINITY)),
+            ("1/3", Token::Rational(bignum("1"), bignum("3"))),
+            ("-6/4", Token::Rational(bignum("-6"), bignum("4"))),
+            (
+                "1/18446744073709551616",
+                Token::Rational(bignum("1"), bignum("18446744073709551616")),
+            ),
+            ("1/0", Unrecognized),
+            ("1/-2", Unrecognized),
+            ("1/2/3", Unrecognized),
+            ("/", Symbol("/")),
+            ("a/b", Symbol("a/b")),
+            ("1e", Unrecognized),
+            ("1x", Unrecognized),
+            ("inf", Symbol("inf")),
+            ("...", Symbol("...")),
+            ("-", Symbol("-")),
         ];
 
         for (source, expected) in &cases {
@@ -220,6 +445,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn nan() {
+        for source in &["+nan.0", "-nan.0"] {
+            match lex(source).next() {
+                Some((Flonum(flonum), _)) => assert!(flonum.is_nan()),
+                token => panic!("source = {:?}, actual = {:?}", source, token),
+            }
+        }
+    }
+
     #[test]
     fn full() {
         let source = r#"(let (a #f
@@ -241,9 +476,9 @@ mod tests {
             Symbol("b"),
             Boolean(true),
             Symbol("c"),
-            Character(b't'),
+            Character('t'),
             Symbol("d"),
-            Character(b'\t'),
+            Character('\t'),
             Symbol("e"),
             Integer(443),
             Symbol("f"),
@@ -276,6 +511,33 @@ mod tests {
         assert!(actual.eq(expected));
     }
 
+    #[test]
+    fn strings() {
+        let cases = [
+            (r#""""#, Some("")),
+            (r#""hello""#, Some("hello")),
+            (r#""hello world""#, Some("hello world")),
+            (r#""a\"b""#, Some("a\"b")),
+            (r#""a\\b""#, Some("a\\b")),
+            (r#""a\nb\tc""#, Some("a\nb\tc")),
+            (r#""(not a list)""#, Some("(not a list)")),
+            (r#""λ""#, Some("λ")),
+            (r#""a\qb""#, None),
+        ];
+
+        for (source, expected) in &cases {
+            let (actual, range) = lex(source).next().unwrap();
+
+            match expected {
+                Some(string) => {
+                    assert_eq!(actual, String(string.to_string()));
+                    assert_eq!(range, 0..source.len());
+                }
+                None => assert_eq!(actual, Unrecognized),
+            }
+        }
+    }
+
     #[test]
     fn tricky() {
         let cases = [
@@ -291,6 +553,24 @@ mod tests {
             ("", vec![]),
             (" \n\t", vec![]),
             ("[[", vec![(Unrecognized, 0..1), (Unrecognized, 1..2)]),
+            ("\"abc", vec![(Unrecognized, 0..4)]),
+            ("#é a", vec![(Unrecognized, 0..3), (Symbol("a"), 4..5)]),
+            (
+                "(1x)",
+                vec![
+                    (LeftParenthesis, 0..1),
+                    (Unrecognized, 1..3),
+                    (RightParenthesis, 3..4),
+                ],
+            ),
+            (
+                r"(#\ab)",
+                vec![
+                    (LeftParenthesis, 0..1),
+                    (Unrecognized, 1..5),
+                    (RightParenthesis, 5..6),
+                ],
+            ),
             (
                 "(not #c)",
                 vec![
@@ -308,6 +588,45 @@ mod tests {
                     (Symbol("bar"), 5..8),
                 ],
             ),
+            (
+                "; comment\n(a ; another\n b) ;",
+                vec![
+                    (LeftParenthesis, 10..11),
+                    (Symbol("a"), 11..12),
+                    (Symbol("b"), 24..25),
+                    (RightParenthesis, 25..26),
+                ],
+            ),
+            (
+                "#| outer #| inner |# still |# a #|#||#|#",
+                vec![(Symbol("a"), 30..31)],
+            ),
+            (
+                "a #| open #| nested |#",
+                vec![(Symbol("a"), 0..1), (Unrecognized, 2..22)],
+            ),
+            (
+                "#;a #; (b)",
+                vec![
+                    (DatumComment, 0..2),
+                    (Symbol("a"), 2..3),
+                    (DatumComment, 4..6),
+                    (LeftParenthesis, 7..8),
+                    (Symbol("b"), 8..9),
+                    (RightParenthesis, 9..10),
+                ],
+            ),
+            (
+                r#"#(1 "two" #())"#,
+                vec![
+                    (HashParenthesis, 0..2),
+                    (Integer(1), 2..3),
+                    (String("two".to_string()), 4..9),
+                    (HashParenthesis, 10..12),
+                    (RightParenthesis, 12..13),
+                    (RightParenthesis, 13..14),
+                ],
+            ),
         ];
 
         for (source, expected) in &cases {
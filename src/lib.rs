@@ -1,4 +1,14 @@
+pub mod analyze;
+pub mod assignment;
+pub mod closure;
 pub mod compile;
+pub mod datum;
+pub mod diagnostic;
+pub mod driver;
 pub mod expression;
 pub mod lex;
 pub mod parse;
+pub mod repl;
+pub mod syntax;
+pub mod target;
+pub mod temporary_directory;
@@ -0,0 +1,219 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+use inc::{
+    compile::Safety,
+    driver::{build, BuildError, Emit},
+    repl::{self, Session},
+    target::Target,
+    temporary_directory::TemporaryDirectory,
+};
+
+const USAGE: &str = "\
+usage: inc build <file> [-o <output>] [--emit asm|obj|exe] [--target macho|elf] [--unsafe]
+       inc run <file> [--unsafe]
+       inc repl";
+
+enum Subcommand {
+    Build {
+        input: PathBuf,
+        output: Option<PathBuf>,
+        emit: Emit,
+        target: Target,
+        safety: Safety,
+    },
+    Run {
+        input: PathBuf,
+        safety: Safety,
+    },
+    Repl,
+}
+
+fn main() {
+    let arguments: Vec<String> = env::args().skip(1).collect();
+
+    let subcommand = match parse_arguments(&arguments) {
+        Some(subcommand) => subcommand,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    process::exit(match subcommand {
+        Subcommand::Build {
+            input,
+            output,
+            emit,
+            target,
+            safety,
+        } => {
+            let output = output.unwrap_or_else(|| input.with_extension(emit.extension()));
+            compile(&input, target, safety, emit, &output).map_or(1, |()| 0)
+        }
+        Subcommand::Run { input, safety } => run(&input, safety),
+        Subcommand::Repl => read_eval_print(),
+    });
+}
+
+fn parse_arguments(arguments: &[String]) -> Option<Subcommand> {
+    let (subcommand, rest) = arguments.split_first()?;
+
+    if subcommand == "repl" {
+        return if rest.is_empty() {
+            Some(Subcommand::Repl)
+        } else {
+            None
+        };
+    }
+
+    let mut rest = rest.iter();
+
+    let mut input = None;
+    let mut output = None;
+    let mut emit = Emit::Executable;
+    let mut target = Target::host();
+    let mut safety = Safety::Safe;
+
+    while let Some(argument) = rest.next() {
+        match argument.as_str() {
+            "-o" => output = Some(PathBuf::from(rest.next()?)),
+            "--emit" => emit = rest.next()?.parse().ok()?,
+            "--target" => target = rest.next()?.parse().ok()?,
+            "--unsafe" => safety = Safety::Unsafe,
+            _ if input.is_none() && !argument.starts_with('-') => {
+                input = Some(PathBuf::from(argument))
+            }
+            _ => return None,
+        }
+    }
+
+    let input = input?;
+
+    match subcommand.as_str() {
+        "build" => Some(Subcommand::Build {
+            input,
+            output,
+            emit,
+            target,
+            safety,
+        }),
+        "run" if output.is_none() && emit == Emit::Executable && target == Target::host() => {
+            Some(Subcommand::Run { input, safety })
+        }
+        _ => None,
+    }
+}
+
+// Reports failures on standard error, so callers only pick an exit code.
+fn compile(
+    input: &Path,
+    target: Target,
+    safety: Safety,
+    emit: Emit,
+    output: &Path,
+) -> Result<(), ()> {
+    let source = fs::read_to_string(input)
+        .map_err(|error| eprintln!("inc: {}: {}", input.display(), error))?;
+
+    if is_same_file(input, output) {
+        eprintln!(
+            "inc: {}: output would overwrite the input",
+            output.display()
+        );
+        return Err(());
+    }
+
+    build(&source, target, safety, emit, output).map_err(|error| match error {
+        BuildError::Compilation(error) => eprint!("{}", error.render(&source)),
+        error => eprintln!("inc: {}", error),
+    })
+}
+
+// Whether `output` names `input`, which must exist, by the same path or
+// another one.
+fn is_same_file(input: &Path, output: &Path) -> bool {
+    input == output
+        || match (fs::canonicalize(input), fs::canonicalize(output)) {
+            (Ok(input), Ok(output)) => input == output,
+            _ => false,
+        }
+}
+
+fn run(input: &Path, safety: Safety) -> i32 {
+    let directory = match TemporaryDirectory::create() {
+        Ok(directory) => directory,
+        Err(error) => {
+            eprintln!("inc: {}", error);
+            return 1;
+        }
+    };
+    let executable = directory.0.join("program");
+
+    if compile(input, Target::host(), safety, Emit::Executable, &executable).is_err() {
+        return 1;
+    }
+
+    match Command::new(&executable).status() {
+        Ok(status) => status.code().unwrap_or(1),
+        Err(error) => {
+            eprintln!("inc: {}", error);
+            1
+        }
+    }
+}
+
+fn read_eval_print() -> i32 {
+    let mut session = Session::new();
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut input = String::new();
+
+    loop {
+        print!("{}", if input.is_empty() { "> " } else { "  " });
+        let _ = io::stdout().flush();
+
+        match lines.next() {
+            Some(Ok(line)) => {
+                input.push_str(&line);
+                input.push('\n');
+            }
+            Some(Err(error)) => {
+                eprintln!("inc: {}", error);
+                return 1;
+            }
+            None => {
+                println!();
+                return 0;
+            }
+        }
+
+        if !repl::is_complete(&input) {
+            continue;
+        }
+
+        for datum in repl::data(&input) {
+            match session.evaluate(datum) {
+                Ok(Some(value)) => println!("{}", value),
+                Ok(None) => {}
+                Err(BuildError::Compilation(error)) => {
+                    eprint!("{}", error.render(datum));
+                    break;
+                }
+                Err(BuildError::Runtime(message)) => {
+                    eprintln!("{}", message);
+                    break;
+                }
+                Err(error) => {
+                    eprintln!("inc: {}", error);
+                    break;
+                }
+            }
+        }
+        input.clear();
+    }
+}
@@ -1,153 +1,79 @@
-use std::ops::Range;
+use std::{error::Error, fmt, ops::Range};
 
-use super::{
-    expression::{
-        call::{Call, UnaryPrimitive},
-        immediate::Immediate,
-        Expression,
-    },
-    lex::Token,
-};
+use super::{analyze, datum, expression::program::Program, expression::Expression, lex::Token};
 
-#[derive(Debug)]
-pub struct ParseError;
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Range<usize>,
+}
 
-#[derive(Debug)]
-enum State {
-    Zero,
-    One(Expression),
-    Two,
-    Three(Immediate),
-    Four(UnaryPrimitive),
-    Five(Expression),
-    Six,
+#[derive(Debug, PartialEq)]
+pub enum ParseErrorKind {
+    UnrecognizedToken,
+    UnexpectedToken {
+        expected: &'static str,
+        found: String,
+    },
+    WrongArity {
+        operator: String,
+        expected: &'static str,
+        found: usize,
+    },
+    Invalid(&'static str),
 }
 
-#[derive(Debug)]
-enum Production {
-    Immediate,
-    Call,
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
 }
 
-#[derive(Debug)]
-enum Action {
-    Shift(State),
-    Reduce(Production),
-    Accept,
-    Error,
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ParseErrorKind::UnrecognizedToken => f.write_str("unrecognized token"),
+            ParseErrorKind::UnexpectedToken { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+            ParseErrorKind::WrongArity {
+                operator,
+                expected,
+                found,
+            } => write!(f, "`{}` expects {}, found {}", operator, expected, found),
+            ParseErrorKind::Invalid(message) => f.write_str(message),
+        }
+    }
 }
 
+impl Error for ParseError {}
+
+/// Parses a single expression, optionally preceded by definitions.
 pub fn parse<'a>(
     tokens: &mut impl Iterator<Item = (Token<'a>, Range<usize>)>,
 ) -> Result<Expression, ParseError> {
-    let mut stack = vec![State::Zero];
-    let mut token = tokens.next().map(|(token, _)| token);
-
-    loop {
-        if let Some(state) = stack.last() {
-            match action(state, token.as_ref()) {
-                Action::Shift(state) => {
-                    stack.push(state);
-                    token = tokens.next().map(|(token, _)| token);
-                }
-                Action::Reduce(production) => match production {
-                    Production::Immediate => {
-                        if let Some(State::Three(immediate)) = stack.pop() {
-                            match stack.last() {
-                                Some(State::Zero) => {
-                                    stack.push(State::One(Expression::Immediate(immediate)))
-                                }
-                                Some(State::Four(_)) => {
-                                    stack.push(State::Five(Expression::Immediate(immediate)))
-                                }
-                                _ => (),
-                            };
-                        } else {
-                            break Err(ParseError);
-                        }
-                    }
-                    Production::Call => {
-                        if let (
-                            Some(State::Six),
-                            Some(State::Five(expression)),
-                            Some(State::Four(primitive)),
-                            Some(State::Two),
-                        ) = (stack.pop(), stack.pop(), stack.pop(), stack.pop())
-                        {
-                            match stack.last() {
-                                Some(State::Zero) => stack.push(State::One(Expression::Call(
-                                    Call::new(primitive, expression),
-                                ))),
-                                Some(State::Four(_)) => stack.push(State::Five(Expression::Call(
-                                    Call::new(primitive, expression),
-                                ))),
-                                _ => (),
-                            };
-                        } else {
-                            break Err(ParseError);
-                        }
-                    }
-                },
-                Action::Accept => {
-                    break match stack.pop() {
-                        Some(State::One(expression)) => Ok(expression),
-                        _ => Err(ParseError),
-                    }
-                }
-                Action::Error => break Err(ParseError),
-            }
-        } else {
-            break Err(ParseError);
-        }
-    }
+    analyze::expression(datum::read(tokens)?)
 }
 
-fn action(state: &State, token: Option<&Token>) -> Action {
-    match (state, token) {
-        (State::Zero, Some(Token::LeftParenthesis)) => Action::Shift(State::Two),
-        (State::Zero, Some(Token::Null)) => Action::Shift(State::Three(Immediate::Null)),
-        (State::Zero, Some(Token::Boolean(boolean))) => {
-            Action::Shift(State::Three(Immediate::Boolean(*boolean)))
-        }
-        (State::Zero, Some(Token::Character(character))) => {
-            Action::Shift(State::Three(Immediate::Character(*character)))
-        }
-        (State::Zero, Some(Token::Integer(integer))) => {
-            Action::Shift(State::Three(Immediate::Integer(*integer)))
-        }
-        (State::One(_), None) => Action::Accept,
-        (State::Two, Some(Token::Symbol(symbol))) => {
-            if let Ok(primitive) = symbol.parse() {
-                Action::Shift(State::Four(primitive))
-            } else {
-                Action::Error
-            }
-        }
-        (State::Three(_), Some(Token::RightParenthesis)) | (State::Three(_), None) => {
-            Action::Reduce(Production::Immediate)
-        }
-        (State::Four(_), Some(Token::LeftParenthesis)) => Action::Shift(State::Two),
-        (State::Four(_), Some(Token::Null)) => Action::Shift(State::Three(Immediate::Null)),
-        (State::Four(_), Some(Token::Boolean(boolean))) => {
-            Action::Shift(State::Three(Immediate::Boolean(*boolean)))
-        }
-        (State::Four(_), Some(Token::Character(character))) => {
-            Action::Shift(State::Three(Immediate::Character(*character)))
-        }
-        (State::Four(_), Some(Token::Integer(integer))) => {
-            Action::Shift(State::Three(Immediate::Integer(*integer)))
-        }
-        (State::Five(_), Some(Token::RightParenthesis)) => Action::Shift(State::Six),
-        (State::Six, Some(Token::RightParenthesis)) | (State::Six, None) => {
-            Action::Reduce(Production::Call)
-        }
-        _ => Action::Error,
-    }
+/// Parses any number of top-level definitions and expressions.
+pub fn parse_program<'a>(
+    tokens: &mut impl Iterator<Item = (Token<'a>, Range<usize>)>,
+) -> Result<Program, ParseError> {
+    analyze::program(datum::read(tokens)?)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::expression::{
+        bignum::Bignum,
+        binding::{Binding, Let, LetKind, Set},
+        call::{BinaryPrimitive, Call, TernaryPrimitive, UnaryPrimitive},
+        conditional::If,
+        immediate::Immediate,
+        procedure::{Application, Code, Label, LabelCall, Labels, Lambda},
+        program::Form,
+    };
 
     #[test]
     fn success() {
@@ -155,100 +81,399 @@ mod tests {
             ("()", Expression::Immediate(Immediate::Null)),
             ("#t", Expression::Immediate(Immediate::Boolean(true))),
             ("#f", Expression::Immediate(Immediate::Boolean(false))),
-            (r"#\a", Expression::Immediate(Immediate::Character(b'a'))),
-            (r"#\z", Expression::Immediate(Immediate::Character(b'z'))),
+            (r"#\a", Expression::Immediate(Immediate::Character('a'))),
+            (r"#\z", Expression::Immediate(Immediate::Character('z'))),
             ("5", Expression::Immediate(Immediate::Integer(5))),
             ("-600", Expression::Immediate(Immediate::Integer(-600))),
+            (
+                "2305843009213693952",
+                Expression::Bignum(Bignum::from(2305843009213693952)),
+            ),
+            (
+                "-18446744073709551616",
+                Expression::Bignum(Bignum {
+                    negative: true,
+                    limbs: vec![0, 0, 1],
+                }),
+            ),
+            ("-1.5", Expression::Flonum(-1.5)),
+            (
+                "-2/4",
+                Expression::Rational(Bignum::from(-2), Bignum::from(4)),
+            ),
+            (
+                "(fl+ 1e3 .5)",
+                Expression::Call(Call::new(
+                    BinaryPrimitive::FlAdd,
+                    vec![Expression::Flonum(1000.0), Expression::Flonum(0.5)],
+                )),
+            ),
+            (
+                "(+ 1 2)",
+                Expression::Call(Call::new(
+                    BinaryPrimitive::Add,
+                    vec![
+                        Expression::Immediate(Immediate::Integer(1)),
+                        Expression::Immediate(Immediate::Integer(2)),
+                    ],
+                )),
+            ),
             (
                 "(fxadd1 60)",
                 Expression::Call(Call::new(
                     UnaryPrimitive::FxAdd1,
-                    Expression::Immediate(Immediate::Integer(60)),
+                    vec![Expression::Immediate(Immediate::Integer(60))],
                 )),
             ),
             (
                 "(fxsub1 5)",
                 Expression::Call(Call::new(
                     UnaryPrimitive::FxSub1,
-                    Expression::Immediate(Immediate::Integer(5)),
+                    vec![Expression::Immediate(Immediate::Integer(5))],
                 )),
             ),
             (
                 "(fxlognot 1)",
                 Expression::Call(Call::new(
                     UnaryPrimitive::FxLogNot,
-                    Expression::Immediate(Immediate::Integer(1)),
+                    vec![Expression::Immediate(Immediate::Integer(1))],
                 )),
             ),
             (
                 r"(char->fixnum #\a)",
                 Expression::Call(Call::new(
                     UnaryPrimitive::CharToFixnum,
-                    Expression::Immediate(Immediate::Character(b'a')),
+                    vec![Expression::Immediate(Immediate::Character('a'))],
                 )),
             ),
             (
                 "(fixnum->char 55)",
                 Expression::Call(Call::new(
                     UnaryPrimitive::FixnumToChar,
-                    Expression::Immediate(Immediate::Integer(55)),
+                    vec![Expression::Immediate(Immediate::Integer(55))],
                 )),
             ),
             (
                 "(fxzero? 0)",
                 Expression::Call(Call::new(
                     UnaryPrimitive::FixnumIsZero,
-                    Expression::Immediate(Immediate::Integer(0)),
+                    vec![Expression::Immediate(Immediate::Integer(0))],
                 )),
             ),
             (
                 "(null? ())",
                 Expression::Call(Call::new(
                     UnaryPrimitive::IsNull,
-                    Expression::Immediate(Immediate::Null),
+                    vec![Expression::Immediate(Immediate::Null)],
                 )),
             ),
             (
                 "(not #t)",
                 Expression::Call(Call::new(
                     UnaryPrimitive::Not,
-                    Expression::Immediate(Immediate::Boolean(true)),
+                    vec![Expression::Immediate(Immediate::Boolean(true))],
                 )),
             ),
             (
                 "(fixnum? 6)",
                 Expression::Call(Call::new(
                     UnaryPrimitive::IsFixnum,
-                    Expression::Immediate(Immediate::Integer(6)),
+                    vec![Expression::Immediate(Immediate::Integer(6))],
                 )),
             ),
             (
                 "(boolean? #f)",
                 Expression::Call(Call::new(
                     UnaryPrimitive::IsBoolean,
-                    Expression::Immediate(Immediate::Boolean(false)),
+                    vec![Expression::Immediate(Immediate::Boolean(false))],
                 )),
             ),
             (
                 r"(char? #\z)",
                 Expression::Call(Call::new(
                     UnaryPrimitive::IsCharacter,
-                    Expression::Immediate(Immediate::Character(b'z')),
+                    vec![Expression::Immediate(Immediate::Character('z'))],
                 )),
             ),
             (
                 "(char? (fixnum->char (fxadd1 55)))",
                 Expression::Call(Call::new(
                     UnaryPrimitive::IsCharacter,
-                    Expression::Call(Call::new(
+                    vec![Expression::Call(Call::new(
                         UnaryPrimitive::FixnumToChar,
+                        vec![Expression::Call(Call::new(
+                            UnaryPrimitive::FxAdd1,
+                            vec![Expression::Immediate(Immediate::Integer(55))],
+                        ))],
+                    ))],
+                )),
+            ),
+            (
+                "(fx+ 1 2)",
+                Expression::Call(Call::new(
+                    BinaryPrimitive::FxAdd,
+                    vec![
+                        Expression::Immediate(Immediate::Integer(1)),
+                        Expression::Immediate(Immediate::Integer(2)),
+                    ],
+                )),
+            ),
+            (
+                r"(char<? #\a (fixnum->char 98))",
+                Expression::Call(Call::new(
+                    BinaryPrimitive::CharLess,
+                    vec![
+                        Expression::Immediate(Immediate::Character('a')),
+                        Expression::Call(Call::new(
+                            UnaryPrimitive::FixnumToChar,
+                            vec![Expression::Immediate(Immediate::Integer(98))],
+                        )),
+                    ],
+                )),
+            ),
+            (
+                "(fx* (fx- 5 2) (fxadd1 3))",
+                Expression::Call(Call::new(
+                    BinaryPrimitive::FxMul,
+                    vec![
+                        Expression::Call(Call::new(
+                            BinaryPrimitive::FxSub,
+                            vec![
+                                Expression::Immediate(Immediate::Integer(5)),
+                                Expression::Immediate(Immediate::Integer(2)),
+                            ],
+                        )),
                         Expression::Call(Call::new(
                             UnaryPrimitive::FxAdd1,
-                            Expression::Immediate(Immediate::Integer(55)),
+                            vec![Expression::Immediate(Immediate::Integer(3))],
+                        )),
+                    ],
+                )),
+            ),
+            ("x", Expression::Variable(String::from("x"))),
+            (
+                "(let ((x 1) (y #t)) x)",
+                Expression::Let(Let::new(
+                    LetKind::Let,
+                    vec![
+                        Binding::new("x", Expression::Immediate(Immediate::Integer(1))),
+                        Binding::new("y", Expression::Immediate(Immediate::Boolean(true))),
+                    ],
+                    Expression::Variable(String::from("x")),
+                )),
+            ),
+            (
+                "(let () 5)",
+                Expression::Let(Let::new(
+                    LetKind::Let,
+                    vec![],
+                    Expression::Immediate(Immediate::Integer(5)),
+                )),
+            ),
+            (
+                "(let* ((x 1) (y (fxadd1 x))) (let ((z y)) (fx+ x z)))",
+                Expression::Let(Let::new(
+                    LetKind::LetStar,
+                    vec![
+                        Binding::new("x", Expression::Immediate(Immediate::Integer(1))),
+                        Binding::new(
+                            "y",
+                            Expression::Call(Call::new(
+                                UnaryPrimitive::FxAdd1,
+                                vec![Expression::Variable(String::from("x"))],
+                            )),
+                        ),
+                    ],
+                    Expression::Let(Let::new(
+                        LetKind::Let,
+                        vec![Binding::new("z", Expression::Variable(String::from("y")))],
+                        Expression::Call(Call::new(
+                            BinaryPrimitive::FxAdd,
+                            vec![
+                                Expression::Variable(String::from("x")),
+                                Expression::Variable(String::from("z")),
+                            ],
                         )),
                     )),
                 )),
             ),
+            (
+                "(if #t 1 2)",
+                Expression::If(If::new(
+                    Expression::Immediate(Immediate::Boolean(true)),
+                    Expression::Immediate(Immediate::Integer(1)),
+                    Expression::Immediate(Immediate::Integer(2)),
+                )),
+            ),
+            ("(and)", Expression::Immediate(Immediate::Boolean(true))),
+            (
+                "(and 1 2)",
+                Expression::If(If::new(
+                    Expression::Immediate(Immediate::Integer(1)),
+                    Expression::Immediate(Immediate::Integer(2)),
+                    Expression::Immediate(Immediate::Boolean(false)),
+                )),
+            ),
+            ("(or)", Expression::Immediate(Immediate::Boolean(false))),
+            ("(or x)", Expression::Variable(String::from("x"))),
+            (
+                "(unless (fxzero? 1) 2)",
+                Expression::If(If::new(
+                    Expression::Call(Call::new(
+                        UnaryPrimitive::FixnumIsZero,
+                        vec![Expression::Immediate(Immediate::Integer(1))],
+                    )),
+                    Expression::Immediate(Immediate::Boolean(false)),
+                    Expression::Immediate(Immediate::Integer(2)),
+                )),
+            ),
+            (
+                "(cond (#f 1) (else 2))",
+                Expression::If(If::new(
+                    Expression::Immediate(Immediate::Boolean(false)),
+                    Expression::Immediate(Immediate::Integer(1)),
+                    Expression::Immediate(Immediate::Integer(2)),
+                )),
+            ),
+            (r#""hello""#, Expression::String(String::from("hello"))),
+            ("#()", Expression::Vector(vec![])),
+            (
+                r#"#(1 #\a "b" #(()))"#,
+                Expression::Vector(vec![
+                    Expression::Immediate(Immediate::Integer(1)),
+                    Expression::Immediate(Immediate::Character('a')),
+                    Expression::String(String::from("b")),
+                    Expression::Vector(vec![Expression::Immediate(Immediate::Null)]),
+                ]),
+            ),
+            (
+                r#"(string-set! s 0 #\a)"#,
+                Expression::Call(Call::new(
+                    TernaryPrimitive::StringSet,
+                    vec![
+                        Expression::Variable(String::from("s")),
+                        Expression::Immediate(Immediate::Integer(0)),
+                        Expression::Immediate(Immediate::Character('a')),
+                    ],
+                )),
+            ),
+            (
+                "(labels ((f (code (x) (fx+ x 1)))) (labelcall f 1))",
+                Expression::Labels(Labels::new(
+                    vec![Label::new(
+                        "f",
+                        Code::new(
+                            vec![String::from("x")],
+                            Expression::Call(Call::new(
+                                BinaryPrimitive::FxAdd,
+                                vec![
+                                    Expression::Variable(String::from("x")),
+                                    Expression::Immediate(Immediate::Integer(1)),
+                                ],
+                            )),
+                        ),
+                    )],
+                    Expression::LabelCall(LabelCall::new(
+                        "f",
+                        vec![Expression::Immediate(Immediate::Integer(1))],
+                    )),
+                )),
+            ),
+            (
+                "(labels () 1)",
+                Expression::Labels(Labels::new(
+                    Vec::new(),
+                    Expression::Immediate(Immediate::Integer(1)),
+                )),
+            ),
+            (
+                "(define (f) 1) (define (g x y) (f)) (g 1 2)",
+                Expression::Labels(Labels::new(
+                    vec![
+                        Label::new(
+                            "f",
+                            Code::new(Vec::new(), Expression::Immediate(Immediate::Integer(1))),
+                        ),
+                        Label::new(
+                            "g",
+                            Code::new(
+                                vec![String::from("x"), String::from("y")],
+                                Expression::Application(Application::new(
+                                    Expression::Variable(String::from("f")),
+                                    Vec::new(),
+                                )),
+                            ),
+                        ),
+                    ],
+                    Expression::Application(Application::new(
+                        Expression::Variable(String::from("g")),
+                        vec![
+                            Expression::Immediate(Immediate::Integer(1)),
+                            Expression::Immediate(Immediate::Integer(2)),
+                        ],
+                    )),
+                )),
+            ),
+            (
+                r"(hello #\a)",
+                Expression::Application(Application::new(
+                    Expression::Variable(String::from("hello")),
+                    vec![Expression::Immediate(Immediate::Character('a'))],
+                )),
+            ),
+            (
+                "(lambda (x y) (fx+ x y))",
+                Expression::Lambda(Lambda::new(
+                    vec![String::from("x"), String::from("y")],
+                    Expression::Call(Call::new(
+                        BinaryPrimitive::FxAdd,
+                        vec![
+                            Expression::Variable(String::from("x")),
+                            Expression::Variable(String::from("y")),
+                        ],
+                    )),
+                )),
+            ),
+            (
+                "((lambda () 1))",
+                Expression::Application(Application::new(
+                    Expression::Lambda(Lambda::new(
+                        Vec::new(),
+                        Expression::Immediate(Immediate::Integer(1)),
+                    )),
+                    Vec::new(),
+                )),
+            ),
+            (
+                "(begin (fxadd1 1) 2)",
+                Expression::Begin(vec![
+                    Expression::Call(Call::new(
+                        UnaryPrimitive::FxAdd1,
+                        vec![Expression::Immediate(Immediate::Integer(1))],
+                    )),
+                    Expression::Immediate(Immediate::Integer(2)),
+                ]),
+            ),
+            (
+                "(set! x (fxadd1 x))",
+                Expression::Set(Set::new(
+                    "x",
+                    Expression::Call(Call::new(
+                        UnaryPrimitive::FxAdd1,
+                        vec![Expression::Variable(String::from("x"))],
+                    )),
+                )),
+            ),
+            (
+                "((f 1) 2)",
+                Expression::Application(Application::new(
+                    Expression::Application(Application::new(
+                        Expression::Variable(String::from("f")),
+                        vec![Expression::Immediate(Immediate::Integer(1))],
+                    )),
+                    vec![Expression::Immediate(Immediate::Integer(2))],
+                )),
+            ),
         ];
 
         for (source, expected) in &cases {
@@ -266,11 +491,134 @@ mod tests {
             "#f 45",
             "",
             " ",
-            r"(hello #\a)",
+            "(labelcall)",
+            "(fx+ 1)",
+            "(fx+ 1 2 3)",
+            "(fxadd1 1 2)",
+            "(fx= 1 2",
+            "(let ((x 1)))",
+            "(let ((x 1)) x x)",
+            "(let ((x)) x)",
+            "(let ((1 x)) x)",
+            "(let (x 1) x)",
+            "(let x)",
+            "(if)",
+            "(if #t)",
+            "(if #t 1 2 3)",
+            "(when #t)",
+            "(cond (else 1) (#t 2))",
+            "(cond ())",
+            "(cond (#t 1 2))",
+            "(cond (else))",
+            "#(x)",
+            "#((fxadd1 1))",
+            "#(1 2",
+            r#"(vector-set! v 0)"#,
+            "(labels ((f (code () 1))))",
+            "(labels ((f (code x 1))) 1)",
+            "(labels ((f (lambda () 1))) 1)",
+            "(labels () (labels () 1))",
+            "(let ((x 1)) (labels () x))",
+            "(fxadd1 (define (f) 1))",
+            "(define () 1) 1",
+            "(define (f) 1)",
+            "(lambda (x))",
+            "(lambda (x) x x)",
+            "(lambda (1) 1)",
+            "(lambda x x)",
+            "(1 2)",
+            "(begin)",
+            "(set! 1 2)",
+            "(set! x)",
+            "(set! (x) 1)",
         ];
 
         for source in &cases {
             assert!(source.parse::<Expression>().is_err());
         }
     }
+
+    #[test]
+    fn programs() {
+        let cases = [
+            ("", Vec::new()),
+            (
+                "(fxadd1 2) 5",
+                vec![
+                    Form::Expression(Expression::Call(Call::new(
+                        UnaryPrimitive::FxAdd1,
+                        vec![Expression::Immediate(Immediate::Integer(2))],
+                    ))),
+                    Form::Expression(Expression::Immediate(Immediate::Integer(5))),
+                ],
+            ),
+            (
+                "1 (define (f) 2) (f)",
+                vec![
+                    Form::Expression(Expression::Immediate(Immediate::Integer(1))),
+                    Form::Definition(Label::new(
+                        "f",
+                        Code::new(Vec::new(), Expression::Immediate(Immediate::Integer(2))),
+                    )),
+                    Form::Expression(Expression::Application(Application::new(
+                        Expression::Variable(String::from("f")),
+                        Vec::new(),
+                    ))),
+                ],
+            ),
+        ];
+
+        for (source, expected) in cases {
+            assert_eq!(source.parse::<Program>().unwrap(), Program::new(expected));
+        }
+
+        for source in &[
+            "(fxadd1 2) (",
+            "(define (f) 1) )",
+            "(fxadd1 (define (f) 1))",
+        ] {
+            assert!(source.parse::<Program>().is_err());
+        }
+    }
+
+    #[test]
+    fn diagnostics() {
+        let cases = [
+            ("", "expected expression, found end of input", 0..0),
+            ("(5 #t)", "expected operator, found `5`", 1..2),
+            ("(fxadd1 2) 5", "expected end of input, found `5`", 11..12),
+            (
+                "(fxadd1 1",
+                "expected expression or `)`, found end of input",
+                9..9,
+            ),
+            ("(let ((1 x)) x)", "expected variable name, found `1`", 7..8),
+            ("(not #c)", "unrecognized token", 5..7),
+            ("(fxadd1)", "`fxadd1` expects 1 argument, found 0", 0..8),
+            (
+                "(if #t (fx+ 1 2 3))",
+                "`fx+` expects 2 arguments, found 3",
+                7..18,
+            ),
+            (
+                "(cond (else 1) (#t 2))",
+                "`else` clause must come last",
+                0..22,
+            ),
+            ("#(1 x)", "vector literals may only contain constants", 4..5),
+            (
+                "(fxadd1 (define (f) 1))",
+                "`define` is only allowed at top level",
+                8..22,
+            ),
+            ("(set! x)", "`set!` expects 2 arguments, found 1", 0..8),
+            ("(set! 1 2)", "expected variable name, found `1`", 6..7),
+        ];
+
+        for (source, message, span) in &cases {
+            let error = source.parse::<Expression>().unwrap_err();
+            assert_eq!(error.to_string(), *message);
+            assert_eq!(error.span, *span);
+        }
+    }
 }
@@ -0,0 +1,238 @@
+use std::{ops::Range, process::Command};
+
+use super::{
+    compile::{run, CompilationError, Safety},
+    driver::{link, BuildError, Emit},
+    lex::{lex, Token},
+    target::Target,
+    temporary_directory::TemporaryDirectory,
+};
+
+/// Returns whether `input` holds complete data, that is, every list, string
+/// and block comment it opens is closed again, and every datum comment has
+/// its datum.
+pub fn is_complete(input: &str) -> bool {
+    let mut depth = 0;
+    let mut comments = 0;
+
+    for (token, span) in lex(input) {
+        match token {
+            Token::LeftParenthesis | Token::HashParenthesis => depth += 1,
+            Token::RightParenthesis => depth -= 1,
+            Token::DatumComment if depth <= 0 => {
+                comments += 1;
+                continue;
+            }
+            Token::Unrecognized if input[span.clone()].starts_with('"') => return false,
+            Token::Unrecognized if input[span].starts_with("#|") => return false,
+            _ => {}
+        }
+        if depth <= 0 && comments > 0 {
+            comments -= 1;
+        }
+    }
+
+    depth <= 0 && comments == 0
+}
+
+/// Splits `input` into its top-level data, leaving out those commented out
+/// with `#;`.
+pub fn data(input: &str) -> Vec<&str> {
+    let mut data = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut comments = 0;
+
+    for (token, span) in lex(input) {
+        if depth == 0 {
+            start = span.start;
+        }
+        match token {
+            Token::LeftParenthesis | Token::HashParenthesis => depth += 1,
+            Token::RightParenthesis if depth > 0 => depth -= 1,
+            Token::DatumComment if depth == 0 => {
+                comments += 1;
+                continue;
+            }
+            _ => {}
+        }
+        if depth == 0 {
+            if comments > 0 {
+                comments -= 1;
+            } else {
+                data.push(&input[start..span.end]);
+            }
+        }
+    }
+    if depth > 0 {
+        data.push(&input[start..]);
+    }
+
+    data
+}
+
+/// The state of an interactive session: the top-level definitions entered so
+/// far, which every later expression is compiled together with.
+pub struct Session {
+    target: Target,
+    definitions: Vec<(String, String)>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            target: Target::host(),
+            definitions: Vec::new(),
+        }
+    }
+
+    /// Evaluates a single datum. Definitions of procedures and macros are
+    /// compiled with the other definitions, remembered, replacing any earlier
+    /// definition of the same name, and produce no value; any other
+    /// expression is compiled, linked and run, and its printed value is
+    /// returned.
+    pub fn evaluate(&mut self, datum: &str) -> Result<Option<String>, BuildError> {
+        if let Some(name) = definition(datum) {
+            // A definition that does not compile, such as one that refers to
+            // an unbound variable, would break every later expression.
+            let mut definitions = self.definitions.clone();
+            definitions.retain(|(defined, _)| *defined != name);
+            compile(self.target, &definitions, &format!("{}\n0", datum))?;
+            definitions.push((name, datum.to_string()));
+            self.definitions = definitions;
+
+            return Ok(None);
+        }
+
+        let assembly = compile(self.target, &self.definitions, datum)?;
+
+        let directory = TemporaryDirectory::create()?;
+        let executable = directory.0.join("repl");
+        link(&assembly, Emit::Executable, &executable)?;
+
+        let output = Command::new(&executable).output()?;
+        if !output.status.success() {
+            let message = String::from_utf8_lossy(&output.stderr);
+            return Err(if message.trim().is_empty() {
+                BuildError::Tool(String::from("program"), output.status)
+            } else {
+                BuildError::Runtime(message.trim_end().to_string())
+            });
+        }
+
+        Ok(Some(
+            String::from_utf8_lossy(&output.stdout)
+                .trim_end()
+                .to_string(),
+        ))
+    }
+}
+
+// Compiles `source` after `definitions`. It comes after them so that errors
+// in it can be reported relative to it.
+fn compile(
+    target: Target,
+    definitions: &[(String, String)],
+    source: &str,
+) -> Result<String, CompilationError> {
+    let mut program = String::new();
+    for (_, definition) in definitions {
+        program.push_str(definition);
+        program.push('\n');
+    }
+    let offset = program.len();
+    program.push_str(source);
+
+    run(&program, target, Safety::Safe).map_err(|error| relative(error, offset))
+}
+
+// Returns the name bound by `datum` if it is a `define` or `define-syntax`
+// form.
+fn definition(datum: &str) -> Option<String> {
+    let mut tokens = lex(datum).map(|(token, _)| token);
+
+    match (tokens.next(), tokens.next(), tokens.next(), tokens.next()) {
+        (
+            Some(Token::LeftParenthesis),
+            Some(Token::Symbol("define")),
+            Some(Token::LeftParenthesis),
+            Some(Token::Symbol(name)),
+        )
+        | (
+            Some(Token::LeftParenthesis),
+            Some(Token::Symbol("define-syntax")),
+            Some(Token::Symbol(name)),
+            _,
+        ) => Some(name.to_string()),
+        _ => None,
+    }
+}
+
+// Moves the span of `error` back by `offset` bytes.
+fn relative(error: CompilationError, offset: usize) -> CompilationError {
+    let shift =
+        |span: Range<usize>| span.start.saturating_sub(offset)..span.end.saturating_sub(offset);
+
+    match error {
+        CompilationError::LexicalError(span) => CompilationError::LexicalError(shift(span)),
+        CompilationError::ParseError(mut error) => {
+            error.span = shift(error.span);
+            CompilationError::ParseError(error)
+        }
+        error => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completeness() {
+        let cases = [
+            ("", true),
+            ("1", true),
+            ("(fx+ 1 2)", true),
+            ("(fx+ 1", false),
+            ("(let ((x 1))\n", false),
+            ("#(1 2", false),
+            ("\"abc", false),
+            ("\"a(c\"", true),
+            ("(f))", true),
+            ("1 ; (", true),
+            ("#| 1", false),
+            ("#| 1 |# 2", true),
+            ("#;", false),
+            ("#; (f", false),
+            ("#; (f) 1", true),
+            ("(f #;)", true),
+        ];
+
+        for (input, expected) in &cases {
+            assert_eq!(is_complete(input), *expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn splitting() {
+        let cases: [(&str, &[&str]); 7] = [
+            ("", &[]),
+            ("1 #t", &["1", "#t"]),
+            ("(define (f) 1)\n(f) x", &["(define (f) 1)", "(f)", "x"]),
+            ("(let ((x 1))\n  x) #(1)", &["(let ((x 1))\n  x)", "#(1)"]),
+            ("(f (g)", &["(f (g)"]),
+            ("; f\n1 #| 2 |# 3", &["1", "3"]),
+            ("#; (f) #;#; 1 2 3 (g #;x)", &["3", "(g #;x)"]),
+        ];
+
+        for (input, expected) in &cases {
+            assert_eq!(data(input), *expected);
+        }
+    }
+}
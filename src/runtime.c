@@ -1,4 +1,9 @@
+#include <inttypes.h>
+#include <math.h>
+#include <stdint.h>
 #include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
 
 #define BOOLEAN_FALSE 0x2f
 #define BOOLEAN_TRUE 0x6f
@@ -6,6 +11,7 @@
 #define FIXNUM_MASK 0x03
 #define FIXNUM_TAG 0x00
 #define FIXNUM_SHIFT 2
+#define FIXNUM_MAX (INT64_MAX >> FIXNUM_SHIFT)
 
 #define CHARACTER_MASK 0x3f
 #define CHARACTER_TAG 0x0f
@@ -13,39 +19,818 @@
 
 #define LIST_NULL 0x3f
 
-typedef unsigned int ptr;
+#define OBJECT_MASK 0x07
+#define PAIR_TAG 0x01
+#define CLOSURE_TAG 0x02
+#define NUMBER_TAG 0x03
+#define KIND_MASK 0xff
+#define KIND_SHIFT 8
+#define BIGNUM_KIND 0x01
+#define FLONUM_KIND 0x02
+#define RATNUM_KIND 0x03
+#define VECTOR_TAG 0x05
+#define STRING_TAG 0x06
 
-ptr scheme_entry();
+#define HEAP_SIZE (64 * 1024 * 1024)
 
-static void print_char(char c)
+typedef uint64_t ptr;
+
+ptr scheme_entry(char *heap);
+
+static char *heap;
+
+static ptr *object(ptr x, ptr tag)
+{
+  return (ptr *) (heap + (x - tag));
+}
+
+static void fail(const char *who, const char *message)
+{
+  fflush(stdout);
+  fprintf(stderr, "%s: %s\n", who, message);
+  exit(1);
+}
+
+void scheme_type_error(const char *who, const char *expected, ptr x);
+
+/* An integer being computed on: its sign and its magnitude in 32-bit limbs,
+   least significant first. Bignums on the heap have the same limbs after
+   their length, which is negated for negative numbers. */
+typedef struct {
+  int negative;
+  int64_t length;
+  uint32_t *limbs;
+} number;
+
+/* What the arithmetic functions return to generated code, in %rax and %rdx:
+   the value and the heap offset after what they allocated. */
+typedef struct {
+  ptr value;
+  ptr next;
+} result;
+
+static number make_number(int negative, int64_t length)
+{
+  number n = { negative, length, calloc(length + 1, sizeof(uint32_t)) };
+  if (n.limbs == NULL) {
+    perror("calloc");
+    exit(1);
+  }
+  return n;
+}
+
+static void trim(number *n)
 {
-  if      (c == '\t') printf("#\\tab");
-  else if (c == '\n') printf("#\\newline");
-  else if (c == '\r') printf("#\\return");
-  else if (c == ' ')  printf("#\\space");
-  else                printf("#\\%c", c);
+  while (n->length > 0 && n->limbs[n->length - 1] == 0)
+    n->length--;
+}
+
+static number unpack(ptr x)
+{
+  number n;
+
+  if ((x & FIXNUM_MASK) == FIXNUM_TAG) {
+    int64_t value = ((int64_t) x) >> FIXNUM_SHIFT;
+    uint64_t magnitude = value < 0 ? -(uint64_t) value : (uint64_t) value;
+    n = make_number(value < 0, 2);
+    n.limbs[0] = (uint32_t) magnitude;
+    n.limbs[1] = (uint32_t) (magnitude >> 32);
+  } else {
+    ptr *bignum = object(x, NUMBER_TAG);
+    int64_t length = ((int64_t) bignum[0]) >> KIND_SHIFT;
+    n = make_number(length < 0, length < 0 ? -length : length);
+    memcpy(n.limbs, bignum + 1, n.length * sizeof(uint32_t));
+  }
+
+  trim(&n);
+  return n;
 }
 
-static void print_ptr(ptr x)
+/* Stores `n` as a fixnum if it fits in one and as a bignum at `next`
+   otherwise. */
+static result pack(number n, ptr next)
+{
+  result r;
+
+  trim(&n);
+  if (n.length <= 2) {
+    uint64_t magnitude = n.length == 0 ? 0 : n.limbs[0];
+    if (n.length == 2)
+      magnitude |= (uint64_t) n.limbs[1] << 32;
+    if (magnitude <= (uint64_t) FIXNUM_MAX + n.negative) {
+      int64_t value = n.negative ? -(int64_t) magnitude : (int64_t) magnitude;
+      free(n.limbs);
+      r.value = (ptr) value << FIXNUM_SHIFT;
+      r.next = next;
+      return r;
+    }
+  }
+
+  ptr *bignum = (ptr *) (heap + next);
+  bignum[0] = (ptr) (n.negative ? -n.length : n.length) << KIND_SHIFT | BIGNUM_KIND;
+  memcpy(bignum + 1, n.limbs, n.length * sizeof(uint32_t));
+  free(n.limbs);
+  r.value = next | NUMBER_TAG;
+  r.next = next + ((sizeof(ptr) + n.length * sizeof(uint32_t) + 7) & ~(ptr) 7);
+  return r;
+}
+
+static int compare_magnitudes(number a, number b)
+{
+  if (a.length != b.length)
+    return a.length < b.length ? -1 : 1;
+  for (int64_t i = a.length - 1; i >= 0; i--) {
+    if (a.limbs[i] != b.limbs[i])
+      return a.limbs[i] < b.limbs[i] ? -1 : 1;
+  }
+  return 0;
+}
+
+static number add_magnitudes(number a, number b)
+{
+  number sum = make_number(0, (a.length > b.length ? a.length : b.length) + 1);
+  uint64_t carry = 0;
+
+  for (int64_t i = 0; i < sum.length; i++) {
+    carry += (i < a.length ? a.limbs[i] : 0) + (uint64_t) (i < b.length ? b.limbs[i] : 0);
+    sum.limbs[i] = (uint32_t) carry;
+    carry >>= 32;
+  }
+  trim(&sum);
+  return sum;
+}
+
+/* Subtracts the magnitude of `b` from that of `a` in place, which must be
+   at least as large. */
+static void subtract_magnitude(number *a, number b)
+{
+  int64_t borrow = 0;
+
+  for (int64_t i = 0; i < a->length; i++) {
+    int64_t difference = (int64_t) a->limbs[i] - (i < b.length ? b.limbs[i] : 0) - borrow;
+    borrow = difference < 0;
+    a->limbs[i] = (uint32_t) difference;
+  }
+  trim(a);
+}
+
+static number add(number a, number b)
+{
+  if (a.negative == b.negative) {
+    number sum = add_magnitudes(a, b);
+    sum.negative = a.negative;
+    free(a.limbs);
+    free(b.limbs);
+    return sum;
+  } else if (compare_magnitudes(a, b) >= 0) {
+    subtract_magnitude(&a, b);
+    free(b.limbs);
+    return a;
+  } else {
+    subtract_magnitude(&b, a);
+    free(a.limbs);
+    return b;
+  }
+}
+
+static number multiply(number a, number b)
+{
+  number product = make_number(a.negative != b.negative, a.length + b.length);
+
+  for (int64_t i = 0; i < a.length; i++) {
+    uint64_t carry = 0;
+    for (int64_t j = 0; j < b.length; j++) {
+      carry += (uint64_t) a.limbs[i] * b.limbs[j] + product.limbs[i + j];
+      product.limbs[i + j] = (uint32_t) carry;
+      carry >>= 32;
+    }
+    product.limbs[i + b.length] = (uint32_t) carry;
+  }
+  free(a.limbs);
+  free(b.limbs);
+  trim(&product);
+  return product;
+}
+
+/* Divides `a` by `b` one bit at a time, truncating towards zero. The
+   remainder has the sign of `a`. */
+static void divide(const char *who, number a, number b, number *quotient, number *remainder)
+{
+  if (b.length == 0)
+    fail(who, "division by zero");
+
+  *quotient = make_number(a.negative != b.negative, a.length);
+  *remainder = make_number(a.negative, b.length + 1);
+  remainder->length = 0;
+
+  for (int64_t i = a.length * 32 - 1; i >= 0; i--) {
+    uint32_t carry = (a.limbs[i / 32] >> (i % 32)) & 1;
+    for (int64_t j = 0; j < remainder->length; j++) {
+      uint32_t limb = remainder->limbs[j];
+      remainder->limbs[j] = limb << 1 | carry;
+      carry = limb >> 31;
+    }
+    if (carry)
+      remainder->limbs[remainder->length++] = carry;
+    if (compare_magnitudes(*remainder, b) >= 0) {
+      subtract_magnitude(remainder, b);
+      quotient->limbs[i / 32] |= (uint32_t) 1 << (i % 32);
+    }
+  }
+  trim(quotient);
+  free(a.limbs);
+  free(b.limbs);
+}
+
+static number copy(number n)
+{
+  number c = make_number(n.negative, n.length);
+  memcpy(c.limbs, n.limbs, n.length * sizeof(uint32_t));
+  return c;
+}
+
+/* The greatest common divisor of the magnitudes, by Euclid's algorithm. */
+static number gcd(number a, number b)
+{
+  a.negative = b.negative = 0;
+  while (b.length > 0) {
+    number quotient, remainder;
+    divide("gcd", a, copy(b), &quotient, &remainder);
+    free(quotient.limbs);
+    a = b;
+    b = remainder;
+  }
+  free(b.limbs);
+  return a;
+}
+
+/* An exact rational being computed on. Integers have a denominator of one. */
+typedef struct {
+  number numerator;
+  number denominator;
+} fraction;
+
+static int kind(ptr x)
+{
+  if ((x & OBJECT_MASK) != NUMBER_TAG)
+    return 0;
+  return object(x, NUMBER_TAG)[0] & KIND_MASK;
+}
+
+static int is_flonum(ptr x)
+{
+  return kind(x) == FLONUM_KIND;
+}
+
+static int is_ratnum(ptr x)
+{
+  return kind(x) == RATNUM_KIND;
+}
+
+static void check_number(const char *who, ptr x)
+{
+  if ((x & FIXNUM_MASK) != FIXNUM_TAG && kind(x) == 0)
+    scheme_type_error(who, "number", x);
+}
+
+static void check_integer(const char *who, ptr x)
+{
+  check_number(who, x);
+  if (is_ratnum(x))
+    scheme_type_error(who, "integer", x);
+}
+
+static double flonum_value(ptr x)
+{
+  double d;
+  memcpy(&d, object(x, NUMBER_TAG) + 1, sizeof d);
+  return d;
+}
+
+static result make_flonum(double d, ptr next)
+{
+  ptr *flonum = (ptr *) (heap + next);
+  result r = { next | NUMBER_TAG, next + 2 * sizeof(ptr) };
+
+  flonum[0] = FLONUM_KIND;
+  memcpy(flonum + 1, &d, sizeof d);
+  return r;
+}
+
+static fraction unpack_fraction(ptr x)
+{
+  fraction f;
+
+  if (is_ratnum(x)) {
+    f.numerator = unpack(object(x, NUMBER_TAG)[1]);
+    f.denominator = unpack(object(x, NUMBER_TAG)[2]);
+  } else {
+    f.numerator = unpack(x);
+    f.denominator = make_number(0, 1);
+    f.denominator.limbs[0] = 1;
+  }
+  return f;
+}
+
+/* Stores `f` in lowest terms with a positive denominator, as an integer if
+   the denominator is one. */
+static result pack_fraction(const char *who, fraction f, ptr next)
+{
+  number divisor, unused;
+  result numerator, denominator, r;
+
+  if (f.denominator.length == 0)
+    fail(who, "division by zero");
+
+  f.numerator.negative = f.numerator.negative != f.denominator.negative;
+  f.denominator.negative = 0;
+  divisor = gcd(copy(f.numerator), copy(f.denominator));
+  divide(who, f.numerator, copy(divisor), &f.numerator, &unused);
+  free(unused.limbs);
+  divide(who, f.denominator, divisor, &f.denominator, &unused);
+  free(unused.limbs);
+
+  if (f.denominator.length == 1 && f.denominator.limbs[0] == 1) {
+    free(f.denominator.limbs);
+    return pack(f.numerator, next);
+  }
+
+  numerator = pack(f.numerator, next);
+  denominator = pack(f.denominator, numerator.next);
+  ptr *ratnum = (ptr *) (heap + denominator.next);
+  ratnum[0] = RATNUM_KIND;
+  ratnum[1] = numerator.value;
+  ratnum[2] = denominator.value;
+  r.value = denominator.next | NUMBER_TAG;
+  r.next = denominator.next + 3 * sizeof(ptr);
+  return r;
+}
+
+static fraction add_fractions(fraction a, fraction b)
+{
+  fraction sum;
+
+  sum.numerator = add(multiply(a.numerator, copy(b.denominator)),
+                      multiply(b.numerator, copy(a.denominator)));
+  sum.denominator = multiply(a.denominator, b.denominator);
+  return sum;
+}
+
+static fraction multiply_fractions(fraction a, fraction b)
+{
+  fraction product = {
+    multiply(a.numerator, b.numerator),
+    multiply(a.denominator, b.denominator)
+  };
+  return product;
+}
+
+static double number_to_double(number n)
+{
+  double d = 0;
+
+  for (int64_t i = n.length - 1; i >= 0; i--)
+    d = d * 4294967296.0 + n.limbs[i];
+  free(n.limbs);
+  return n.negative ? -d : d;
+}
+
+static double to_double(ptr x)
+{
+  if (is_flonum(x))
+    return flonum_value(x);
+
+  fraction f = unpack_fraction(x);
+  return number_to_double(f.numerator) / number_to_double(f.denominator);
+}
+
+/* The power of two `2^shift` times `m`. */
+static number shifted(uint64_t m, int shift)
+{
+  number n = make_number(0, (64 + shift) / 32 + 1);
+
+  for (int i = 0; i < 64; i++) {
+    if ((m >> i) & 1)
+      n.limbs[(i + shift) / 32] |= (uint32_t) 1 << ((i + shift) % 32);
+  }
+  trim(&n);
+  return n;
+}
+
+/* The exact value of `d`, from its 53-bit mantissa and its exponent. */
+static fraction from_double(const char *who, double d)
+{
+  int exponent;
+  uint64_t mantissa;
+  fraction f;
+
+  if (!isfinite(d))
+    fail(who, "no exact representation");
+
+  mantissa = (uint64_t) ldexp(frexp(fabs(d), &exponent), 53);
+  exponent -= 53;
+  if (exponent >= 0) {
+    f.numerator = shifted(mantissa, exponent);
+    f.denominator = shifted(1, 0);
+  } else {
+    f.numerator = shifted(mantissa, 0);
+    f.denominator = shifted(1, -exponent);
+  }
+  f.numerator.negative = d < 0;
+  return f;
+}
+
+/* Arithmetic is inexact if either operand is, and otherwise exact on
+   integers or on fractions. */
+
+result scheme_add(ptr a, ptr b, ptr next)
+{
+  check_number("+", a);
+  check_number("+", b);
+  if (is_flonum(a) || is_flonum(b))
+    return make_flonum(to_double(a) + to_double(b), next);
+  if (is_ratnum(a) || is_ratnum(b))
+    return pack_fraction("+", add_fractions(unpack_fraction(a), unpack_fraction(b)), next);
+  return pack(add(unpack(a), unpack(b)), next);
+}
+
+result scheme_subtract(ptr a, ptr b, ptr next)
+{
+  check_number("-", a);
+  check_number("-", b);
+  if (is_flonum(a) || is_flonum(b))
+    return make_flonum(to_double(a) - to_double(b), next);
+
+  fraction negated = unpack_fraction(b);
+  negated.numerator.negative = !negated.numerator.negative;
+  if (is_ratnum(a) || is_ratnum(b))
+    return pack_fraction("-", add_fractions(unpack_fraction(a), negated), next);
+  free(negated.denominator.limbs);
+  return pack(add(unpack(a), negated.numerator), next);
+}
+
+result scheme_multiply(ptr a, ptr b, ptr next)
+{
+  check_number("*", a);
+  check_number("*", b);
+  if (is_flonum(a) || is_flonum(b))
+    return make_flonum(to_double(a) * to_double(b), next);
+  if (is_ratnum(a) || is_ratnum(b))
+    return pack_fraction("*", multiply_fractions(unpack_fraction(a), unpack_fraction(b)), next);
+  return pack(multiply(unpack(a), unpack(b)), next);
+}
+
+result scheme_divide(ptr a, ptr b, ptr next)
+{
+  check_number("/", a);
+  check_number("/", b);
+  if (is_flonum(a) || is_flonum(b))
+    return make_flonum(to_double(a) / to_double(b), next);
+
+  fraction divisor = unpack_fraction(b), reciprocal = { divisor.denominator, divisor.numerator };
+  return pack_fraction("/", multiply_fractions(unpack_fraction(a), reciprocal), next);
+}
+
+result scheme_quotient(ptr a, ptr b, ptr next)
+{
+  number quotient, remainder;
+
+  check_integer("quotient", a);
+  check_integer("quotient", b);
+  if (is_flonum(a) || is_flonum(b))
+    return make_flonum(trunc(to_double(a) / to_double(b)), next);
+
+  divide("quotient", unpack(a), unpack(b), &quotient, &remainder);
+  free(remainder.limbs);
+  return pack(quotient, next);
+}
+
+result scheme_remainder(ptr a, ptr b, ptr next)
+{
+  number quotient, remainder;
+
+  check_integer("remainder", a);
+  check_integer("remainder", b);
+  if (is_flonum(a) || is_flonum(b))
+    return make_flonum(fmod(to_double(a), to_double(b)), next);
+
+  divide("remainder", unpack(a), unpack(b), &quotient, &remainder);
+  free(quotient.limbs);
+  return pack(remainder, next);
+}
+
+result scheme_inexact(ptr x, ptr unused, ptr next)
+{
+  result r = { x, next };
+
+  (void) unused;
+  check_number("exact->inexact", x);
+  if (is_flonum(x))
+    return r;
+  return make_flonum(to_double(x), next);
+}
+
+result scheme_exact(ptr x, ptr unused, ptr next)
+{
+  result r = { x, next };
+
+  (void) unused;
+  check_number("inexact->exact", x);
+  if (!is_flonum(x))
+    return r;
+  return pack_fraction("inexact->exact", from_double("inexact->exact", flonum_value(x)), next);
+}
+
+/* The numerator and denominator of a flonum are those of its exact value,
+   made inexact. */
+
+result scheme_numerator(ptr x, ptr unused, ptr next)
+{
+  result r = { x, next };
+
+  (void) unused;
+  check_number("numerator", x);
+  if (is_flonum(x)) {
+    r = scheme_exact(x, unused, next);
+    r = scheme_numerator(r.value, unused, r.next);
+    return scheme_inexact(r.value, unused, r.next);
+  } else if (is_ratnum(x)) {
+    r.value = object(x, NUMBER_TAG)[1];
+  }
+  return r;
+}
+
+result scheme_denominator(ptr x, ptr unused, ptr next)
+{
+  result r = { (ptr) 1 << FIXNUM_SHIFT, next };
+
+  (void) unused;
+  check_number("denominator", x);
+  if (is_flonum(x)) {
+    r = scheme_exact(x, unused, next);
+    r = scheme_denominator(r.value, unused, r.next);
+    return scheme_inexact(r.value, unused, r.next);
+  } else if (is_ratnum(x)) {
+    r.value = object(x, NUMBER_TAG)[2];
+  }
+  return r;
+}
+
+static void put_utf8(FILE *out, ptr c)
+{
+  if (c < 0x80) {
+    fputc(c, out);
+  } else if (c < 0x800) {
+    fputc(0xc0 | (c >> 6), out);
+    fputc(0x80 | (c & 0x3f), out);
+  } else if (c < 0x10000) {
+    fputc(0xe0 | (c >> 12), out);
+    fputc(0x80 | ((c >> 6) & 0x3f), out);
+    fputc(0x80 | (c & 0x3f), out);
+  } else {
+    fputc(0xf0 | (c >> 18), out);
+    fputc(0x80 | ((c >> 12) & 0x3f), out);
+    fputc(0x80 | ((c >> 6) & 0x3f), out);
+    fputc(0x80 | (c & 0x3f), out);
+  }
+}
+
+static void print_char(FILE *out, ptr c)
+{
+  if      (c == 0x07) fprintf(out, "#\\alarm");
+  else if (c == 0x08) fprintf(out, "#\\backspace");
+  else if (c == 0x7f) fprintf(out, "#\\delete");
+  else if (c == 0x1b) fprintf(out, "#\\escape");
+  else if (c == '\n') fprintf(out, "#\\newline");
+  else if (c == 0x00) fprintf(out, "#\\null");
+  else if (c == '\r') fprintf(out, "#\\return");
+  else if (c == ' ')  fprintf(out, "#\\space");
+  else if (c == '\t') fprintf(out, "#\\tab");
+  else if (c < 0x20 || (c >= 0x80 && c < 0xa0)) fprintf(out, "#\\x%" PRIx64, c);
+  else {
+    fprintf(out, "#\\");
+    put_utf8(out, c);
+  }
+}
+
+static void print_object(FILE *out, ptr x);
+
+static void print_pair(FILE *out, ptr x)
+{
+  fprintf(out, "(");
+  print_object(out, object(x, PAIR_TAG)[0]);
+  x = object(x, PAIR_TAG)[1];
+  while ((x & OBJECT_MASK) == PAIR_TAG) {
+    fprintf(out, " ");
+    print_object(out, object(x, PAIR_TAG)[0]);
+    x = object(x, PAIR_TAG)[1];
+  }
+  if (x != LIST_NULL) {
+    fprintf(out, " . ");
+    print_object(out, x);
+  }
+  fprintf(out, ")");
+}
+
+static void print_vector(FILE *out, ptr x)
+{
+  ptr *vector = object(x, VECTOR_TAG);
+  int64_t length = ((int64_t) vector[0]) >> FIXNUM_SHIFT;
+
+  fprintf(out, "#(");
+  for (int64_t i = 0; i < length; i++) {
+    if (i > 0)
+      fprintf(out, " ");
+    print_object(out, vector[i + 1]);
+  }
+  fprintf(out, ")");
+}
+
+static void print_string(FILE *out, ptr x)
+{
+  ptr *string = object(x, STRING_TAG);
+  int64_t length = ((int64_t) string[0]) >> FIXNUM_SHIFT;
+  uint32_t *characters = (uint32_t *) (string + 1);
+
+  fprintf(out, "\"");
+  for (int64_t i = 0; i < length; i++) {
+    ptr c = characters[i];
+    if      (c == '"')  fprintf(out, "\\\"");
+    else if (c == '\\') fprintf(out, "\\\\");
+    else if (c == '\n') fprintf(out, "\\n");
+    else if (c == '\t') fprintf(out, "\\t");
+    else if (c == '\r') fprintf(out, "\\r");
+    else                put_utf8(out, c);
+  }
+  fprintf(out, "\"");
+}
+
+/* Prints the digits in groups of nine, which are the remainders of
+   repeatedly dividing by 10^9. */
+static void print_bignum(FILE *out, ptr x)
+{
+  number n = unpack(x);
+  uint32_t *groups = calloc(n.length * 10 / 9 + 1, sizeof(uint32_t));
+  int64_t count = 0;
+
+  while (n.length > 0) {
+    uint64_t remainder = 0;
+    for (int64_t i = n.length - 1; i >= 0; i--) {
+      uint64_t dividend = remainder << 32 | n.limbs[i];
+      n.limbs[i] = (uint32_t) (dividend / 1000000000);
+      remainder = dividend % 1000000000;
+    }
+    groups[count++] = (uint32_t) remainder;
+    trim(&n);
+  }
+
+  fprintf(out, "%s%" PRIu32, n.negative ? "-" : "", groups[count - 1]);
+  for (int64_t i = count - 2; i >= 0; i--)
+    fprintf(out, "%09" PRIu32, groups[i]);
+  free(groups);
+  free(n.limbs);
+}
+
+/* Prints the fewest digits that read back as `x`, with a decimal point or
+   an exponent. */
+static void print_flonum(FILE *out, double x)
+{
+  char buffer[32], digits[32];
+  int precision, count = 0, exponent;
+  char *e;
+
+  if (isnan(x)) {
+    fprintf(out, "+nan.0");
+    return;
+  } else if (isinf(x)) {
+    fprintf(out, x < 0 ? "-inf.0" : "+inf.0");
+    return;
+  } else if (signbit(x)) {
+    fprintf(out, "-");
+    x = -x;
+  }
+
+  for (precision = 1; precision < 17; precision++) {
+    snprintf(buffer, sizeof buffer, "%.*e", precision - 1, x);
+    if (strtod(buffer, NULL) == x)
+      break;
+  }
+  snprintf(buffer, sizeof buffer, "%.*e", precision - 1, x);
+  e = strchr(buffer, 'e');
+  exponent = atoi(e + 1);
+  for (char *p = buffer; p < e; p++) {
+    if (*p != '.')
+      digits[count++] = *p;
+  }
+  digits[count] = '\0';
+
+  if (x != 0 && (x < 1e-4 || x >= 1e16)) {
+    fprintf(out, "%c", digits[0]);
+    if (count > 1)
+      fprintf(out, ".%s", digits + 1);
+    fprintf(out, "e%d", exponent);
+  } else if (exponent < 0) {
+    fprintf(out, "0.");
+    for (int i = -1; i > exponent; i--)
+      fputc('0', out);
+    fprintf(out, "%s", digits);
+  } else {
+    for (int i = 0; i <= exponent; i++)
+      fputc(i < count ? digits[i] : '0', out);
+    fprintf(out, ".%s", count > exponent + 1 ? digits + exponent + 1 : "0");
+  }
+}
+
+static void print_object(FILE *out, ptr x)
 {
   if ((x & FIXNUM_MASK) == FIXNUM_TAG)
-    printf("%d", ((int) x) >> FIXNUM_SHIFT);
+    fprintf(out, "%" PRId64, ((int64_t) x) >> FIXNUM_SHIFT);
   else if (x == BOOLEAN_FALSE)
-    printf("#f");
+    fprintf(out, "#f");
   else if (x == BOOLEAN_TRUE)
-    printf("#t");
+    fprintf(out, "#t");
   else if (x == LIST_NULL)
-    printf("()");
+    fprintf(out, "()");
   else if ((x & CHARACTER_MASK) == CHARACTER_TAG)
-    print_char(x >> CHARACTER_SHIFT);
+    print_char(out, x >> CHARACTER_SHIFT);
+  else if ((x & OBJECT_MASK) == PAIR_TAG)
+    print_pair(out, x);
+  else if ((x & OBJECT_MASK) == CLOSURE_TAG)
+    fprintf(out, "#<procedure>");
+  else if (is_flonum(x))
+    print_flonum(out, flonum_value(x));
+  else if (is_ratnum(x)) {
+    print_object(out, object(x, NUMBER_TAG)[1]);
+    fprintf(out, "/");
+    print_object(out, object(x, NUMBER_TAG)[2]);
+  }
+  else if ((x & OBJECT_MASK) == NUMBER_TAG)
+    print_bignum(out, x);
+  else if ((x & OBJECT_MASK) == VECTOR_TAG)
+    print_vector(out, x);
+  else if ((x & OBJECT_MASK) == STRING_TAG)
+    print_string(out, x);
   else
-    printf("#<unknown 0x%08x>", x);
-  printf("\n");
+    fprintf(out, "#<unknown 0x%016" PRIx64 ">", x);
+}
+
+static void print_ptr(FILE *out, ptr x)
+{
+  print_object(out, x);
+  fprintf(out, "\n");
+}
+
+/* Reports that `who` was given `x` where it expected another type of value.
+   Generated code calls this when an argument fails its tag check. */
+void scheme_type_error(const char *who, const char *expected, ptr x)
+{
+  fflush(stdout);
+  fprintf(stderr, "%s: expected %s, got ", who, expected);
+  print_ptr(stderr, x);
+  exit(1);
+}
+
+/* Reports that `who` was given `index`, which is not an index of `x`. */
+void scheme_index_error(const char *who, ptr x, ptr index)
+{
+  fflush(stdout);
+  fprintf(stderr, "%s: index ", who);
+  print_object(stderr, index);
+  fprintf(stderr, " is out of range for ");
+  print_ptr(stderr, x);
+  exit(1);
+}
+
+/* Reports that a procedure of `expected` parameters was applied to `actual`
+   arguments. */
+void scheme_arity_error(int64_t expected, int64_t actual)
+{
+  fflush(stdout);
+  fprintf(stderr, "application: expected %" PRId64 " argument%s, got %" PRId64 "\n",
+          expected, expected == 1 ? "" : "s", actual);
+  exit(1);
+}
+
+/* Reports that `who` was asked for an object of `length`, which does not fit
+   in what is left of the heap. */
+void scheme_heap_error(const char *who, ptr length)
+{
+  fflush(stdout);
+  fprintf(stderr, "%s: length ", who);
+  print_object(stderr, length);
+  fprintf(stderr, " does not fit in the heap\n");
+  exit(1);
 }
 
 int main()
 {
-  print_ptr(scheme_entry());
+  heap = calloc(HEAP_SIZE, 1);
+  if (heap == NULL) {
+    perror("calloc");
+    return 1;
+  }
+
+  print_ptr(stdout, scheme_entry(heap));
 
   return 0;
-}
\ No newline at end of file
+}
@@ -0,0 +1,1519 @@
+use std::{collections::HashMap, convert::TryFrom, ops::Range, rc::Rc};
+
+use super::{
+    invalid, symbols, variables, Bindings, Context, Datum, Environment, Expander, Match, Meaning,
+    Transcription,
+};
+use crate::{
+    expression::{
+        bignum::Bignum,
+        immediate::{FIXNUM_MAX, FIXNUM_MIN},
+    },
+    parse::ParseError,
+};
+
+// Evaluation nested deeper than this is assumed not to terminate. Calls in
+// tail position do not nest.
+const MAX_DEPTH: usize = 100;
+// A transformer that calls procedures more often than this, in tail position
+// or not, is assumed not to terminate.
+const MAX_CALLS: usize = 1_000_000;
+
+const SPECIAL_FORMS: &[&str] = &[
+    "and",
+    "begin",
+    "cond",
+    "define",
+    "if",
+    "lambda",
+    "let",
+    "let*",
+    "letrec",
+    "letrec*",
+    "or",
+    "quote",
+    "set!",
+    "syntax",
+    "syntax-case",
+    "unless",
+    "when",
+    "with-syntax",
+];
+
+const PRIMITIVES: &[&str] = &[
+    "*",
+    "+",
+    "-",
+    "<",
+    "<=",
+    "=",
+    ">",
+    ">=",
+    "append",
+    "apply",
+    "boolean?",
+    "bound-identifier=?",
+    "caddr",
+    "cadr",
+    "car",
+    "cddr",
+    "cdr",
+    "char->fixnum",
+    "char?",
+    "cons",
+    "datum->syntax",
+    "eq?",
+    "equal?",
+    "eqv?",
+    "fixnum->char",
+    "fixnum?",
+    "free-identifier=?",
+    "fx*",
+    "fx+",
+    "fx-",
+    "fx<",
+    "fx<=",
+    "fx=",
+    "fx>",
+    "fx>=",
+    "fxadd1",
+    "fxsub1",
+    "fxzero?",
+    "generate-temporaries",
+    "identifier?",
+    "integer?",
+    "length",
+    "list",
+    "list->vector",
+    "list?",
+    "map",
+    "not",
+    "null?",
+    "number->string",
+    "pair?",
+    "procedure?",
+    "quotient",
+    "remainder",
+    "reverse",
+    "string->symbol",
+    "string-append",
+    "string-length",
+    "string?",
+    "symbol->string",
+    "symbol?",
+    "syntax->datum",
+    "vector",
+    "vector->list",
+    "vector-length",
+    "vector-ref",
+    "vector?",
+    "zero?",
+];
+
+/// A value computed by a macro transformer at compile time.
+///
+/// Syntax objects are the data the expander works on. They are taken apart
+/// one layer at a time when a procedure needs a pair, a vector or a constant,
+/// so the parts keep their spans.
+#[derive(Clone)]
+pub(super) enum Value {
+    Boolean(bool),
+    Character(char),
+    Integer(i64),
+    Bignum(Bignum),
+    Flonum(f64),
+    Rational(Bignum, Bignum),
+    Null,
+    Pair(Rc<(Value, Value)>),
+    Procedure(Rc<Procedure>),
+    String(String),
+    Symbol(String),
+    Syntax(Datum),
+    Vector(Rc<Vec<Value>>),
+}
+
+pub(super) enum Procedure {
+    Primitive(&'static str),
+    Lambda {
+        parameters: Vec<String>,
+        rest: Option<String>,
+        body: Vec<Datum>,
+        locals: Locals,
+    },
+}
+
+// Pattern variables live alongside ordinary variables, but can only be used
+// in `syntax` templates.
+#[derive(Clone)]
+pub(super) enum Local {
+    Value(Value),
+    Pattern(Match),
+}
+
+type Locals = Environment<Local>;
+
+// What is left of a form once its subforms are evaluated: its value, or the
+// expression in tail position and the scope to evaluate it in.
+enum Tail {
+    Value(Value),
+    Expression(Datum, Locals),
+}
+
+// One call of a transformer: the environment it was defined in, the
+// environment of the macro use, and the aliases given to the identifiers its
+// templates introduce.
+struct Invocation<'a> {
+    environment: &'a Environment,
+    use_site: &'a Environment,
+    renames: HashMap<String, String>,
+    span: Range<usize>,
+    depth: usize,
+    calls: usize,
+}
+
+impl<'a> Invocation<'a> {
+    fn new(environment: &'a Environment, use_site: &'a Environment, span: Range<usize>) -> Self {
+        Self {
+            environment,
+            use_site,
+            renames: HashMap::new(),
+            span,
+            depth: 0,
+            calls: 0,
+        }
+    }
+
+    // Counts a call of a procedure defined in the transformer.
+    fn call(&mut self) -> Result<(), ParseError> {
+        self.calls += 1;
+        if self.calls > MAX_CALLS {
+            return Err(invalid(
+                "macro transformer makes too many calls",
+                self.span.clone(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Expander {
+    pub(super) fn evaluate_transformer(
+        &mut self,
+        specification: &Datum,
+        environment: &Environment,
+    ) -> Result<Value, ParseError> {
+        let mut invocation = Invocation::new(environment, environment, specification.span());
+
+        match self.evaluate(specification, &Locals::default().extend(), &mut invocation)? {
+            procedure @ Value::Procedure(_) => Ok(procedure),
+            _ => Err(invalid(
+                "a macro transformer must be `syntax-rules` or a procedure",
+                specification.span(),
+            )),
+        }
+    }
+
+    // Calls a transformer with the syntax of a macro use, and turns what it
+    // returns back into a datum.
+    pub(super) fn apply_transformer(
+        &mut self,
+        procedure: &Value,
+        definition: &Environment,
+        form: &Datum,
+        environment: &Environment,
+    ) -> Result<Datum, ParseError> {
+        let span = form.span();
+        let mut invocation = Invocation::new(definition, environment, span.clone());
+        let output = self.apply(
+            procedure,
+            vec![Value::Syntax(form.clone())],
+            &span,
+            &mut invocation,
+        )?;
+        to_datum(output, &span)
+    }
+
+    fn evaluate(
+        &mut self,
+        datum: &Datum,
+        locals: &Locals,
+        invocation: &mut Invocation,
+    ) -> Result<Value, ParseError> {
+        if invocation.depth >= MAX_DEPTH {
+            return Err(invalid(
+                "macro transformer recursion is too deep",
+                invocation.span.clone(),
+            ));
+        }
+
+        invocation.depth += 1;
+        let result = self.evaluate_tail(datum.clone(), locals.clone(), invocation);
+        invocation.depth -= 1;
+
+        result
+    }
+
+    // Evaluates the expression in tail position of each form in a loop, so
+    // that tail calls take no stack. Each form is evaluated by a method of
+    // its own, which keeps the frames of nested evaluation small.
+    fn evaluate_tail(
+        &mut self,
+        mut datum: Datum,
+        mut locals: Locals,
+        invocation: &mut Invocation,
+    ) -> Result<Value, ParseError> {
+        loop {
+            let (items, span) = match datum {
+                Datum::Symbol(name, span) => {
+                    return self.variable(&name, span, &locals, invocation)
+                }
+                Datum::List(items, span) if !items.is_empty() => (items, span),
+                Datum::List(_, span) => return Err(invalid("expected an expression", span)),
+                datum => return Ok(self.quote(&datum)),
+            };
+
+            let tail = match self.special_form(&items[0], &locals, invocation) {
+                Some(keyword) => self.special(&keyword, items, span, &locals, invocation)?,
+                None => self.application(items, span, &locals, invocation)?,
+            };
+            match tail {
+                Tail::Value(value) => return Ok(value),
+                Tail::Expression(next, scope) => {
+                    datum = next;
+                    locals = scope;
+                }
+            }
+        }
+    }
+
+    fn special_form(
+        &self,
+        head: &Datum,
+        locals: &Locals,
+        invocation: &Invocation,
+    ) -> Option<String> {
+        match head {
+            Datum::Symbol(name, _) if locals.lookup(name).is_none() => {
+                match self.resolve(name, invocation.environment) {
+                    Meaning::Free(keyword) if SPECIAL_FORMS.contains(&keyword.as_str()) => {
+                        Some(keyword)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn special(
+        &mut self,
+        keyword: &str,
+        items: Vec<Datum>,
+        span: Range<usize>,
+        locals: &Locals,
+        invocation: &mut Invocation,
+    ) -> Result<Tail, ParseError> {
+        match (keyword, &items[..]) {
+            ("quote", [_, datum]) => Ok(Tail::Value(self.quote(datum))),
+            ("syntax", [_, template]) => {
+                Ok(Tail::Value(self.syntax(template, locals, invocation)?))
+            }
+            ("if", [_, _, _]) | ("if", [_, _, _, _]) => self.conditional(items, locals, invocation),
+            ("and" | "or", _) => self.logical(keyword == "and", items, locals, invocation),
+            ("when" | "unless", [_, condition, body @ ..]) if !body.is_empty() => {
+                self.when(keyword == "when", condition, body, locals, invocation)
+            }
+            ("begin", [_, body @ ..]) if !body.is_empty() => Ok(Tail::Expression(
+                self.sequence(body, locals, invocation)?,
+                locals.clone(),
+            )),
+            ("cond", [_, clauses @ ..]) => self.cond(clauses, &span, locals, invocation),
+            ("lambda", [_, formals, body @ ..]) if !body.is_empty() => {
+                Ok(Tail::Value(self.closure(formals, body, locals)?))
+            }
+            ("define", _) => self.define(&items, &span, locals, invocation),
+            ("set!", [_, Datum::Symbol(name, span), value]) => {
+                self.assign(name, span, value, locals, invocation)
+            }
+            ("let" | "let*" | "letrec" | "letrec*", _) => {
+                let (scope, body) = self.let_form(keyword, &items, &span, locals, invocation)?;
+                let datum = self.sequence(body, &scope, invocation)?;
+                Ok(Tail::Expression(datum, scope))
+            }
+            ("syntax-case", _) => self.syntax_case(&items, &span, locals, invocation),
+            ("with-syntax", [_, Datum::List(clauses, _), body @ ..]) if !body.is_empty() => {
+                self.with_syntax(clauses, body, &span, locals, invocation)
+            }
+            _ => Err(invalid("malformed special form", span)),
+        }
+    }
+
+    fn conditional(
+        &mut self,
+        mut items: Vec<Datum>,
+        locals: &Locals,
+        invocation: &mut Invocation,
+    ) -> Result<Tail, ParseError> {
+        let branch = if truthy(&self.evaluate(&items[1], locals, invocation)?) {
+            2
+        } else {
+            3
+        };
+
+        if branch < items.len() {
+            Ok(Tail::Expression(items.swap_remove(branch), locals.clone()))
+        } else {
+            Ok(Tail::Value(unspecified()))
+        }
+    }
+
+    // `and` stops at the first false value, `or` at the first true one.
+    fn logical(
+        &mut self,
+        and: bool,
+        mut items: Vec<Datum>,
+        locals: &Locals,
+        invocation: &mut Invocation,
+    ) -> Result<Tail, ParseError> {
+        let last = match items.pop() {
+            Some(last) if !items.is_empty() => last,
+            _ => return Ok(Tail::Value(Value::Boolean(and))),
+        };
+
+        for item in &items[1..] {
+            let value = self.evaluate(item, locals, invocation)?;
+            if truthy(&value) != and {
+                return Ok(Tail::Value(value));
+            }
+        }
+
+        Ok(Tail::Expression(last, locals.clone()))
+    }
+
+    fn when(
+        &mut self,
+        when: bool,
+        condition: &Datum,
+        body: &[Datum],
+        locals: &Locals,
+        invocation: &mut Invocation,
+    ) -> Result<Tail, ParseError> {
+        if truthy(&self.evaluate(condition, locals, invocation)?) != when {
+            return Ok(Tail::Value(unspecified()));
+        }
+
+        Ok(Tail::Expression(
+            self.sequence(body, locals, invocation)?,
+            locals.clone(),
+        ))
+    }
+
+    fn assign(
+        &mut self,
+        name: &str,
+        span: &Range<usize>,
+        value: &Datum,
+        locals: &Locals,
+        invocation: &mut Invocation,
+    ) -> Result<Tail, ParseError> {
+        let value = self.evaluate(value, locals, invocation)?;
+
+        if !locals.assign(name, Local::Value(value)) {
+            return Err(invalid(
+                "only variables of the transformer can be assigned",
+                span.clone(),
+            ));
+        }
+
+        Ok(Tail::Value(unspecified()))
+    }
+
+    fn application(
+        &mut self,
+        items: Vec<Datum>,
+        span: Range<usize>,
+        locals: &Locals,
+        invocation: &mut Invocation,
+    ) -> Result<Tail, ParseError> {
+        let mut values = Vec::new();
+        for item in &items {
+            values.push(self.evaluate(item, locals, invocation)?);
+        }
+        let procedure = values.remove(0);
+
+        match &procedure {
+            Value::Procedure(lambda) => match &**lambda {
+                Procedure::Lambda { body, .. } => {
+                    invocation.call()?;
+                    let scope = arguments(lambda, values, &span)?;
+                    let datum = self.sequence(body, &scope, invocation)?;
+                    Ok(Tail::Expression(datum, scope))
+                }
+                Procedure::Primitive(name) => Ok(Tail::Value(
+                    self.primitive(name, values, &span, invocation)?,
+                )),
+            },
+            _ => Err(invalid(
+                "application of a value that is not a procedure",
+                span,
+            )),
+        }
+    }
+
+    fn define(
+        &mut self,
+        items: &[Datum],
+        span: &Range<usize>,
+        locals: &Locals,
+        invocation: &mut Invocation,
+    ) -> Result<Tail, ParseError> {
+        let (name, value) = match items {
+            [_, Datum::Symbol(name, _), value] => {
+                (name.clone(), self.evaluate(value, locals, invocation)?)
+            }
+            [_, Datum::List(header, _), body @ ..] if !body.is_empty() => match &header[..] {
+                [Datum::Symbol(name, _), parameters @ ..] => {
+                    let formals = Datum::List(parameters.to_vec(), span.clone());
+                    (name.clone(), self.closure(&formals, body, locals)?)
+                }
+                _ => return Err(invalid("malformed special form", span.clone())),
+            },
+            _ => return Err(invalid("malformed special form", span.clone())),
+        };
+
+        locals.bind(name, Local::Value(value));
+        Ok(Tail::Value(unspecified()))
+    }
+
+    fn with_syntax(
+        &mut self,
+        clauses: &[Datum],
+        body: &[Datum],
+        span: &Range<usize>,
+        locals: &Locals,
+        invocation: &mut Invocation,
+    ) -> Result<Tail, ParseError> {
+        let scope = locals.extend();
+
+        for clause in clauses {
+            let (pattern, value) = match clause {
+                Datum::List(items, _) if items.len() == 2 => (&items[0], &items[1]),
+                _ => return Err(invalid("malformed special form", span.clone())),
+            };
+            let input = to_datum(self.evaluate(value, locals, invocation)?, span)?;
+            if !self.bind_pattern(pattern, &input, &[], &scope, invocation) {
+                return Err(invalid(
+                    "`with-syntax` pattern does not match",
+                    clause.span(),
+                ));
+            }
+        }
+
+        let datum = self.sequence(body, &scope, invocation)?;
+        Ok(Tail::Expression(datum, scope))
+    }
+
+    fn apply(
+        &mut self,
+        procedure: &Value,
+        values: Vec<Value>,
+        span: &Range<usize>,
+        invocation: &mut Invocation,
+    ) -> Result<Value, ParseError> {
+        match procedure {
+            Value::Procedure(lambda) => match &**lambda {
+                Procedure::Lambda { body, .. } => {
+                    invocation.call()?;
+                    let locals = arguments(lambda, values, span)?;
+                    let tail = self.sequence(body, &locals, invocation)?;
+                    self.evaluate(&tail, &locals, invocation)
+                }
+                Procedure::Primitive(name) => self.primitive(name, values, span, invocation),
+            },
+            _ => Err(invalid(
+                "application of a value that is not a procedure",
+                span.clone(),
+            )),
+        }
+    }
+
+    // Evaluates all but the last expression of a body, and returns the last.
+    fn sequence(
+        &mut self,
+        body: &[Datum],
+        locals: &Locals,
+        invocation: &mut Invocation,
+    ) -> Result<Datum, ParseError> {
+        let (last, rest) = body.split_last().unwrap();
+        for datum in rest {
+            self.evaluate(datum, locals, invocation)?;
+        }
+        Ok(last.clone())
+    }
+
+    fn variable(
+        &mut self,
+        name: &str,
+        span: Range<usize>,
+        locals: &Locals,
+        invocation: &mut Invocation,
+    ) -> Result<Value, ParseError> {
+        match locals.lookup(name) {
+            Some(Local::Value(value)) => return Ok(value),
+            Some(Local::Pattern(_)) => {
+                return Err(invalid("pattern variable used outside `syntax`", span))
+            }
+            None => {}
+        }
+
+        match self.resolve(name, invocation.environment) {
+            Meaning::Free(name) => match PRIMITIVES.iter().find(|&&primitive| primitive == name) {
+                Some(primitive) => Ok(Value::Procedure(Rc::new(Procedure::Primitive(primitive)))),
+                None => Err(invalid("variable is not available at compile time", span)),
+            },
+            Meaning::Variable(_) => Err(invalid("variable is not available at compile time", span)),
+            Meaning::Macro(_) => Err(invalid("macro keyword used as an expression", span)),
+        }
+    }
+
+    fn closure(
+        &self,
+        formals: &Datum,
+        body: &[Datum],
+        locals: &Locals,
+    ) -> Result<Value, ParseError> {
+        let malformed = || invalid("malformed parameter list", formals.span());
+
+        let (parameters, rest) = match formals {
+            Datum::Symbol(rest, _) => (&[][..], Some(rest.clone())),
+            Datum::List(parameters, _) => (&parameters[..], None),
+            Datum::Pair(parameters, rest, _) => match &**rest {
+                Datum::Symbol(rest, _) => (&parameters[..], Some(rest.clone())),
+                _ => return Err(malformed()),
+            },
+            _ => return Err(malformed()),
+        };
+        let parameters = parameters
+            .iter()
+            .map(|parameter| parameter.symbol().map(String::from).ok_or_else(malformed))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Value::Procedure(Rc::new(Procedure::Lambda {
+            parameters,
+            rest,
+            body: body.to_vec(),
+            locals: locals.clone(),
+        })))
+    }
+
+    // Binds the variables of a `let`, `let*`, `letrec` or `letrec*` form, or
+    // the loop of a named `let`, and returns the scope and body.
+    fn let_form<'t>(
+        &mut self,
+        keyword: &str,
+        items: &'t [Datum],
+        span: &Range<usize>,
+        locals: &Locals,
+        invocation: &mut Invocation,
+    ) -> Result<(Locals, &'t [Datum]), ParseError> {
+        let malformed = || invalid("malformed special form", span.clone());
+
+        let (name, bindings, body) = match items {
+            [_, Datum::Symbol(name, _), Datum::List(bindings, _), body @ ..]
+                if keyword == "let" =>
+            {
+                (Some(name), bindings, body)
+            }
+            [_, Datum::List(bindings, _), body @ ..] => (None, bindings, body),
+            _ => return Err(malformed()),
+        };
+        if body.is_empty() {
+            return Err(malformed());
+        }
+
+        let mut names = Vec::new();
+        let mut initializers = Vec::new();
+        for binding in bindings {
+            match binding {
+                Datum::List(binding, _) => match &binding[..] {
+                    [Datum::Symbol(name, _), initializer] => {
+                        names.push(name.clone());
+                        initializers.push(initializer);
+                    }
+                    _ => return Err(malformed()),
+                },
+                _ => return Err(malformed()),
+            }
+        }
+
+        let scope = locals.extend();
+        if keyword == "let" {
+            let mut values = Vec::new();
+            for initializer in initializers {
+                values.push(self.evaluate(initializer, locals, invocation)?);
+            }
+
+            if let Some(name) = name {
+                let formals = Datum::List(
+                    names
+                        .iter()
+                        .map(|name| Datum::Symbol(name.clone(), span.clone()))
+                        .collect(),
+                    span.clone(),
+                );
+                let procedure = self.closure(&formals, body, &scope)?;
+                scope.bind(name.clone(), Local::Value(procedure.clone()));
+                let Value::Procedure(lambda) = procedure else {
+                    unreachable!()
+                };
+                return Ok((arguments(&lambda, values, span)?, body));
+            }
+
+            for (name, value) in names.into_iter().zip(values) {
+                scope.bind(name, Local::Value(value));
+            }
+        } else {
+            // The bindings of `let*` and `letrec*` are made one at a time in
+            // one scope, where later ones shadow earlier ones of the same
+            // name.
+            for (name, initializer) in names.into_iter().zip(initializers) {
+                let value = self.evaluate(initializer, &scope, invocation)?;
+                scope.bind(name, Local::Value(value));
+            }
+        }
+
+        Ok((scope, body))
+    }
+
+    // Returns the expression of the first clause whose test is true, or the
+    // value of the test of a clause without expressions.
+    fn cond(
+        &mut self,
+        clauses: &[Datum],
+        span: &Range<usize>,
+        locals: &Locals,
+        invocation: &mut Invocation,
+    ) -> Result<Tail, ParseError> {
+        for clause in clauses {
+            let clause = match clause {
+                Datum::List(clause, _) if !clause.is_empty() => clause,
+                _ => return Err(invalid("malformed special form", span.clone())),
+            };
+
+            let test = match &clause[0] {
+                Datum::Symbol(name, _)
+                    if locals.lookup(name).is_none()
+                        && self.resolve(name, invocation.environment)
+                            == Meaning::Free(String::from("else")) =>
+                {
+                    Value::Boolean(true)
+                }
+                test => self.evaluate(test, locals, invocation)?,
+            };
+            if truthy(&test) {
+                return match clause.len() {
+                    1 => Ok(Tail::Value(test)),
+                    _ => Ok(Tail::Expression(
+                        self.sequence(&clause[1..], locals, invocation)?,
+                        locals.clone(),
+                    )),
+                };
+            }
+        }
+
+        Ok(Tail::Value(unspecified()))
+    }
+
+    // Matches the syntax object against the pattern of each clause in turn,
+    // and returns the output expression of the first clause that matches and
+    // whose fender, if any, is true, in the scope of its pattern variables.
+    fn syntax_case(
+        &mut self,
+        items: &[Datum],
+        span: &Range<usize>,
+        locals: &Locals,
+        invocation: &mut Invocation,
+    ) -> Result<Tail, ParseError> {
+        let malformed = || invalid("malformed `syntax-case`", span.clone());
+
+        let (input, literals, clauses) = match items {
+            [_, input, Datum::List(literals, _), clauses @ ..] => (input, literals, clauses),
+            _ => return Err(malformed()),
+        };
+        let literals: Vec<String> = literals
+            .iter()
+            .map(|literal| literal.symbol().map(String::from).ok_or_else(malformed))
+            .collect::<Result<_, _>>()?;
+
+        let input = to_datum(self.evaluate(input, locals, invocation)?, span)?;
+
+        for clause in clauses {
+            let (pattern, fender, output) = match clause {
+                Datum::List(clause, _) => match &clause[..] {
+                    [pattern, output] => (pattern, None, output),
+                    [pattern, fender, output] => (pattern, Some(fender), output),
+                    _ => return Err(malformed()),
+                },
+                _ => return Err(malformed()),
+            };
+
+            let scope = locals.extend();
+            if !self.bind_pattern(pattern, &input, &literals, &scope, invocation) {
+                continue;
+            }
+            if let Some(fender) = fender {
+                if !truthy(&self.evaluate(fender, &scope, invocation)?) {
+                    continue;
+                }
+            }
+            return Ok(Tail::Expression(output.clone(), scope));
+        }
+
+        Err(invalid(
+            "no `syntax-case` pattern matches this syntax",
+            input.span(),
+        ))
+    }
+
+    fn bind_pattern(
+        &self,
+        pattern: &Datum,
+        input: &Datum,
+        literals: &[String],
+        scope: &Locals,
+        invocation: &Invocation,
+    ) -> bool {
+        let context = Context {
+            ellipsis: "...",
+            literals,
+            environment: invocation.environment,
+        };
+
+        let mut bindings = Bindings::new();
+        if !self.matches(&context, pattern, input, invocation.use_site, &mut bindings) {
+            return false;
+        }
+        for name in variables(&context, pattern) {
+            if let Some(matched) = bindings.remove(&name) {
+                scope.bind(name, Local::Pattern(matched));
+            }
+        }
+        true
+    }
+
+    // Fills in a template with the pattern variables in scope. Identifiers it
+    // introduces are renamed as in `syntax-rules`.
+    fn syntax(
+        &mut self,
+        template: &Datum,
+        locals: &Locals,
+        invocation: &mut Invocation,
+    ) -> Result<Value, ParseError> {
+        let mut names = Vec::new();
+        symbols(template, &mut names);
+        let mut bindings = Bindings::new();
+        for name in names {
+            if let Some(Local::Pattern(matched)) = locals.lookup(&name) {
+                bindings.insert(name, matched);
+            }
+        }
+
+        let mut transcription = Transcription {
+            context: Context {
+                ellipsis: "...",
+                literals: &[],
+                environment: invocation.environment,
+            },
+            renames: &mut invocation.renames,
+            span: &invocation.span,
+        };
+        let datum = self.template(&mut transcription, template, &bindings, true)?;
+        Ok(Value::Syntax(datum))
+    }
+
+    // Returns the name an identifier had in the source. Variables are
+    // renamed in the output the same way as aliases, but without an entry.
+    pub(super) fn original<'n>(&'n self, mut name: &'n str) -> &'n str {
+        while let Some((original, _)) = self.aliases.get(name) {
+            name = original;
+        }
+        name.split(' ').next().unwrap_or(name)
+    }
+
+    fn quote(&self, datum: &Datum) -> Value {
+        match datum {
+            Datum::Symbol(name, _) => Value::Symbol(self.original(name).to_string()),
+            Datum::List(items, _) | Datum::Pair(items, _, _) => {
+                let tail = match datum {
+                    Datum::Pair(_, tail, _) => self.quote(tail),
+                    _ => Value::Null,
+                };
+                items.iter().rev().fold(tail, |tail, item| {
+                    Value::Pair(Rc::new((self.quote(item), tail)))
+                })
+            }
+            Datum::Vector(items, _) => {
+                Value::Vector(Rc::new(items.iter().map(|item| self.quote(item)).collect()))
+            }
+            constant => self::constant(constant),
+        }
+    }
+
+    // Converts a datum to syntax. Symbols become identifiers that mean what
+    // `context` would mean if it were written in their place.
+    fn datum_to_syntax(&mut self, context: &Datum, value: Value) -> Value {
+        let (context, span) = match context {
+            Datum::Symbol(name, span) => (name, span),
+            _ => return value,
+        };
+
+        match value {
+            Value::Symbol(name) => {
+                let name = match self.aliases.get(context.as_str()) {
+                    Some((_, environment)) => {
+                        let environment = environment.clone();
+                        self.count += 1;
+                        let alias = format!("{} {}", name, self.count);
+                        self.aliases.insert(alias.clone(), (name, environment));
+                        alias
+                    }
+                    None => name,
+                };
+                Value::Syntax(Datum::Symbol(name, span.clone()))
+            }
+            Value::Pair(pair) => {
+                let (car, cdr) = (*pair).clone();
+                let car = self.datum_to_syntax(&Datum::Symbol(context.clone(), span.clone()), car);
+                let cdr = self.datum_to_syntax(&Datum::Symbol(context.clone(), span.clone()), cdr);
+                Value::Pair(Rc::new((car, cdr)))
+            }
+            Value::Vector(items) => Value::Vector(Rc::new(
+                items
+                    .iter()
+                    .map(|item| {
+                        self.datum_to_syntax(
+                            &Datum::Symbol(context.clone(), span.clone()),
+                            item.clone(),
+                        )
+                    })
+                    .collect(),
+            )),
+            value => value,
+        }
+    }
+
+    fn syntax_to_datum(&self, value: Value) -> Value {
+        match value {
+            Value::Syntax(datum) => self.quote(&datum),
+            Value::Pair(pair) => {
+                let (car, cdr) = (*pair).clone();
+                Value::Pair(Rc::new((
+                    self.syntax_to_datum(car),
+                    self.syntax_to_datum(cdr),
+                )))
+            }
+            Value::Vector(items) => Value::Vector(Rc::new(
+                items
+                    .iter()
+                    .map(|item| self.syntax_to_datum(item.clone()))
+                    .collect(),
+            )),
+            value => value,
+        }
+    }
+
+    fn primitive(
+        &mut self,
+        name: &str,
+        values: Vec<Value>,
+        span: &Range<usize>,
+        invocation: &mut Invocation,
+    ) -> Result<Value, ParseError> {
+        let error = |message| Err(invalid(message, span.clone()));
+        let integer = |value: &Value| match expose(value.clone()) {
+            Value::Integer(integer) => Ok(integer),
+            _ => Err(invalid("expected an integer", span.clone())),
+        };
+        let overflow = || invalid("integer overflow in macro transformer", span.clone());
+        // The `fx` primitives compute with fixnums, as they do at run time.
+        let fixnum = |result: i64| {
+            if name.starts_with("fx") && !(FIXNUM_MIN..=FIXNUM_MAX).contains(&result) {
+                Err(overflow())
+            } else {
+                Ok(Value::Integer(result))
+            }
+        };
+
+        let arity = match name {
+            "list" | "vector" | "append" | "string-append" | "+" | "*" | "generate-temporaries" => {
+                None
+            }
+            "apply" | "map" => Some(2..usize::MAX),
+            "-" | "=" | "<" | "<=" | ">" | ">=" => Some(1..usize::MAX),
+            "fx+" | "fx-" | "fx*" | "fx=" | "fx<" | "fx<=" | "fx>" | "fx>=" | "cons" | "eq?"
+            | "eqv?" | "equal?" | "quotient" | "remainder" | "vector-ref" | "datum->syntax"
+            | "free-identifier=?" | "bound-identifier=?" => Some(2..3),
+            _ => Some(1..2),
+        };
+        if let Some(arity) = arity {
+            if !arity.contains(&values.len()) {
+                return error("wrong number of arguments in macro transformer");
+            }
+        }
+
+        let boolean = Value::Boolean;
+        let value = &values.first().cloned().map(expose).unwrap_or(Value::Null);
+
+        Ok(match name {
+            "+" | "fx+" | "*" | "fx*" => {
+                let multiply = name.ends_with('*');
+                let mut result = multiply as i64;
+                for value in &values {
+                    let value = integer(value)?;
+                    result = match multiply {
+                        true => result.checked_mul(value),
+                        false => result.checked_add(value),
+                    }
+                    .ok_or_else(overflow)?;
+                }
+                fixnum(result)?
+            }
+            "-" | "fx-" => {
+                let first = integer(value)?;
+                if values.len() == 1 {
+                    fixnum(first.checked_neg().ok_or_else(overflow)?)?
+                } else {
+                    let mut result = first;
+                    for value in &values[1..] {
+                        result = result.checked_sub(integer(value)?).ok_or_else(overflow)?;
+                    }
+                    fixnum(result)?
+                }
+            }
+            "quotient" | "remainder" => {
+                let (a, b) = (integer(&values[0])?, integer(&values[1])?);
+                if b == 0 {
+                    return error("division by zero in macro transformer");
+                }
+                match name {
+                    "quotient" => Value::Integer(a.checked_div(b).ok_or_else(overflow)?),
+                    _ => Value::Integer(a.checked_rem(b).ok_or_else(overflow)?),
+                }
+            }
+            "fxadd1" => fixnum(integer(value)?.checked_add(1).ok_or_else(overflow)?)?,
+            "fxsub1" => fixnum(integer(value)?.checked_sub(1).ok_or_else(overflow)?)?,
+            "=" | "<" | "<=" | ">" | ">=" | "fx=" | "fx<" | "fx<=" | "fx>" | "fx>=" => {
+                let integers = values.iter().map(integer).collect::<Result<Vec<_>, _>>()?;
+                boolean(
+                    integers
+                        .windows(2)
+                        .all(|pair| match name.trim_start_matches("fx") {
+                            "=" => pair[0] == pair[1],
+                            "<" => pair[0] < pair[1],
+                            "<=" => pair[0] <= pair[1],
+                            ">" => pair[0] > pair[1],
+                            _ => pair[0] >= pair[1],
+                        }),
+                )
+            }
+            "zero?" | "fxzero?" => boolean(integer(value)? == 0),
+            "fixnum?" => boolean(matches!(value, Value::Integer(_))),
+            "integer?" => boolean(matches!(value, Value::Integer(_) | Value::Bignum(_))),
+            "not" => boolean(!truthy(value)),
+            "boolean?" => boolean(matches!(value, Value::Boolean(_))),
+            "char?" => boolean(matches!(value, Value::Character(_))),
+            "char->fixnum" => match value {
+                Value::Character(character) => Value::Integer(u32::from(*character).into()),
+                _ => return error("expected a character"),
+            },
+            "fixnum->char" => match u32::try_from(integer(value)?).ok().and_then(char::from_u32) {
+                Some(character) => Value::Character(character),
+                None => return error("expected a character code"),
+            },
+            "null?" => boolean(matches!(value, Value::Null)),
+            "pair?" => boolean(matches!(value, Value::Pair(_))),
+            "list?" => boolean(elements(values[0].clone()).is_some()),
+            "symbol?" => boolean(matches!(value, Value::Symbol(_))),
+            "string?" => boolean(matches!(value, Value::String(_))),
+            "vector?" => boolean(matches!(value, Value::Vector(_))),
+            "procedure?" => boolean(matches!(value, Value::Procedure(_))),
+            "identifier?" => boolean(matches!(values[0], Value::Syntax(Datum::Symbol(..)))),
+            "eq?" | "eqv?" => boolean(eqv(&expose(values[0].clone()), &expose(values[1].clone()))),
+            "equal?" => boolean(equal(&values[0], &values[1])),
+            "cons" => Value::Pair(Rc::new((values[0].clone(), values[1].clone()))),
+            "car" | "cdr" | "cadr" | "cddr" | "caddr" => {
+                let mut value = value.clone();
+                for step in name[1..name.len() - 1].chars().rev() {
+                    value = match expose(value) {
+                        Value::Pair(pair) if step == 'a' => pair.0.clone(),
+                        Value::Pair(pair) => pair.1.clone(),
+                        _ => return error("expected a pair"),
+                    };
+                }
+                value
+            }
+            "list" => list(values),
+            "length" => match elements(values[0].clone()) {
+                Some(items) => Value::Integer(items.len() as i64),
+                None => return error("expected a list"),
+            },
+            "reverse" => match elements(values[0].clone()) {
+                Some(mut items) => {
+                    items.reverse();
+                    list(items)
+                }
+                None => return error("expected a list"),
+            },
+            "append" => {
+                let mut values = values;
+                let mut result = values.pop().unwrap_or(Value::Null);
+                for value in values.into_iter().rev() {
+                    match elements(value) {
+                        Some(items) => {
+                            result = items
+                                .into_iter()
+                                .rev()
+                                .fold(result, |tail, item| Value::Pair(Rc::new((item, tail))));
+                        }
+                        None => return error("expected a list"),
+                    }
+                }
+                result
+            }
+            "apply" => {
+                let mut values = values;
+                let procedure = values.remove(0);
+                let last = values.pop().unwrap();
+                match elements(last) {
+                    Some(items) => values.extend(items),
+                    None => return error("expected a list"),
+                }
+                return self.apply(&procedure, values, span, invocation);
+            }
+            "map" => {
+                let mut lists = Vec::new();
+                for value in &values[1..] {
+                    match elements(value.clone()) {
+                        Some(items) => lists.push(items),
+                        None => return error("expected a list"),
+                    }
+                }
+                let length = lists.iter().map(Vec::len).min().unwrap();
+                let mut results = Vec::new();
+                for i in 0..length {
+                    let arguments = lists.iter().map(|items| items[i].clone()).collect();
+                    results.push(self.apply(&values[0], arguments, span, invocation)?);
+                }
+                list(results)
+            }
+            "vector" => Value::Vector(Rc::new(values)),
+            "vector-length" => match value {
+                Value::Vector(items) => Value::Integer(items.len() as i64),
+                _ => return error("expected a vector"),
+            },
+            "vector-ref" => match (value, usize::try_from(integer(&values[1])?)) {
+                (Value::Vector(items), Ok(index)) if index < items.len() => items[index].clone(),
+                (Value::Vector(_), _) => return error("vector index out of range"),
+                _ => return error("expected a vector"),
+            },
+            "vector->list" => match value {
+                Value::Vector(items) => list(items.to_vec()),
+                _ => return error("expected a vector"),
+            },
+            "list->vector" => match elements(values[0].clone()) {
+                Some(items) => Value::Vector(Rc::new(items)),
+                None => return error("expected a list"),
+            },
+            "string-length" => match value {
+                Value::String(string) => Value::Integer(string.chars().count() as i64),
+                _ => return error("expected a string"),
+            },
+            "string-append" => {
+                let mut result = String::new();
+                for value in values {
+                    match expose(value) {
+                        Value::String(string) => result.push_str(&string),
+                        _ => return error("expected a string"),
+                    }
+                }
+                Value::String(result)
+            }
+            "symbol->string" => match value {
+                Value::Symbol(name) => Value::String(name.clone()),
+                _ => return error("expected a symbol"),
+            },
+            "string->symbol" => match value {
+                Value::String(string) => Value::Symbol(string.clone()),
+                _ => return error("expected a string"),
+            },
+            "number->string" => Value::String(integer(value)?.to_string()),
+            "syntax->datum" => self.syntax_to_datum(values[0].clone()),
+            "datum->syntax" => match &values[0] {
+                Value::Syntax(context @ Datum::Symbol(..)) => {
+                    self.datum_to_syntax(context, values[1].clone())
+                }
+                _ => return error("expected an identifier"),
+            },
+            "free-identifier=?" | "bound-identifier=?" => match (&values[0], &values[1]) {
+                (Value::Syntax(Datum::Symbol(a, _)), Value::Syntax(Datum::Symbol(b, _))) => {
+                    match name {
+                        "bound-identifier=?" => boolean(a == b),
+                        _ => boolean(
+                            self.resolve(a, invocation.use_site)
+                                == self.resolve(b, invocation.use_site),
+                        ),
+                    }
+                }
+                _ => return error("expected an identifier"),
+            },
+            // Temporaries are identifiers that nothing else can refer to.
+            "generate-temporaries" => {
+                let count = match values.as_slice() {
+                    [items] => elements(items.clone()).map(|items| items.len()),
+                    _ => None,
+                };
+                let temporaries = match count {
+                    Some(count) => (0..count)
+                        .map(|_| {
+                            self.count += 1;
+                            let alias = format!("t {}", self.count);
+                            self.aliases
+                                .insert(alias.clone(), (String::from("t"), Environment::default()));
+                            Value::Syntax(Datum::Symbol(alias, span.clone()))
+                        })
+                        .collect(),
+                    None => return error("expected a list"),
+                };
+                list(temporaries)
+            }
+            _ => unreachable!("unknown primitive `{}`", name),
+        })
+    }
+}
+
+// Binds the parameters of a procedure to the values it is called with.
+fn arguments(
+    lambda: &Procedure,
+    mut values: Vec<Value>,
+    span: &Range<usize>,
+) -> Result<Locals, ParseError> {
+    let (parameters, rest, locals) = match lambda {
+        Procedure::Lambda {
+            parameters,
+            rest,
+            locals,
+            ..
+        } => (parameters, rest, locals),
+        Procedure::Primitive(_) => unreachable!(),
+    };
+
+    if values.len() < parameters.len() || (rest.is_none() && values.len() > parameters.len()) {
+        return Err(invalid(
+            "wrong number of arguments in macro transformer",
+            span.clone(),
+        ));
+    }
+
+    let scope = locals.extend();
+    let extra = values.split_off(parameters.len());
+    for (parameter, value) in parameters.iter().zip(values) {
+        scope.bind(parameter.clone(), Local::Value(value));
+    }
+    if let Some(rest) = rest {
+        scope.bind(rest.clone(), Local::Value(list(extra)));
+    }
+
+    Ok(scope)
+}
+
+// Converts what a transformer returned to a datum. Parts that are not syntax
+// objects get the span of the macro use.
+fn to_datum(value: Value, span: &Range<usize>) -> Result<Datum, ParseError> {
+    Ok(match value {
+        Value::Syntax(datum) => datum,
+        Value::Boolean(boolean) => Datum::Boolean(boolean, span.clone()),
+        Value::Character(character) => Datum::Character(character, span.clone()),
+        Value::Integer(integer) => Datum::Integer(integer, span.clone()),
+        Value::Bignum(bignum) => Datum::Bignum(bignum, span.clone()),
+        Value::Flonum(flonum) => Datum::Flonum(flonum, span.clone()),
+        Value::Rational(numerator, denominator) => {
+            Datum::Rational(numerator, denominator, span.clone())
+        }
+        Value::String(string) => Datum::String(string, span.clone()),
+        Value::Symbol(name) => Datum::Symbol(name, span.clone()),
+        Value::Null => Datum::List(Vec::new(), span.clone()),
+        Value::Pair(_) => {
+            let mut items = Vec::new();
+            let mut value = value;
+            while let Value::Pair(pair) = value {
+                let (car, cdr) = (*pair).clone();
+                items.push(to_datum(car, span)?);
+                value = cdr;
+            }
+            Datum::pair(items, to_datum(value, span)?, span.clone())
+        }
+        Value::Vector(items) => Datum::Vector(
+            items
+                .iter()
+                .map(|item| to_datum(item.clone(), span))
+                .collect::<Result<_, _>>()?,
+            span.clone(),
+        ),
+        Value::Procedure(_) => {
+            return Err(invalid(
+                "a macro transformer must return syntax, not a procedure",
+                span.clone(),
+            ))
+        }
+    })
+}
+
+// Takes the outermost layer of a syntax object apart, so that a list becomes
+// a pair, a vector a vector of syntax objects and a constant its value.
+// Identifiers stay syntax objects.
+fn expose(value: Value) -> Value {
+    match value {
+        Value::Syntax(Datum::List(items, _)) if items.is_empty() => Value::Null,
+        Value::Syntax(Datum::List(mut items, span)) => {
+            let rest = items.split_off(1);
+            let first = items.pop().unwrap();
+            let start = rest
+                .first()
+                .map_or(span.end - 1, |second| second.span().start);
+            let rest = Datum::List(rest, start..span.end);
+            Value::Pair(Rc::new((Value::Syntax(first), Value::Syntax(rest))))
+        }
+        Value::Syntax(Datum::Pair(mut items, tail, span)) => {
+            let rest = items.split_off(1);
+            let first = items.pop().unwrap();
+            let start = rest.first().unwrap_or(&tail).span().start;
+            let rest = Datum::pair(rest, *tail, start..span.end);
+            Value::Pair(Rc::new((Value::Syntax(first), Value::Syntax(rest))))
+        }
+        Value::Syntax(Datum::Vector(items, _)) => {
+            Value::Vector(Rc::new(items.into_iter().map(Value::Syntax).collect()))
+        }
+        Value::Syntax(
+            datum @ (Datum::Boolean(..)
+            | Datum::Character(..)
+            | Datum::Integer(..)
+            | Datum::Bignum(..)
+            | Datum::Flonum(..)
+            | Datum::Rational(..)
+            | Datum::String(..)),
+        ) => constant(&datum),
+        value => value,
+    }
+}
+
+fn elements(mut value: Value) -> Option<Vec<Value>> {
+    let mut items = Vec::new();
+    loop {
+        value = match expose(value) {
+            Value::Null => return Some(items),
+            Value::Pair(pair) => {
+                items.push(pair.0.clone());
+                pair.1.clone()
+            }
+            _ => return None,
+        };
+    }
+}
+
+fn list(items: Vec<Value>) -> Value {
+    items
+        .into_iter()
+        .rev()
+        .fold(Value::Null, |tail, item| Value::Pair(Rc::new((item, tail))))
+}
+
+// The value of a constant. Identifiers and compound data are not constants,
+// and stay syntax.
+fn constant(datum: &Datum) -> Value {
+    match datum {
+        Datum::Boolean(boolean, _) => Value::Boolean(*boolean),
+        Datum::Character(character, _) => Value::Character(*character),
+        Datum::Integer(integer, _) => Value::Integer(*integer),
+        Datum::Bignum(bignum, _) => Value::Bignum(bignum.clone()),
+        Datum::Flonum(flonum, _) => Value::Flonum(*flonum),
+        Datum::Rational(numerator, denominator, _) => {
+            Value::Rational(numerator.clone(), denominator.clone())
+        }
+        Datum::String(string, _) => Value::String(string.clone()),
+        Datum::Symbol(..) | Datum::List(..) | Datum::Pair(..) | Datum::Vector(..) => {
+            Value::Syntax(datum.clone())
+        }
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    !matches!(
+        value,
+        Value::Boolean(false) | Value::Syntax(Datum::Boolean(false, _))
+    )
+}
+
+// The value of forms like a one-armed `if` whose value is unspecified.
+fn unspecified() -> Value {
+    Value::Boolean(false)
+}
+
+fn eqv(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Boolean(a), Value::Boolean(b)) => a == b,
+        (Value::Character(a), Value::Character(b)) => a == b,
+        (Value::Integer(a), Value::Integer(b)) => a == b,
+        (Value::Bignum(a), Value::Bignum(b)) => a == b,
+        (Value::Flonum(a), Value::Flonum(b)) => a.to_bits() == b.to_bits(),
+        (Value::Rational(a, b), Value::Rational(c, d)) => a == c && b == d,
+        (Value::Null, Value::Null) => true,
+        (Value::Symbol(a), Value::Symbol(b)) => a == b,
+        (Value::Pair(a), Value::Pair(b)) => Rc::ptr_eq(a, b),
+        (Value::Procedure(a), Value::Procedure(b)) => Rc::ptr_eq(a, b),
+        (Value::Vector(a), Value::Vector(b)) => Rc::ptr_eq(a, b),
+        (Value::Syntax(Datum::Symbol(a, _)), Value::Syntax(Datum::Symbol(b, _))) => a == b,
+        _ => false,
+    }
+}
+
+fn equal(a: &Value, b: &Value) -> bool {
+    match (expose(a.clone()), expose(b.clone())) {
+        (Value::Pair(a), Value::Pair(b)) => equal(&a.0, &b.0) && equal(&a.1, &b.1),
+        (Value::Vector(a), Value::Vector(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| equal(a, b))
+        }
+        (Value::String(a), Value::String(b)) => a == b,
+        (a, b) => eqv(&a, &b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::expand;
+
+    #[test]
+    fn transformers() {
+        let cases = [
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((_ a b) (syntax (fx+ b a)))))) (m 1 2)",
+                "(fx+ 2 1)",
+            ),
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((_ n) (let f ((n (syntax->datum (syntax n)))) (if (= n 0) 1 (* n (f (- n 1))))))))) (m 5)",
+                "120",
+            ),
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((_ e ...) (length (syntax (e ...))))))) (m 1 2 3)",
+                "3",
+            ),
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((_ a) (fixnum? (syntax->datum (syntax a))) (syntax 1)) ((_ a) (syntax 2))))) (cons (m 1) (m #t))",
+                "(cons 1 2)",
+            ),
+            (
+                "(define-syntax m (lambda (x) (syntax-case x (=>) ((_ => a) (syntax a)) ((_ a b) (syntax b))))) (cons (m => 1) (let ((=> 0)) (m => 2)))",
+                "(cons 1 (let ((=> 1 0)) 2))",
+            ),
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((_ (a b) ...) (with-syntax (((t ...) (generate-temporaries (syntax (a ...))))) (syntax (let ((t a) ...) (fx+ t ...)))))))) (m (1 2) (3 4))",
+                "(let ((t 5 1) (t 6 3)) (fx+ t 5 t 6))",
+            ),
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((_ v) (list->vector (map (lambda (n) (* n n)) (syntax->datum (syntax v)))))))) (m (1 2 3))",
+                "#(1 4 9)",
+            ),
+            // `it` gets the meaning of the macro keyword at the macro use.
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((k e) (with-syntax ((it (datum->syntax (syntax k) (quote it)))) (syntax (let ((it e)) it))))))) (m 1)",
+                "(let ((it 2 1)) it 2)",
+            ),
+            // The `t` introduced by the template does not capture the one
+            // passed in.
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((_ a b) (syntax (let ((t a)) (if t t b))))))) (let ((t 1)) (m #f t))",
+                "(let ((t 1 1)) (let ((t 5 #f)) (if t 5 t 5 t 1)))",
+            ),
+            (
+                "(let-syntax ((m (lambda (x) (syntax-case x () ((_ a) (if (identifier? (syntax a)) (syntax 1) (syntax 2))))))) (cons (m a) (m 1)))",
+                "(cons 1 2)",
+            ),
+        ];
+
+        for (source, expected) in &cases {
+            assert_eq!(expand(source).unwrap().to_string(), *expected);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((_ a) (syntax a))))) (m)",
+                "no `syntax-case` pattern matches this syntax",
+                69..72,
+            ),
+            (
+                "(define-syntax m (lambda (x) (car 1))) (m)",
+                "expected a pair",
+                29..36,
+            ),
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((_ a) a)))) (m 1)",
+                "pattern variable used outside `syntax`",
+                54..55,
+            ),
+            (
+                "(define (f) 1) (define-syntax m (lambda (x) (f))) (m)",
+                "variable is not available at compile time",
+                45..46,
+            ),
+            (
+                "(define-syntax m (lambda (x) (let f ((n 10000)) (if (= n 0) 0 (fx+ 1 (f (- n 1))))))) (m)",
+                "macro transformer recursion is too deep",
+                86..89,
+            ),
+            (
+                "(define-syntax m (lambda (x) (fx+ 2305843009213693951 1))) (m)",
+                "integer overflow in macro transformer",
+                29..56,
+            ),
+            (
+                "(define-syntax m (lambda (x) (fx* 4611686018427387903 2))) (m)",
+                "integer overflow in macro transformer",
+                29..56,
+            ),
+            (
+                "(define-syntax m (lambda (x) (fxsub1 -2305843009213693952))) (m)",
+                "integer overflow in macro transformer",
+                29..58,
+            ),
+            (
+                "(define-syntax m (lambda (x) (let f () (f)))) (m)",
+                "macro transformer makes too many calls",
+                46..49,
+            ),
+            (
+                "(define-syntax m (lambda (x) (lambda (y) y))) (m)",
+                "a macro transformer must return syntax, not a procedure",
+                46..49,
+            ),
+            (
+                "(define-syntax m (syntax-rules () ((_) 1))) (syntax-case 1 ())",
+                "`syntax-case` is only allowed in a macro transformer",
+                44..62,
+            ),
+        ];
+
+        for (source, message, span) in &cases {
+            let error = expand(source).unwrap_err();
+            assert_eq!(error.to_string(), *message);
+            assert_eq!(error.span, *span);
+        }
+    }
+
+    #[test]
+    fn tail_calls() {
+        let source = "(define-syntax m (lambda (x) (let loop ((n 100000)) (if (= n 0) (syntax 1) (loop (- n 1)))))) (m)";
+        assert_eq!(expand(source).unwrap().to_string(), "1");
+    }
+}
@@ -0,0 +1,1135 @@
+use std::{cell::RefCell, collections::HashMap, convert::TryFrom, ops::Range, rc::Rc};
+
+use super::{
+    analyze,
+    datum::{self, Datum},
+    expression::program::Program,
+    lex::lex,
+    parse::{ParseError, ParseErrorKind},
+};
+
+mod evaluate;
+
+use evaluate::Value;
+
+// Macro uses nested deeper than this, or expanding into another macro use
+// more often than this, are assumed not to terminate.
+const MAX_DEPTH: usize = 200;
+const MAX_EXPANSIONS: usize = 10000;
+
+/// Parses `source` as a program, first expanding the macros it defines with
+/// `define-syntax`, `let-syntax` or `letrec-syntax`.
+///
+/// Expansion works on the data read from the source before they are analyzed,
+/// and keeps the spans of the data it copies, so errors in expanded code are
+/// reported where the user wrote it. Macros are hygienic: every identifier a
+/// template introduces is renamed, so it neither captures nor is captured by
+/// the identifiers at the macro use, and it keeps the meaning it had where
+/// the macro was defined.
+///
+/// A macro is either a `syntax-rules` form or an expression that evaluates to
+/// a procedure from syntax to syntax. Such procedures are run by an
+/// interpreter in the compiler, and take their input apart with
+/// `syntax-case`.
+pub fn expand(source: &str) -> Result<Program, ParseError> {
+    let data = datum::read(&mut lex(source))?;
+
+    let mut expander = Expander::default();
+    let environment = Environment::default().extend();
+    let mut expanded = Vec::new();
+    for datum in data {
+        expander.top_level(datum, &environment, &mut expanded)?;
+    }
+
+    analyze::program(expanded).map_err(|error| expander.restore(error))
+}
+
+#[derive(Clone)]
+enum Meaning {
+    // A local variable, with the name it is given in the output.
+    Variable(String),
+    Macro(Rc<Macro>),
+    // Anything not bound locally: a keyword, a primitive or a procedure
+    // defined at top level.
+    Free(String),
+}
+
+impl PartialEq for Meaning {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Meaning::Variable(a), Meaning::Variable(b)) | (Meaning::Free(a), Meaning::Free(b)) => {
+                a == b
+            }
+            (Meaning::Macro(a), Meaning::Macro(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+struct Macro {
+    transformer: Transformer,
+    environment: Environment,
+}
+
+enum Transformer {
+    Rules {
+        ellipsis: String,
+        literals: Vec<String>,
+        rules: Vec<(Datum, Datum)>,
+    },
+    // A procedure from the syntax of a macro use to its expansion, evaluated
+    // at compile time.
+    Procedure(Value),
+}
+
+// How patterns and templates are read: the ellipsis, the identifiers that are
+// literals rather than pattern variables, and the environment of the macro
+// they belong to.
+struct Context<'a> {
+    ellipsis: &'a str,
+    literals: &'a [String],
+    environment: &'a Environment,
+}
+
+// A chain of scopes. Scopes are shared, so that the macros of a
+// `letrec-syntax` and those defined at top level can refer to each other.
+struct Environment<T = Meaning>(Option<Rc<Scope<T>>>);
+
+struct Scope<T> {
+    bindings: RefCell<HashMap<String, T>>,
+    parent: Environment<T>,
+}
+
+impl<T> Clone for Environment<T> {
+    fn clone(&self) -> Self {
+        Environment(self.0.clone())
+    }
+}
+
+impl<T> Default for Environment<T> {
+    fn default() -> Self {
+        Environment(None)
+    }
+}
+
+impl<T: Clone> Environment<T> {
+    fn extend(&self) -> Self {
+        Environment(Some(Rc::new(Scope {
+            bindings: RefCell::new(HashMap::new()),
+            parent: self.clone(),
+        })))
+    }
+
+    fn bind(&self, name: String, meaning: T) {
+        if let Some(scope) = &self.0 {
+            scope.bindings.borrow_mut().insert(name, meaning);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<T> {
+        let mut environment = self;
+        while let Some(scope) = &environment.0 {
+            if let Some(meaning) = scope.bindings.borrow().get(name) {
+                return Some(meaning.clone());
+            }
+            environment = &scope.parent;
+        }
+        None
+    }
+
+    // Replaces the innermost binding of `name`, and returns whether there
+    // was one.
+    fn assign(&self, name: &str, meaning: T) -> bool {
+        let mut environment = self;
+        while let Some(scope) = &environment.0 {
+            if let Some(bound) = scope.bindings.borrow_mut().get_mut(name) {
+                *bound = meaning;
+                return true;
+            }
+            environment = &scope.parent;
+        }
+        false
+    }
+}
+
+#[derive(Clone)]
+enum Match {
+    One(Datum),
+    Many(Vec<Match>),
+}
+
+type Bindings = HashMap<String, Match>;
+
+#[derive(Default)]
+struct Expander {
+    // The identifier each renamed identifier stands for, and the environment
+    // of the macro that introduced it.
+    aliases: HashMap<String, (String, Environment)>,
+    count: usize,
+    depth: usize,
+}
+
+fn invalid(message: &'static str, span: Range<usize>) -> ParseError {
+    ParseError::new(ParseErrorKind::Invalid(message), span)
+}
+
+impl Expander {
+    fn resolve(&self, name: &str, environment: &Environment) -> Meaning {
+        if let Some(meaning) = environment.lookup(name) {
+            return meaning;
+        }
+        match self.aliases.get(name) {
+            Some((original, environment)) => self.resolve(original, environment),
+            None => Meaning::Free(name.to_string()),
+        }
+    }
+
+    fn keyword(&self, datum: &Datum, environment: &Environment) -> Option<Meaning> {
+        datum.symbol().map(|name| self.resolve(name, environment))
+    }
+
+    // Renamed identifiers are told apart from those in the source by a space,
+    // which cannot appear in a symbol.
+    fn fresh(&mut self, name: &str) -> String {
+        let name = self.original(name).to_string();
+        self.count += 1;
+        format!("{} {}", name, self.count)
+    }
+
+    // Gives the identifier an error from analyzing the expansion is about
+    // the name the user wrote, rather than the one it was renamed to.
+    fn restore(&self, mut error: ParseError) -> ParseError {
+        if let ParseErrorKind::UnexpectedToken { found, .. } = &mut error.kind {
+            let name = found
+                .strip_prefix('`')
+                .and_then(|name| name.strip_suffix('`'));
+            if let Some(name) = name.filter(|name| !name.starts_with(&['"', '#'][..])) {
+                *found = format!("`{}`", self.original(name));
+            }
+        }
+        error
+    }
+
+    fn bind_variable(&mut self, datum: &Datum, environment: &Environment) -> Datum {
+        match datum {
+            Datum::Symbol(name, span) => {
+                let fresh = self.fresh(name);
+                environment.bind(name.clone(), Meaning::Variable(fresh.clone()));
+                Datum::Symbol(fresh, span.clone())
+            }
+            datum => datum.clone(),
+        }
+    }
+
+    fn top_level(
+        &mut self,
+        datum: Datum,
+        environment: &Environment,
+        output: &mut Vec<Datum>,
+    ) -> Result<(), ParseError> {
+        let (datum, _) = self.expand_uses(datum, environment)?;
+        let items = match &datum {
+            Datum::List(items, _) if !items.is_empty() => items,
+            _ => {
+                output.push(self.expression(datum, environment)?);
+                return Ok(());
+            }
+        };
+
+        match self.keyword(&items[0], environment) {
+            Some(Meaning::Free(keyword)) if keyword == "define-syntax" => match &items[..] {
+                [_, Datum::Symbol(name, _), specification] => {
+                    let transformer = self.transformer(specification, environment, environment)?;
+                    environment.bind(name.clone(), Meaning::Macro(Rc::new(transformer)));
+                    Ok(())
+                }
+                _ => Err(invalid("malformed `define-syntax`", datum.span())),
+            },
+            Some(Meaning::Free(keyword)) if keyword == "define" => {
+                output.push(self.definition(datum, environment)?);
+                Ok(())
+            }
+            _ => {
+                output.push(self.expression(datum, environment)?);
+                Ok(())
+            }
+        }
+    }
+
+    // The name of a procedure defined at top level is left as it is, unless
+    // a macro introduced it.
+    fn definition(&mut self, datum: Datum, environment: &Environment) -> Result<Datum, ParseError> {
+        let (mut items, span) = match datum {
+            Datum::List(items, span) => (items, span),
+            datum => return Ok(datum),
+        };
+
+        if let Some(Datum::List(header, _)) = items.get_mut(1) {
+            if let Some(Datum::Symbol(name, _)) = header.first_mut() {
+                if self.aliases.contains_key(name.as_str()) {
+                    let fresh = self.fresh(name);
+                    environment.bind(name.clone(), Meaning::Variable(fresh.clone()));
+                    *name = fresh;
+                } else {
+                    // Bound all the same, so that it is no longer taken for
+                    // a keyword of the same name.
+                    environment.bind(name.clone(), Meaning::Variable(name.clone()));
+                }
+            }
+        }
+
+        let scope = environment.extend();
+        let mut output = Vec::new();
+        for (i, item) in items.into_iter().enumerate() {
+            output.push(match (i, item) {
+                (1, Datum::List(header, span)) => {
+                    let mut header = header.into_iter();
+                    let mut bound: Vec<Datum> = header.next().into_iter().collect();
+                    bound.extend(header.map(|parameter| self.bind_variable(&parameter, &scope)));
+                    Datum::List(bound, span)
+                }
+                (0, item) => self.expression(item, environment)?,
+                (_, item) => self.expression(item, &scope)?,
+            });
+        }
+
+        Ok(Datum::List(output, span))
+    }
+
+    // Replaces a macro use by its expansion until something else is left,
+    // and returns whether anything was expanded. Only the macro uses nested
+    // inside an expansion take stack space, so only those count towards
+    // `depth`.
+    fn expand_uses(
+        &mut self,
+        mut datum: Datum,
+        environment: &Environment,
+    ) -> Result<(Datum, bool), ParseError> {
+        let mut count = 0;
+
+        while let Datum::List(items, span) = &datum {
+            let transformer = match items
+                .first()
+                .and_then(|item| self.keyword(item, environment))
+            {
+                Some(Meaning::Macro(transformer)) => transformer,
+                _ => break,
+            };
+            count += 1;
+            if count > MAX_EXPANSIONS || self.depth >= MAX_DEPTH {
+                return Err(invalid("macro expansion is too deep", span.clone()));
+            }
+            datum = self.transcribe(&transformer, &datum, environment)?;
+        }
+
+        Ok((datum, count > 0))
+    }
+
+    fn expression(&mut self, datum: Datum, environment: &Environment) -> Result<Datum, ParseError> {
+        let (datum, expanded) = self.expand_uses(datum, environment)?;
+
+        self.depth += expanded as usize;
+        let result = match datum {
+            Datum::Symbol(name, span) => match self.resolve(&name, environment) {
+                Meaning::Variable(name) | Meaning::Free(name) => Ok(Datum::Symbol(name, span)),
+                Meaning::Macro(_) => Err(invalid("macro keyword used as an expression", span)),
+            },
+            Datum::List(items, span) if !items.is_empty() => self.form(items, span, environment),
+            datum => Ok(datum),
+        };
+        self.depth -= expanded as usize;
+
+        result
+    }
+
+    fn form(
+        &mut self,
+        items: Vec<Datum>,
+        span: Range<usize>,
+        environment: &Environment,
+    ) -> Result<Datum, ParseError> {
+        let keyword = match self.keyword(&items[0], environment) {
+            Some(Meaning::Free(keyword)) => keyword,
+            _ => return self.all(items, span, environment),
+        };
+
+        match keyword.as_str() {
+            "let" | "let*" => self.binding(keyword == "let*", items, span, environment),
+            "lambda" => self.lambda(items, span, environment),
+            "define" => self.definition(Datum::List(items, span), environment),
+            "labels" => self.labels(items, span, environment),
+            "cond" => {
+                let mut items = items.into_iter();
+                let mut output = vec![self.expression(items.next().unwrap(), environment)?];
+                for clause in items {
+                    output.push(match clause {
+                        Datum::List(clause, span) => self.all(clause, span, environment)?,
+                        clause => clause,
+                    });
+                }
+                Ok(Datum::List(output, span))
+            }
+            "let-syntax" | "letrec-syntax" => {
+                self.syntax_binding(keyword == "letrec-syntax", items, span, environment)
+            }
+            "define-syntax" => Err(invalid(
+                "`define-syntax` is only allowed at top level",
+                span,
+            )),
+            "syntax-rules" => Err(invalid(
+                "`syntax-rules` is only allowed in a macro definition",
+                span,
+            )),
+            "syntax-case" => Err(invalid(
+                "`syntax-case` is only allowed in a macro transformer",
+                span,
+            )),
+            _ => self.all(items, span, environment),
+        }
+    }
+
+    fn all(
+        &mut self,
+        items: Vec<Datum>,
+        span: Range<usize>,
+        environment: &Environment,
+    ) -> Result<Datum, ParseError> {
+        let items = items
+            .into_iter()
+            .map(|item| self.expression(item, environment))
+            .collect::<Result<_, _>>()?;
+        Ok(Datum::List(items, span))
+    }
+
+    // `let` evaluates every initializer outside the new scope, `let*` each
+    // one in the scope of the bindings before it.
+    fn binding(
+        &mut self,
+        sequential: bool,
+        items: Vec<Datum>,
+        span: Range<usize>,
+        environment: &Environment,
+    ) -> Result<Datum, ParseError> {
+        let scope = environment.extend();
+        let mut output = Vec::new();
+
+        for (i, item) in items.into_iter().enumerate() {
+            output.push(match (i, item) {
+                (0, item) => self.expression(item, environment)?,
+                (1, Datum::List(bindings, span)) => {
+                    let mut converted = Vec::new();
+                    for binding in bindings {
+                        converted.push(match binding {
+                            Datum::List(binding, span) if binding.len() == 2 => {
+                                let [name, initializer] = <[Datum; 2]>::try_from(binding).unwrap();
+                                let (initializer, name) = if sequential {
+                                    let initializer = self.expression(initializer, &scope)?;
+                                    (initializer, self.bind_variable(&name, &scope))
+                                } else {
+                                    (self.expression(initializer, environment)?, name)
+                                };
+                                Datum::List(vec![name, initializer], span)
+                            }
+                            binding => binding,
+                        });
+                    }
+                    if !sequential {
+                        for binding in &mut converted {
+                            if let Datum::List(binding, _) = binding {
+                                if binding.len() == 2 {
+                                    binding[0] = self.bind_variable(&binding[0].clone(), &scope);
+                                }
+                            }
+                        }
+                    }
+                    Datum::List(converted, span)
+                }
+                (_, item) => self.expression(item, &scope)?,
+            });
+        }
+
+        Ok(Datum::List(output, span))
+    }
+
+    fn lambda(
+        &mut self,
+        items: Vec<Datum>,
+        span: Range<usize>,
+        environment: &Environment,
+    ) -> Result<Datum, ParseError> {
+        let scope = environment.extend();
+        let mut output = Vec::new();
+
+        for (i, item) in items.into_iter().enumerate() {
+            output.push(match (i, item) {
+                (0, item) => self.expression(item, environment)?,
+                (1, Datum::List(parameters, span)) => Datum::List(
+                    parameters
+                        .iter()
+                        .map(|parameter| self.bind_variable(parameter, &scope))
+                        .collect(),
+                    span,
+                ),
+                (_, item) => self.expression(item, &scope)?,
+            });
+        }
+
+        Ok(Datum::List(output, span))
+    }
+
+    // Labels name code, not variables, so only the parameters and bodies of
+    // the code are expanded.
+    fn labels(
+        &mut self,
+        items: Vec<Datum>,
+        span: Range<usize>,
+        environment: &Environment,
+    ) -> Result<Datum, ParseError> {
+        let mut output = Vec::new();
+
+        for (i, item) in items.into_iter().enumerate() {
+            output.push(match (i, item) {
+                (1, Datum::List(labels, span)) => {
+                    let mut converted = Vec::new();
+                    for label in labels {
+                        converted.push(match label {
+                            Datum::List(mut label, span) if label.len() == 2 => {
+                                let code = label.pop().unwrap();
+                                let code = match code {
+                                    Datum::List(code, span) if !code.is_empty() => {
+                                        self.lambda(code, span, environment)?
+                                    }
+                                    code => code,
+                                };
+                                label.push(code);
+                                Datum::List(label, span)
+                            }
+                            label => label,
+                        });
+                    }
+                    Datum::List(converted, span)
+                }
+                (_, item) => self.expression(item, environment)?,
+            });
+        }
+
+        Ok(Datum::List(output, span))
+    }
+
+    fn syntax_binding(
+        &mut self,
+        recursive: bool,
+        items: Vec<Datum>,
+        span: Range<usize>,
+        environment: &Environment,
+    ) -> Result<Datum, ParseError> {
+        let mut items = items.into_iter().skip(1);
+        let scope = environment.extend();
+        let definition = if recursive { &scope } else { environment };
+
+        match items.next() {
+            Some(Datum::List(bindings, _)) => {
+                for binding in bindings {
+                    match binding {
+                        Datum::List(binding, span) => match &binding[..] {
+                            [Datum::Symbol(name, _), specification] => {
+                                let transformer =
+                                    self.transformer(specification, definition, environment)?;
+                                scope.bind(name.clone(), Meaning::Macro(Rc::new(transformer)));
+                            }
+                            _ => return Err(invalid("malformed syntax binding", span)),
+                        },
+                        binding => return Err(invalid("malformed syntax binding", binding.span())),
+                    }
+                }
+            }
+            _ => return Err(invalid("malformed syntax binding", span)),
+        }
+
+        let mut body: Vec<Datum> = items.collect();
+        match body.len() {
+            0 => Err(invalid("expected a body", span)),
+            1 => self.expression(body.pop().unwrap(), &scope),
+            _ => {
+                body.insert(0, Datum::Symbol(String::from("begin"), span.clone()));
+                self.expression(Datum::List(body, span), &scope)
+            }
+        }
+    }
+
+    // Reads the transformer of a macro: a `syntax-rules` form, or an
+    // expression that evaluates to a procedure. The keyword is looked up
+    // where the form appears, but the macro's own identifiers mean what they
+    // do in `definition`.
+    fn transformer(
+        &mut self,
+        specification: &Datum,
+        definition: &Environment,
+        environment: &Environment,
+    ) -> Result<Macro, ParseError> {
+        let malformed = || invalid("malformed `syntax-rules`", specification.span());
+
+        let items = match specification {
+            Datum::List(items, _)
+                if items
+                    .first()
+                    .and_then(|item| self.keyword(item, environment))
+                    == Some(Meaning::Free(String::from("syntax-rules"))) =>
+            {
+                items
+            }
+            specification => {
+                let procedure = self.evaluate_transformer(specification, definition)?;
+                return Ok(Macro {
+                    transformer: Transformer::Procedure(procedure),
+                    environment: definition.clone(),
+                });
+            }
+        };
+
+        let (ellipsis, rest) = match &items[1..] {
+            [Datum::Symbol(ellipsis, _), rest @ ..] => (ellipsis.clone(), rest),
+            rest => (String::from("..."), rest),
+        };
+        let (literals, rules) = match rest {
+            [Datum::List(literals, _), rules @ ..] => (literals, rules),
+            _ => return Err(malformed()),
+        };
+
+        let literals = literals
+            .iter()
+            .map(|literal| literal.symbol().map(String::from).ok_or_else(malformed))
+            .collect::<Result<_, _>>()?;
+        let rules = rules
+            .iter()
+            .map(|rule| match rule {
+                Datum::List(rule, _) => match &rule[..] {
+                    [pattern @ (Datum::List(items, _) | Datum::Pair(items, _, _)), template]
+                        if !items.is_empty() =>
+                    {
+                        Ok((pattern.clone(), template.clone()))
+                    }
+                    _ => Err(malformed()),
+                },
+                _ => Err(malformed()),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Macro {
+            transformer: Transformer::Rules {
+                ellipsis,
+                literals,
+                rules,
+            },
+            environment: definition.clone(),
+        })
+    }
+
+    fn transcribe(
+        &mut self,
+        transformer: &Macro,
+        form: &Datum,
+        environment: &Environment,
+    ) -> Result<Datum, ParseError> {
+        match &transformer.transformer {
+            Transformer::Rules {
+                ellipsis,
+                literals,
+                rules,
+            } => {
+                let context = Context {
+                    ellipsis,
+                    literals,
+                    environment: &transformer.environment,
+                };
+                self.rewrite(context, rules, form, environment)
+            }
+            Transformer::Procedure(procedure) => {
+                self.apply_transformer(procedure, &transformer.environment, form, environment)
+            }
+        }
+    }
+
+    // Rewrites a use of a `syntax-rules` macro with the template of the first
+    // rule whose pattern matches it. The keyword position of patterns is
+    // ignored.
+    fn rewrite(
+        &mut self,
+        context: Context,
+        rules: &[(Datum, Datum)],
+        form: &Datum,
+        environment: &Environment,
+    ) -> Result<Datum, ParseError> {
+        let span = form.span();
+        let arguments = match form {
+            Datum::List(items, _) => &items[1..],
+            _ => return Err(invalid("malformed macro use", span)),
+        };
+
+        for (pattern, template) in rules {
+            let (patterns, tail) = items(pattern);
+
+            let mut bindings = Bindings::new();
+            if self.matches_all(
+                &context,
+                (&patterns[1..], tail),
+                (arguments, None),
+                &span,
+                environment,
+                &mut bindings,
+            ) {
+                let mut renames = HashMap::new();
+                let mut transcription = Transcription {
+                    context,
+                    renames: &mut renames,
+                    span: &span,
+                };
+                return self.template(&mut transcription, template, &bindings, true);
+            }
+        }
+
+        Err(invalid("no `syntax-rules` pattern matches this use", span))
+    }
+
+    fn matches(
+        &self,
+        context: &Context,
+        pattern: &Datum,
+        input: &Datum,
+        environment: &Environment,
+        bindings: &mut Bindings,
+    ) -> bool {
+        match (pattern, input) {
+            (Datum::Symbol(name, _), Datum::Symbol(input, _))
+                if context.literals.contains(name) =>
+            {
+                self.resolve(input, environment) == self.resolve(name, context.environment)
+            }
+            (Datum::Symbol(name, _), _) if context.literals.contains(name) => false,
+            (Datum::Symbol(name, _), _) if name == "_" => true,
+            (Datum::Symbol(name, _), input) => {
+                bindings.insert(name.clone(), Match::One(input.clone()));
+                true
+            }
+            (Datum::List(..) | Datum::Pair(..), Datum::List(..) | Datum::Pair(..))
+            | (Datum::Vector(..), Datum::Vector(..)) => self.matches_all(
+                context,
+                items(pattern),
+                items(input),
+                &input.span(),
+                environment,
+                bindings,
+            ),
+            (Datum::Boolean(pattern, _), Datum::Boolean(input, _)) => pattern == input,
+            (Datum::Character(pattern, _), Datum::Character(input, _)) => pattern == input,
+            (Datum::Integer(pattern, _), Datum::Integer(input, _)) => pattern == input,
+            (Datum::Bignum(pattern, _), Datum::Bignum(input, _)) => pattern == input,
+            (Datum::Flonum(pattern, _), Datum::Flonum(input, _)) => pattern == input,
+            (Datum::Rational(a, b, _), Datum::Rational(c, d, _)) => (a, b) == (c, d),
+            (Datum::String(pattern, _), Datum::String(input, _)) => pattern == input,
+            _ => false,
+        }
+    }
+
+    // Matches a sequence of patterns, which may contain one ellipsis and be
+    // followed by a dotted tail, against a sequence of inputs, which may be
+    // followed by one too.
+    fn matches_all(
+        &self,
+        context: &Context,
+        (patterns, tail): (&[Datum], Option<&Datum>),
+        (inputs, rest): (&[Datum], Option<&Datum>),
+        span: &Range<usize>,
+        environment: &Environment,
+        bindings: &mut Bindings,
+    ) -> bool {
+        if tail.is_none() && rest.is_some() {
+            return false;
+        }
+
+        let ellipsis = patterns
+            .iter()
+            .position(|pattern| pattern.symbol() == Some(context.ellipsis))
+            .filter(|&i| i > 0);
+
+        let (before, repeated, after) = match ellipsis {
+            Some(i) => (
+                &patterns[..i - 1],
+                Some(&patterns[i - 1]),
+                &patterns[i + 1..],
+            ),
+            None => (patterns, None, &[][..]),
+        };
+
+        let fixed = before.len() + after.len();
+        if inputs.len() < fixed || (repeated.is_none() && tail.is_none() && inputs.len() != fixed) {
+            return false;
+        }
+        let count = match repeated {
+            Some(_) => inputs.len() - fixed,
+            None => 0,
+        };
+
+        let matched = |patterns: &[Datum], inputs: &[Datum], bindings: &mut Bindings| {
+            patterns.iter().zip(inputs).all(|(pattern, input)| {
+                self.matches(context, pattern, input, environment, bindings)
+            })
+        };
+
+        if !matched(before, &inputs[..before.len()], bindings) {
+            return false;
+        }
+
+        if let Some(repeated) = repeated {
+            let mut matches = Vec::new();
+            for input in &inputs[before.len()..before.len() + count] {
+                let mut inner = Bindings::new();
+                if !self.matches(context, repeated, input, environment, &mut inner) {
+                    return false;
+                }
+                matches.push(inner);
+            }
+            for variable in variables(context, repeated) {
+                let sequence = matches
+                    .iter_mut()
+                    .filter_map(|inner| inner.remove(&variable))
+                    .collect();
+                bindings.insert(variable, Match::Many(sequence));
+            }
+        }
+
+        let start = before.len() + count;
+        if !matched(after, &inputs[start..start + after.len()], bindings) {
+            return false;
+        }
+
+        match tail {
+            Some(tail) => {
+                let remaining = inputs[start + after.len()..].to_vec();
+                let span = remaining
+                    .first()
+                    .map_or(span.end - 1, |first| first.span().start)
+                    ..span.end;
+                let remaining = match rest {
+                    Some(rest) => Datum::pair(remaining, rest.clone(), span),
+                    None => Datum::List(remaining, span),
+                };
+                self.matches(context, tail, &remaining, environment, bindings)
+            }
+            None => true,
+        }
+    }
+
+    fn template(
+        &mut self,
+        transcription: &mut Transcription,
+        template: &Datum,
+        bindings: &Bindings,
+        ellipsis: bool,
+    ) -> Result<Datum, ParseError> {
+        match template {
+            Datum::Symbol(name, _) => match bindings.get(name) {
+                Some(Match::One(datum)) => Ok(datum.clone()),
+                Some(Match::Many(_)) => Err(invalid(
+                    "pattern variable used without an ellipsis",
+                    transcription.span.clone(),
+                )),
+                None => Ok(Datum::Symbol(
+                    self.rename(transcription, name),
+                    transcription.span.clone(),
+                )),
+            },
+            Datum::List(items, _) => match &items[..] {
+                [Datum::Symbol(escape, _), template]
+                    if ellipsis && *escape == transcription.context.ellipsis =>
+                {
+                    self.template(transcription, template, bindings, false)
+                }
+                items => Ok(Datum::List(
+                    self.templates(transcription, items, bindings, ellipsis)?,
+                    transcription.span.clone(),
+                )),
+            },
+            Datum::Pair(items, tail, _) => Ok(Datum::pair(
+                self.templates(transcription, items, bindings, ellipsis)?,
+                self.template(transcription, tail, bindings, ellipsis)?,
+                transcription.span.clone(),
+            )),
+            Datum::Vector(items, _) => Ok(Datum::Vector(
+                self.templates(transcription, items, bindings, ellipsis)?,
+                transcription.span.clone(),
+            )),
+            constant => {
+                let mut constant = constant.clone();
+                *constant.span_mut() = transcription.span.clone();
+                Ok(constant)
+            }
+        }
+    }
+
+    fn templates(
+        &mut self,
+        transcription: &mut Transcription,
+        templates: &[Datum],
+        bindings: &Bindings,
+        ellipsis: bool,
+    ) -> Result<Vec<Datum>, ParseError> {
+        let symbol = transcription.context.ellipsis;
+        let is_ellipsis =
+            |template: Option<&Datum>| ellipsis && template.and_then(Datum::symbol) == Some(symbol);
+
+        let mut output = Vec::new();
+        let mut i = 0;
+
+        while i < templates.len() {
+            let template = &templates[i];
+            let mut depth = 0;
+            while is_ellipsis(templates.get(i + 1 + depth)) {
+                depth += 1;
+            }
+
+            if depth > 0 {
+                self.repeat(transcription, template, bindings, depth, &mut output)?;
+            } else {
+                output.push(self.template(transcription, template, bindings, ellipsis)?);
+            }
+            i += 1 + depth;
+        }
+
+        Ok(output)
+    }
+
+    fn repeat(
+        &mut self,
+        transcription: &mut Transcription,
+        template: &Datum,
+        bindings: &Bindings,
+        depth: usize,
+        output: &mut Vec<Datum>,
+    ) -> Result<(), ParseError> {
+        let mut names = Vec::new();
+        symbols(template, &mut names);
+        let sequences: Vec<(&String, &Vec<Match>)> = names
+            .iter()
+            .filter_map(|name| match bindings.get(name) {
+                Some(Match::Many(sequence)) => Some((name, sequence)),
+                _ => None,
+            })
+            .collect();
+
+        let count = match sequences.first() {
+            Some((_, sequence)) => sequence.len(),
+            None => {
+                return Err(invalid(
+                    "ellipsis follows a template without pattern variables",
+                    transcription.span.clone(),
+                ))
+            }
+        };
+        if sequences
+            .iter()
+            .any(|(_, sequence)| sequence.len() != count)
+        {
+            return Err(invalid(
+                "pattern variables under an ellipsis matched different lengths",
+                transcription.span.clone(),
+            ));
+        }
+
+        for i in 0..count {
+            let mut inner = bindings.clone();
+            for (name, sequence) in &sequences {
+                inner.insert((*name).clone(), sequence[i].clone());
+            }
+            if depth > 1 {
+                self.repeat(transcription, template, &inner, depth - 1, output)?;
+            } else {
+                output.push(self.template(transcription, template, &inner, true)?);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Every occurrence of an identifier in one expansion gets the same alias.
+    fn rename(&mut self, transcription: &mut Transcription, name: &str) -> String {
+        if let Some(alias) = transcription.renames.get(name) {
+            return alias.clone();
+        }
+
+        self.count += 1;
+        let alias = format!("{} {}", name, self.count);
+        self.aliases.insert(
+            alias.clone(),
+            (name.to_string(), transcription.context.environment.clone()),
+        );
+        transcription
+            .renames
+            .insert(name.to_string(), alias.clone());
+        alias
+    }
+}
+
+struct Transcription<'a> {
+    context: Context<'a>,
+    renames: &'a mut HashMap<String, String>,
+    span: &'a Range<usize>,
+}
+
+// Returns the pattern variables in `pattern`.
+fn variables(context: &Context, pattern: &Datum) -> Vec<String> {
+    let mut names = Vec::new();
+    symbols(pattern, &mut names);
+    names.retain(|name| {
+        name != "_" && *name != context.ellipsis && !context.literals.contains(name)
+    });
+    names
+}
+
+fn symbols(datum: &Datum, names: &mut Vec<String>) {
+    match datum {
+        Datum::Symbol(name, _) if !names.contains(name) => names.push(name.clone()),
+        Datum::List(items, _) | Datum::Vector(items, _) => {
+            for item in items {
+                symbols(item, names);
+            }
+        }
+        Datum::Pair(items, tail, _) => {
+            for item in items {
+                symbols(item, names);
+            }
+            symbols(tail, names);
+        }
+        _ => {}
+    }
+}
+
+// Returns the items of a list or vector and the tail of a dotted list.
+fn items(datum: &Datum) -> (&[Datum], Option<&Datum>) {
+    match datum {
+        Datum::List(items, _) | Datum::Vector(items, _) => (items, None),
+        Datum::Pair(items, tail, _) => (items, Some(tail)),
+        _ => (&[], None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expansion() {
+        let cases = [
+            ("(fx+ 1 2)", "(fx+ 1 2)"),
+            ("(define-syntax id (syntax-rules () ((_ x) x))) (id 1)", "1"),
+            (
+                "(define-syntax m (syntax-rules () ((_ (a b) ...) (fx+ (fx* a b) ...)))) (m (1 2) (3 4))",
+                "(fx+ (fx* 1 2) (fx* 3 4))",
+            ),
+            (
+                "(define-syntax m (syntax-rules () ((_ x . rest) (fx+ x . rest)))) (m 1 2)",
+                "(fx+ 1 2)",
+            ),
+            (
+                "(define-syntax m (syntax-rules () ((_ (a . b)) (fx+ a . b)))) (m (1 2))",
+                "(fx+ 1 2)",
+            ),
+            (
+                "(define-syntax m (syntax-rules (else) ((_ else) 1) ((_ x) 2))) (cons (m else) (m 3))",
+                "(cons 1 2)",
+            ),
+            (
+                "(define-syntax m (syntax-rules ::: () ((_ x :::) (fx+ x :::)))) (m 1 2)",
+                "(fx+ 1 2)",
+            ),
+            // The `t` introduced by the template does not capture the one
+            // passed in.
+            (
+                "(define-syntax my-or (syntax-rules () ((_ a b) (let ((t a)) (if t t b))))) (let ((t 1)) (my-or #f t))",
+                "(let ((t 1 1)) (let ((t 5 #f)) (if t 5 t 5 t 1)))",
+            ),
+            // The `x` in the template refers to the binding around the macro.
+            (
+                "(let ((x 1)) (let-syntax ((m (syntax-rules () ((_) x)))) (let ((x 2)) (m))))",
+                "(let ((x 1 1)) (let ((x 2 2)) x 1))",
+            ),
+            (
+                "(define-syntax m (syntax-rules () ((_ name) (define (name) (f))))) (m g) (g)",
+                "(define (g) (f))\n(g)",
+            ),
+            // Programs are expanded whether or not they define macros, and
+            // names bound in them are not taken for keywords.
+            (
+                "(let ((define-syntax 1)) (fx+ define-syntax 1))",
+                "(let ((define-syntax 1 1)) (fx+ define-syntax 1 1))",
+            ),
+            (
+                "(define (let-syntax x) x) (let-syntax 1)",
+                "(define (let-syntax x 1) x 1)\n(let-syntax 1)",
+            ),
+        ];
+
+        for (source, expected) in &cases {
+            assert_eq!(expand(source).unwrap().to_string(), *expected);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            (
+                "(define-syntax m (syntax-rules () ((_ x) x))) (m)",
+                "no `syntax-rules` pattern matches this use",
+                46..49,
+            ),
+            (
+                "(define-syntax m (syntax-rules () ((_ x) x))) m",
+                "macro keyword used as an expression",
+                46..47,
+            ),
+            (
+                "(define-syntax m (syntax-rules () ((_ x) (m x)))) (m 1)",
+                "macro expansion is too deep",
+                50..55,
+            ),
+            (
+                "(define-syntax m (syntax-rules () ((_ x) (fxadd1 (m x))))) (m 1)",
+                "macro expansion is too deep",
+                59..64,
+            ),
+            (
+                "(define-syntax m (syntax-rules () ((_ x ...) (x)))) (m 1)",
+                "pattern variable used without an ellipsis",
+                52..57,
+            ),
+            (
+                "(define-syntax m (syntax-rules () ((_ x) (fxadd1 x x)))) (m 1)",
+                "`fxadd1` expects 1 argument, found 2",
+                57..62,
+            ),
+            // Identifiers renamed by the expansion are reported by the
+            // names they were written with.
+            (
+                "(define-syntax m (syntax-rules () ((_ a) (let ((t a)) (set! t 2) t)))) (m 1)",
+                "expected `)`, found `t`",
+                71..76,
+            ),
+            (
+                "(define-syntax m 1) 1",
+                "a macro transformer must be `syntax-rules` or a procedure",
+                17..18,
+            ),
+            (
+                "(fxadd1 (define-syntax m (syntax-rules () ((_) 1))))",
+                "`define-syntax` is only allowed at top level",
+                8..51,
+            ),
+        ];
+
+        for (source, message, span) in &cases {
+            let error = expand(source).unwrap_err();
+            assert_eq!(error.to_string(), *message);
+            assert_eq!(error.span, *span);
+        }
+    }
+}
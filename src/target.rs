@@ -0,0 +1,69 @@
+use std::{fmt::Write, str::FromStr};
+
+/// Object file format conventions for the emitted assembly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    /// macOS, where C symbols carry a leading underscore.
+    MachO,
+    /// Linux and the BSDs.
+    Elf,
+}
+
+pub struct ParseTargetError;
+
+impl FromStr for Target {
+    type Err = ParseTargetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "macho" => Ok(Target::MachO),
+            "elf" => Ok(Target::Elf),
+            _ => Err(ParseTargetError),
+        }
+    }
+}
+
+impl Target {
+    pub fn host() -> Self {
+        if cfg!(target_vendor = "apple") {
+            Target::MachO
+        } else {
+            Target::Elf
+        }
+    }
+
+    /// Returns the assembly name of the C symbol `name`.
+    pub fn symbol(self, name: &str) -> String {
+        match self {
+            Target::MachO => format!("_{}", name),
+            Target::Elf => name.to_string(),
+        }
+    }
+
+    pub(crate) fn emit_text_section(self, output: &mut String) {
+        writeln!(output, "\t.text").unwrap();
+    }
+
+    pub(crate) fn emit_function_start(self, output: &mut String, symbol: &str) {
+        writeln!(output, "\t.globl {}", symbol).unwrap();
+        if self == Target::Elf {
+            writeln!(output, "\t.type {}, @function", symbol).unwrap();
+        }
+        writeln!(output, "{}:", symbol).unwrap();
+    }
+
+    pub(crate) fn emit_function_end(self, output: &mut String, symbol: &str) {
+        if self == Target::Elf {
+            writeln!(output, "\t.size {}, .-{}", symbol, symbol).unwrap();
+        }
+    }
+
+    /// Emits whatever the linker expects at the end of every object file. On
+    /// ELF, an empty `.note.GNU-stack` section marks the stack as not
+    /// executable.
+    pub(crate) fn emit_end(self, output: &mut String) {
+        if self == Target::Elf {
+            writeln!(output, "\t.section .note.GNU-stack,\"\",@progbits").unwrap();
+        }
+    }
+}
@@ -1,7 +1,9 @@
 use std::{
+    env,
     fs::{create_dir_all, remove_dir_all},
     io::Error,
     path::PathBuf,
+    process,
     sync::atomic::{AtomicUsize, Ordering},
 };
 
@@ -11,8 +13,9 @@ pub struct TemporaryDirectory(pub PathBuf);
 
 impl TemporaryDirectory {
     pub fn create() -> Result<Self, Error> {
-        let path = PathBuf::from(format!(
-            "/tmp/inc-{}",
+        let path = env::temp_dir().join(format!(
+            "inc-{}-{}",
+            process::id(),
             COUNTER.fetch_add(1, Ordering::Relaxed)
         ));
         create_dir_all(&path)?;
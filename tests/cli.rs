@@ -0,0 +1,187 @@
+use inc::temporary_directory::TemporaryDirectory;
+use std::{
+    fs::{read_to_string, write},
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn inc(arguments: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_inc"))
+        .args(arguments)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn build_and_run() {
+    let directory = TemporaryDirectory::create().unwrap();
+    let source = directory.0.join("fact.scm");
+    let executable = directory.0.join("fact");
+    write(
+        &source,
+        "(define (fact n) (if (fxzero? n) 1 (fx* n (fact (fxsub1 n)))))\n(fact 5)\n",
+    )
+    .unwrap();
+
+    let output = inc(&[
+        "build",
+        source.to_str().unwrap(),
+        "-o",
+        executable.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    let output = Command::new(&executable).output().unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "120\n");
+
+    let output = inc(&["run", source.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "120\n");
+}
+
+#[test]
+fn emit() {
+    let directory = TemporaryDirectory::create().unwrap();
+    let source = directory.0.join("answer.scm");
+    write(&source, "42").unwrap();
+
+    let output = inc(&["build", source.to_str().unwrap(), "--emit", "asm"]);
+    assert!(output.status.success());
+    let assembly = read_to_string(directory.0.join("answer.s")).unwrap();
+    assert!(assembly.contains("scheme_entry:"));
+
+    let output = inc(&["build", source.to_str().unwrap(), "--emit", "obj"]);
+    assert!(output.status.success());
+    assert!(directory.0.join("answer.o").exists());
+
+    let output = inc(&[
+        "build",
+        source.to_str().unwrap(),
+        "--emit",
+        "asm",
+        "--target",
+        "macho",
+        "-o",
+        directory.0.join("macho.s").to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    let assembly = read_to_string(directory.0.join("macho.s")).unwrap();
+    assert!(assembly.contains("_scheme_entry:"));
+}
+
+#[test]
+fn keeps_input() {
+    let directory = TemporaryDirectory::create().unwrap();
+    let source = directory.0.join("prog");
+    let assembly = directory.0.join("answer.s");
+    write(&source, "42").unwrap();
+    write(&assembly, "42").unwrap();
+
+    for arguments in [
+        &["build", source.to_str().unwrap()][..],
+        &["build", assembly.to_str().unwrap(), "--emit", "asm"],
+        &[
+            "build",
+            source.to_str().unwrap(),
+            "-o",
+            directory.0.join(".").join("prog").to_str().unwrap(),
+        ],
+    ] {
+        let output = inc(arguments);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .ends_with(": output would overwrite the input\n"));
+    }
+    assert_eq!(read_to_string(&source).unwrap(), "42");
+    assert_eq!(read_to_string(&assembly).unwrap(), "42");
+}
+
+#[test]
+fn unsafe_code() {
+    let directory = TemporaryDirectory::create().unwrap();
+    let source = directory.0.join("arity.scm");
+    write(&source, "(let ((f (lambda (x) x))) (f 1 2))").unwrap();
+
+    let output = inc(&["run", source.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "application: expected 1 argument, got 2\n"
+    );
+
+    let output = inc(&["run", source.to_str().unwrap(), "--unsafe"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1\n");
+}
+
+#[test]
+fn errors() {
+    let directory = TemporaryDirectory::create().unwrap();
+    let source = directory.0.join("broken.scm");
+    write(&source, "(fxadd1 1 2)").unwrap();
+
+    let output = inc(&["run", source.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("error: `fxadd1` expects 1 argument, found 2\n"));
+
+    let output = inc(&["run", directory.0.join("missing.scm").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+
+    for arguments in [
+        &["frobnicate"][..],
+        &["build"],
+        &["build", "x.scm", "--emit", "elf"],
+    ] {
+        assert_eq!(inc(arguments).status.code(), Some(2));
+    }
+}
+
+#[test]
+fn repl() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_inc"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            b"(define (square x)\n  (fx* x x))\n\
+              (square 7)\n\
+              (fxadd1 1 2)\n\
+              (define (square x) (fx+ x x)) (square 7)\n\
+              (let ((x 1)) y)\n\
+              (define-syntax twice (syntax-rules () ((_ e) (fx+ e e))))\n\
+              (twice (square 3))\n\
+              (cons 1\n 2)\n\
+              (car 1)\n\
+              (define (g) (h))\n\
+              (fx+ 20 22)\n",
+        )
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let values: Vec<String> = String::from_utf8(output.stdout)
+        .unwrap()
+        .split("> ")
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect();
+    assert_eq!(values, ["49", "14", "12", "(1 . 2)", "42"]);
+
+    let errors = String::from_utf8(output.stderr).unwrap();
+    assert!(errors.contains("error: `fxadd1` expects 1 argument, found 2\n"));
+    assert!(errors.contains("1 | (fxadd1 1 2)\n  | ^^^^^^^^^^^^\n"));
+    assert!(errors.contains("error: unbound variable `y`\n"));
+    assert!(errors.contains("error: unbound variable `h`\n"));
+    assert!(errors.contains("\ncar: expected pair, got 1\n"));
+}
@@ -1,28 +1,22 @@
-use inc::compile::compile;
-use std::{fs::write, process::Command};
+use inc::{
+    compile::{compile, run, CompilationError, Safety},
+    driver::{build, Emit},
+    target::Target,
+    temporary_directory::TemporaryDirectory,
+};
+use std::process::{Command, Output};
 
-mod temporary_directory;
-
-use temporary_directory::TemporaryDirectory;
-
-fn test(input: &str, expected: &str) {
+fn execute(input: &str, safety: Safety) -> Output {
     let directory = TemporaryDirectory::create().unwrap();
-    let object = directory.0.join("test.s");
     let binary = directory.0.join("test");
 
-    let output = compile(input.parse().unwrap()).unwrap();
-    write(&object, output).unwrap();
+    build(input, Target::host(), safety, Emit::Executable, &binary).unwrap();
 
-    let status = Command::new("clang")
-        .arg(&object)
-        .arg("src/runtime.c")
-        .arg("-o")
-        .arg(&binary)
-        .status()
-        .unwrap();
-    assert!(status.success());
+    Command::new(&binary).output().unwrap()
+}
 
-    let result = Command::new(&binary).output().unwrap();
+fn test(input: &str, expected: &str) {
+    let result = execute(input, Safety::Safe);
     let actual = String::from_utf8(result.stdout).unwrap();
 
     assert!(result.status.success());
@@ -35,17 +29,66 @@ fn cases<'a, I: IntoIterator<Item = &'a (&'a str, &'a str)>>(cases: I) {
     }
 }
 
+// Runs `input`, which must fail with the `expected` message on stderr.
+fn test_error(input: &str, expected: &str) {
+    let result = execute(input, Safety::Safe);
+    let actual = String::from_utf8(result.stderr).unwrap();
+
+    assert!(!result.status.success());
+    assert_eq!(expected, actual.trim());
+}
+
+fn error_cases<'a, I: IntoIterator<Item = &'a (&'a str, &'a str)>>(cases: I) {
+    for (input, message) in cases {
+        test_error(input, message);
+    }
+}
+
 // Step 1: Integers
 mod integers {
     use super::*;
 
     #[test]
     fn integers() {
-        for n in &[0, 1, -1, 10, -10, 2736, -2736, 536_870_911, -536_870_912] {
+        for n in &[
+            0i64,
+            1,
+            -1,
+            10,
+            -10,
+            2736,
+            -2736,
+            536_870_911,
+            -536_870_912,
+            4_294_967_296,
+            -4_294_967_296,
+            2_305_843_009_213_693_951,
+            -2_305_843_009_213_693_952,
+        ] {
             let s = n.to_string();
             test(&s, &s);
         }
     }
+
+    #[test]
+    fn wide_arithmetic() {
+        cases(&[
+            ("(fx+ 2147483647 1)", "2147483648"),
+            ("(fx* 100000 100000)", "10000000000"),
+            ("(fx- -2147483648 1)", "-2147483649"),
+            ("(fxsub1 -2305843009213693951)", "-2305843009213693952"),
+            ("(fx< 4294967296 4294967297)", "#t"),
+            ("(fxlognot 4294967296)", "-4294967297"),
+            (
+                "(cons 10000000000 #(20000000000))",
+                "(10000000000 . #(20000000000))",
+            ),
+            (
+                "(let ((v (make-vector 2))) (begin (vector-set! v 1 -3000000000) v))",
+                "#(0 -3000000000)",
+            ),
+        ]);
+    }
 }
 
 // Step 2: Immediate Constants
@@ -101,12 +144,34 @@ mod immediate {
             r"#\|",
             r"#\}",
             r"#\~",
+            r"#\alarm",
+            r"#\backspace",
+            r"#\delete",
+            r"#\escape",
+            r"#\null",
+            r"#\λ",
+            r"#\é",
+            r"#\😀",
+            r"#\x1f",
         ];
 
         for c in &constants {
             test(c, c);
         }
     }
+
+    #[test]
+    fn unicode_characters() {
+        cases(&[
+            (r"#\x3bb", r"#\λ"),
+            (r"#\x20", r"#\space"),
+            (r"#\x7", r"#\alarm"),
+            (r"(char->fixnum #\λ)", "955"),
+            (r"(char->fixnum #\x10ffff)", "1114111"),
+            (r"(fixnum->char 128512)", r"#\😀"),
+            (r"(char<? #\z #\λ)", "#t"),
+        ]);
+    }
 }
 
 // Step 2: Unary Primitives
@@ -176,6 +241,35 @@ mod unary {
             (r"(char->fixnum #\9)", "57"),
             (r"(char->fixnum (fixnum->char 12))", "12"),
             (r"(fixnum->char (char->fixnum #\x))", "#\\x"),
+            (r"(char->fixnum (fixnum->char 55295))", "55295"),
+            (r"(char->fixnum (fixnum->char 57344))", "57344"),
+            (r"(char->fixnum (fixnum->char 1114111))", "1114111"),
+        ]);
+        error_cases(&[
+            (
+                "(fixnum->char -1)",
+                "fixnum->char: expected Unicode scalar value, got -1",
+            ),
+            (
+                "(fixnum->char 55296)",
+                "fixnum->char: expected Unicode scalar value, got 55296",
+            ),
+            (
+                "(fixnum->char 57343)",
+                "fixnum->char: expected Unicode scalar value, got 57343",
+            ),
+            (
+                "(fixnum->char 1114112)",
+                "fixnum->char: expected Unicode scalar value, got 1114112",
+            ),
+            (
+                "(fixnum->char 99999999)",
+                "fixnum->char: expected Unicode scalar value, got 99999999",
+            ),
+            (
+                "(fixnum->char #\\a)",
+                "fixnum->char: expected Unicode scalar value, got #\\a",
+            ),
         ]);
     }
 
@@ -208,11 +302,16 @@ mod unary {
             (r"(fxzero? -1)", "#f"),
             (r"(fxzero? 64)", "#f"),
             (r"(fxzero? 960)", "#f"),
-            (r"(fxzero? #f)", "#f"),
-            (r"(fxzero? #\newline)", "#f"),
-            (r"(fxzero? (fxzero? 0))", "#f"),
             (r"(fxzero? 0)", "#t"),
-        ])
+        ]);
+        error_cases(&[
+            (r"(fxzero? #f)", "fxzero?: expected fixnum, got #f"),
+            (
+                r"(fxzero? #\newline)",
+                r"fxzero?: expected fixnum, got #\newline",
+            ),
+            (r"(fxzero? (fxzero? 0))", "fxzero?: expected fixnum, got #t"),
+        ]);
     }
 
     #[test]
@@ -275,3 +374,1497 @@ mod unary {
         ]);
     }
 }
+
+// Step 3: Binary Primitives
+mod binary {
+    use super::*;
+
+    #[test]
+    fn fx_add() {
+        cases(&[
+            (r"(fx+ 1 2)", "3"),
+            (r"(fx+ 1 -2)", "-1"),
+            (r"(fx+ -1 2)", "1"),
+            (r"(fx+ -1 -2)", "-3"),
+            (r"(fx+ 536870911 -1)", "536870910"),
+            (r"(fx+ 536870910 1)", "536870911"),
+            (r"(fx+ -536870912 1)", "-536870911"),
+            (r"(fx+ -536870911 -1)", "-536870912"),
+            (r"(fx+ (fx+ 3 5) (fx+ 7 9))", "24"),
+            (r"(fx+ (fx+ (fx+ 1 2) 3) (fx+ 4 (fx+ 5 6)))", "21"),
+            (r"(fx+ (fxadd1 1) (fxsub1 10))", "11"),
+        ]);
+    }
+
+    #[test]
+    fn fx_sub() {
+        cases(&[
+            (r"(fx- 1 2)", "-1"),
+            (r"(fx- 1 -2)", "3"),
+            (r"(fx- -1 2)", "-3"),
+            (r"(fx- -1 -2)", "1"),
+            (r"(fx- 536870910 -1)", "536870911"),
+            (r"(fx- -536870911 1)", "-536870912"),
+            (r"(fx- (fx- 3 5) (fx- 7 9))", "0"),
+            (r"(fx- (fx- (fx- 1 2) 3) (fx- 4 (fx- 5 6)))", "-9"),
+        ]);
+    }
+
+    #[test]
+    fn fx_mul() {
+        cases(&[
+            (r"(fx* 2 3)", "6"),
+            (r"(fx* 2 -3)", "-6"),
+            (r"(fx* -2 3)", "-6"),
+            (r"(fx* -2 -3)", "6"),
+            (r"(fx* 536870911 1)", "536870911"),
+            (r"(fx* -536870912 1)", "-536870912"),
+            (r"(fx* 0 12345)", "0"),
+            (r"(fx* (fx* 2 3) (fx* 4 5))", "120"),
+            (r"(fx* (fx* (fx* 1 2) 3) (fx* 4 (fx* 5 6)))", "720"),
+            (r"(fx* (fx+ 1 2) (fx- 10 4))", "18"),
+        ]);
+    }
+
+    #[test]
+    fn fx_logical() {
+        cases(&[
+            (r"(fxlogand 3 5)", "1"),
+            (r"(fxlogand -1 12)", "12"),
+            (r"(fxlogand 0 -1)", "0"),
+            (r"(fxlogor 3 5)", "7"),
+            (r"(fxlogor 0 12)", "12"),
+            (r"(fxlogor -1 0)", "-1"),
+            (r"(fxlognot (fxlogor (fxlognot 7) 1))", "6"),
+            (r"(fxlognot (fxlogand (fxlognot 7) (fxlognot 2)))", "7"),
+        ]);
+    }
+
+    #[test]
+    fn fx_comparisons() {
+        cases(&[
+            (r"(fx= 12 13)", "#f"),
+            (r"(fx= 12 12)", "#t"),
+            (r"(fx= -12 12)", "#f"),
+            (r"(fx< 12 13)", "#t"),
+            (r"(fx< 13 12)", "#f"),
+            (r"(fx< -13 12)", "#t"),
+            (r"(fx< 12 12)", "#f"),
+            (r"(fx<= 12 13)", "#t"),
+            (r"(fx<= 12 12)", "#t"),
+            (r"(fx<= 13 12)", "#f"),
+            (r"(fx> 12 13)", "#f"),
+            (r"(fx> 13 12)", "#t"),
+            (r"(fx> 12 -13)", "#t"),
+            (r"(fx> 12 12)", "#f"),
+            (r"(fx>= 12 13)", "#f"),
+            (r"(fx>= 12 12)", "#t"),
+            (r"(fx>= 13 12)", "#t"),
+            (r"(fx= (fx+ 13 3) (fx+ 10 6))", "#t"),
+            (r"(fx< (fx+ 10 6) (fx+ 13 1))", "#f"),
+            (r"(not (fx>= 1 2))", "#t"),
+        ]);
+    }
+
+    #[test]
+    fn char_comparisons() {
+        cases(&[
+            (r"(char=? #\a #\a)", "#t"),
+            (r"(char=? #\a #\b)", "#f"),
+            (r"(char<? #\a #\b)", "#t"),
+            (r"(char<? #\b #\a)", "#f"),
+            (r"(char<? #\a #\a)", "#f"),
+            (r"(char<=? #\a #\a)", "#t"),
+            (r"(char<=? #\b #\a)", "#f"),
+            (r"(char>? #\b #\a)", "#t"),
+            (r"(char>? #\a #\b)", "#f"),
+            (r"(char>=? #\a #\a)", "#t"),
+            (r"(char>=? #\a #\b)", "#f"),
+            (r"(char=? (fixnum->char 65) #\A)", "#t"),
+        ]);
+    }
+}
+
+// Step 4: Local Variables
+mod local_variables {
+    use super::*;
+
+    #[test]
+    fn let_() {
+        cases(&[
+            (r"(let ((x 5)) x)", "5"),
+            (r"(let ((x (fx+ 1 2))) x)", "3"),
+            (
+                r"(let ((x (fx+ 1 2))) (let ((y (fx+ 3 4))) (fx+ x y)))",
+                "10",
+            ),
+            (
+                r"(let ((x (fx+ 1 2))) (let ((y (fx+ 3 4))) (fx- y x)))",
+                "4",
+            ),
+            (r"(let ((x (fx+ 1 2)) (y (fx+ 3 4))) (fx- y x))", "4"),
+            (
+                r"(let ((x (let ((y (fx+ 1 2))) (fx* y y)))) (fx+ x x))",
+                "18",
+            ),
+            (r"(let ((x (fx+ 1 2))) (let ((x (fx+ 3 4))) x))", "7"),
+            (r"(let ((x (fx+ 1 2))) (let ((x (fx+ x 4))) x))", "7"),
+            (
+                r"(let ((t (let ((t (let ((t (let ((t (fx+ 1 2))) t))) t))) t))) t)",
+                "3",
+            ),
+            (
+                r"(let ((x 12)) (let ((x (fx+ x x))) (let ((x (fx+ x x))) (let ((x (fx+ x x))) (fx+ x x)))))",
+                "192",
+            ),
+            (r"(let ((x 1)) (let ((x 2) (y x)) y))", "1"),
+            (r"(let () 12)", "12"),
+            (r"(let ((a #\a) (b #\b)) (char<? a b))", "#t"),
+        ]);
+    }
+
+    #[test]
+    fn let_star() {
+        cases(&[
+            (r"(let* ((x 1)) x)", "1"),
+            (r"(let* ((x 1) (y (fx+ x 1))) y)", "2"),
+            (r"(let* ((x 1) (y (fx+ x 1)) (x (fx* y 5))) (fx- x y))", "8"),
+            (r"(let ((x 1)) (let* ((x 2) (y x)) y))", "2"),
+            (r"(let* () 12)", "12"),
+        ]);
+    }
+
+    #[test]
+    fn unbound_variable() {
+        for input in &["x", "(let ((x 1)) y)", "(let ((x 1) (y x)) y)"] {
+            assert!(matches!(
+                compile(input.parse().unwrap(), Target::host(), Safety::Safe),
+                Err(CompilationError::UnboundVariable(_))
+            ));
+        }
+    }
+}
+
+// Step 5: Conditional Expressions
+mod conditional {
+    use super::*;
+
+    #[test]
+    fn if_() {
+        cases(&[
+            (r"(if #t 12 13)", "12"),
+            (r"(if #f 12 13)", "13"),
+            (r"(if 0 12 13)", "12"),
+            (r"(if () 43 ())", "43"),
+            (r"(if #t (if 12 13 4) 17)", "13"),
+            (r"(if #f 12 (if #f 13 4))", "4"),
+            (r"(if #\X (if 1 2 3) (if 4 5 6))", "2"),
+            (r"(if (not (boolean? #t)) 15 (boolean? #f))", "#t"),
+            (
+                r"(if (if (char? #\a) (boolean? #\b) (fixnum? #\c)) 119 -23)",
+                "-23",
+            ),
+            (r"(if (if (if (not 1) (not 2) (not 3)) 4 5) 6 7)", "6"),
+            (r"(if (not (if (if (not 1) (not 2) (not 3)) 4 5)) 6 7)", "7"),
+            (r"(if (char? 12) 13 14)", "14"),
+            (r"(if (char? #\a) 13 14)", "13"),
+            (r"(fxadd1 (if (fxsub1 1) (fxsub1 13) 14))", "13"),
+            (r"(if (fx= 12 13) 12 13)", "13"),
+            (r"(if (fx= 12 12) 13 14)", "13"),
+            (r"(if (fx< 12 13) 12 13)", "12"),
+            (r"(let ((x 5)) (if (fxzero? x) 1 (fx* x 2)))", "10"),
+            (r"(if #f #f)", "#f"),
+        ]);
+    }
+
+    #[test]
+    fn and_or() {
+        cases(&[
+            (r"(and)", "#t"),
+            (r"(and 5)", "5"),
+            (r"(and #f)", "#f"),
+            (r"(and 5 6)", "6"),
+            (r"(and 1 2 #f 3)", "#f"),
+            (r"(and (fx< 1 2) (fx< 2 3) (fx+ 2 3))", "5"),
+            (r"(or)", "#f"),
+            (r"(or #t)", "#t"),
+            (r"(or 5)", "5"),
+            (r"(or 1 2 3)", "1"),
+            (r"(or #f 2 3)", "2"),
+            (r"(or #f #f #f)", "#f"),
+            (r"(let ((x 5)) (or (fxzero? x) x))", "5"),
+            (r"(or (and #f 1) (and 2 3))", "3"),
+        ]);
+    }
+
+    #[test]
+    fn when_unless() {
+        cases(&[
+            (r"(when #t 5)", "5"),
+            (r"(when (fx< 2 1) 5)", "#f"),
+            (r"(unless #t 5)", "#f"),
+            (r"(unless (fx< 2 1) 5)", "5"),
+        ]);
+    }
+
+    #[test]
+    fn cond() {
+        cases(&[
+            (r"(cond (#t 1))", "1"),
+            (r"(cond (#f 1) (else 2))", "2"),
+            (r"(cond ((fx< 2 1) 1) ((fx< 1 2) 2) (else 3))", "2"),
+            (r"(cond (#f 1) (7) (else 3))", "7"),
+            (
+                r"(let ((x 3)) (cond ((fx= x 1) #\a) ((fx= x 2) #\b) ((fx= x 3) #\c) (else #\d)))",
+                r"#\c",
+            ),
+            (
+                r"(let ((x 9)) (cond ((fx= x 1) #\a) ((fx= x 2) #\b) (else #\d)))",
+                r"#\d",
+            ),
+            (r"(cond (#f 1))", "#f"),
+        ]);
+    }
+}
+
+// Step 6: Heap Allocation
+mod pairs {
+    use super::*;
+
+    #[test]
+    fn cons() {
+        cases(&[
+            (r"(cons 1 2)", "(1 . 2)"),
+            (r"(cons 1 ())", "(1)"),
+            (r"(cons 1 (cons 2 ()))", "(1 2)"),
+            (r"(cons 1 (cons 2 3))", "(1 2 . 3)"),
+            (r"(cons (cons 1 2) (cons 3 ()))", "((1 . 2) 3)"),
+            (r"(cons #t (cons #\a (cons () ())))", r"(#t #\a ())"),
+            (r"(let ((x (cons 1 2))) (cons x x))", "((1 . 2) 1 . 2)"),
+            (r"(fxadd1 (car (cons 1 2)))", "2"),
+            (r"(car (cdr (cons 1 (cons 2 ()))))", "2"),
+            (r"(cdr (cons 1 (cons 2 ())))", "(2)"),
+            (
+                r"(let ((x (let ((y (cons 1 2))) (cons y y)))) (cdr (car x)))",
+                "2",
+            ),
+            (
+                r"(let ((t0 (cons 1 2)) (t1 (cons 3 4))) (let ((a0 (car t0)) (a1 (car t1)) (d0 (cdr t0)) (d1 (cdr t1))) (let ((t0 (cons a0 d1)) (t1 (cons a1 d0))) (cons t0 t1))))",
+                "((1 . 4) 3 . 2)",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn is_pair() {
+        cases(&[
+            (r"(pair? (cons 1 2))", "#t"),
+            (r"(pair? ())", "#f"),
+            (r"(pair? 12)", "#f"),
+            (r"(pair? #\a)", "#f"),
+            (r"(pair? #t)", "#f"),
+            (r"(null? (cons 1 2))", "#f"),
+            (r"(fixnum? (cons 1 2))", "#f"),
+            (r"(not (cons 1 2))", "#f"),
+            (r"(if (cons 1 2) 3 4)", "3"),
+            (r"(null? (cdr (cons 1 ())))", "#t"),
+        ]);
+    }
+
+    #[test]
+    fn set_car_cdr() {
+        cases(&[
+            (
+                r"(let ((x (cons 1 2))) (let ((_ (set-car! x 3))) x))",
+                "(3 . 2)",
+            ),
+            (
+                r"(let ((x (cons 1 2))) (let ((_ (set-cdr! x ()))) x))",
+                "(1)",
+            ),
+            (
+                r"(let ((x (cons 1 ())) (y (cons 2 ()))) (let ((_ (set-cdr! x y))) x))",
+                "(1 2)",
+            ),
+            (
+                r"(let* ((x (cons 1 2)) (_ (set-car! x #\a)) (_ (set-cdr! x #\b))) x)",
+                r"(#\a . #\b)",
+            ),
+        ]);
+    }
+}
+
+// Step 7: Vectors and Strings
+mod vectors {
+    use super::*;
+
+    #[test]
+    fn literals() {
+        cases(&[
+            (r"#()", "#()"),
+            (r"#(1 2 3)", "#(1 2 3)"),
+            (r#"#(#t #\a () "b" #(1))"#, r#"#(#t #\a () "b" #(1))"#),
+            (r"(vector-length #(1 2 3))", "3"),
+            (r"(vector-ref #(1 2 3) 2)", "3"),
+        ]);
+    }
+
+    #[test]
+    fn make_vector() {
+        cases(&[
+            (r"(vector? (make-vector 0))", "#t"),
+            (r"(vector-length (make-vector 12))", "12"),
+            (r"(vector? (cons 1 2))", "#f"),
+            (r"(vector? 1287)", "#f"),
+            (r"(vector? ())", "#f"),
+            (r"(vector? #t)", "#f"),
+            (r"(vector? #f)", "#f"),
+            (r#"(vector? "abc")"#, "#f"),
+            (r"(pair? (make-vector 0))", "#f"),
+            (r"(null? (make-vector 0))", "#f"),
+            (r"(boolean? (make-vector 0))", "#f"),
+            (r"(make-vector 0)", "#()"),
+            (
+                r"(let ((v (make-vector 2))) (let* ((_ (vector-set! v 0 #t)) (_ (vector-set! v 1 #f))) v))",
+                "#(#t #f)",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn negative_length() {
+        let result = execute(
+            "(let ((p (cons 1 2))) (let ((v (make-vector -2))) (let ((q (cons 3 4))) (cons p v))))",
+            Safety::Unsafe,
+        );
+        let actual = String::from_utf8(result.stdout).unwrap();
+        assert_eq!(actual.trim(), "((1 . 2) . #())");
+    }
+
+    #[test]
+    fn vector_set() {
+        cases(&[
+            (
+                r"(let ((v (make-vector 3))) (let* ((_ (vector-set! v 0 1)) (_ (vector-set! v 1 (cons 2 3))) (_ (vector-set! v 2 #\c))) v))",
+                r"#(1 (2 . 3) #\c)",
+            ),
+            (
+                r"(let ((v0 (make-vector 2)) (v1 (make-vector 2))) (let* ((_ (vector-set! v0 0 100)) (_ (vector-set! v0 1 200)) (_ (vector-set! v1 0 300)) (_ (vector-set! v1 1 400))) (cons v0 v1)))",
+                "(#(100 200) . #(300 400))",
+            ),
+            (
+                r"(let ((v (make-vector 1))) (let ((_ (vector-set! v 0 (fx* 6 7)))) (vector-ref v 0)))",
+                "42",
+            ),
+            (
+                r"(let ((x (make-vector 3)) (y (make-vector 3))) (fx- (vector-length x) (vector-length y)))",
+                "0",
+            ),
+        ]);
+    }
+}
+
+mod strings {
+    use super::*;
+
+    #[test]
+    fn literals() {
+        cases(&[
+            (r#""""#, r#""""#),
+            (r#""hello""#, r#""hello""#),
+            (r#""hello world""#, r#""hello world""#),
+            (r#""a \"quoted\" word""#, r#""a \"quoted\" word""#),
+            (r#""back\\slash""#, r#""back\\slash""#),
+            (r#""line\nbreak""#, r#""line\nbreak""#),
+            (r#"(string-length "hello")"#, "5"),
+            (r#"(string-ref "hello" 1)"#, r"#\e"),
+            (r#"(cons "a" (cons "b" ()))"#, r#"("a" "b")"#),
+            (r#""λx. x → 😀""#, r#""λx. x → 😀""#),
+            (r#"(string-length "λx. 😀")"#, "5"),
+            (r#"(string-ref "aλb" 1)"#, r"#\λ"),
+        ]);
+    }
+
+    #[test]
+    fn literal_stores() {
+        // Each code point is stored in 32 bits, so none is written past the
+        // end of the string.
+        let assembly = compile(r#""ab""#.parse().unwrap(), Target::host(), Safety::Safe).unwrap();
+        assert!(assembly.contains("\tmovl $97, 8(%r12,%r13)\n"));
+        assert!(assembly.contains("\tmovl $98, 12(%r12,%r13)\n"));
+        assert!(assembly.contains("\taddq $16, %r13\n"));
+    }
+
+    #[test]
+    fn make_string() {
+        cases(&[
+            (r"(string? (make-string 0))", "#t"),
+            (r"(string-length (make-string 0))", "0"),
+            (r"(string-length (make-string 9))", "9"),
+            (r"(string? (make-vector 0))", "#f"),
+            (r"(string? (cons 1 2))", "#f"),
+            (r"(string? 1287)", "#f"),
+            (r"(string? ())", "#f"),
+            (r"(string? #t)", "#f"),
+            (r"(vector? (make-string 0))", "#f"),
+            (r"(make-string 0)", r#""""#),
+        ]);
+    }
+
+    #[test]
+    fn negative_length() {
+        let result = execute(
+            "(let ((p (cons 1 2))) (let ((s (make-string -9))) (let ((q (cons 3 4))) (cons p s))))",
+            Safety::Unsafe,
+        );
+        let actual = String::from_utf8(result.stdout).unwrap();
+        assert_eq!(actual.trim(), r#"((1 . 2) . "")"#);
+    }
+
+    #[test]
+    fn string_set() {
+        cases(&[
+            (
+                r"(let ((s (make-string 1))) (let ((_ (string-set! s 0 #\a))) (string-ref s 0)))",
+                r"#\a",
+            ),
+            (
+                r"(let ((s (make-string 2))) (let* ((_ (string-set! s 0 #\a)) (_ (string-set! s 1 #\b))) s))",
+                r#""ab""#,
+            ),
+            (
+                r"(let ((s (make-string 3))) (let* ((_ (string-set! s 0 #\a)) (_ (string-set! s 1 #\b)) (_ (string-set! s 2 #\c))) s))",
+                r#""abc""#,
+            ),
+            (
+                r#"(let ((s "hello")) (let ((_ (string-set! s 0 #\j))) s))"#,
+                r#""jello""#,
+            ),
+            (
+                r"(let ((s (make-string 3))) (let* ((_ (string-set! s 0 #\a)) (_ (string-set! s 1 #\b)) (_ (string-set! s 2 #\c))) (char<? (string-ref s 0) (string-ref s 2))))",
+                "#t",
+            ),
+            (
+                r"(let ((s (make-string 1))) (let ((_ (string-set! s 0 #\\))) s))",
+                r#""\\""#,
+            ),
+            (
+                r#"(let ((s (make-string 1))) (let ((_ (string-set! s 0 #\"))) s))"#,
+                r#""\"""#,
+            ),
+            (
+                r#"(let ((s (make-string 2))) (let* ((_ (string-set! s 0 #\x3bb)) (_ (string-set! s 1 #\😀))) s))"#,
+                r#""λ😀""#,
+            ),
+        ]);
+    }
+}
+
+// Step 8: Procedures
+mod procedures {
+    use super::*;
+
+    #[test]
+    fn labels() {
+        cases(&[
+            ("(labels () 12)", "12"),
+            ("(labels ((f (code () 5))) 7)", "7"),
+            ("(labels ((f (code () 5))) (labelcall f))", "5"),
+            ("(labels ((f (code () 5))) (fx+ (labelcall f) 6))", "11"),
+            ("(labels ((f (code () 5))) (fx- 20 (labelcall f)))", "15"),
+            (
+                "(labels ((f (code () 5))) (fx+ (labelcall f) (labelcall f)))",
+                "10",
+            ),
+            (
+                "(labels ((f (code (x) (fx+ x 12)))) (labelcall f 13))",
+                "25",
+            ),
+            (
+                "(labels ((f (code (x y) (fx- x y)))) (labelcall f 10 3))",
+                "7",
+            ),
+            (
+                "(labels ((f (code (x) (fx+ x 1))) (g (code (x) (fx* x 2)))) (labelcall f (labelcall g 5)))",
+                "11",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn define() {
+        cases(&[
+            ("(define (f) 5) (f)", "5"),
+            (
+                "(define (add a b c d e f) (fx+ a (fx+ b (fx+ c (fx+ d (fx+ e f)))))) (add 1 2 3 4 5 6)",
+                "21",
+            ),
+            (
+                "(define (f x) (g x 1)) (define (g x y) (fx+ x y)) (let ((x 10)) (fx* (f x) 2))",
+                "22",
+            ),
+            (
+                "(define (fact n) (if (fxzero? n) 1 (fx* n (fact (fxsub1 n))))) (fact 5)",
+                "120",
+            ),
+            (
+                "(define (fib n) (if (fx< n 2) n (fx+ (fib (fx- n 1)) (fib (fx- n 2))))) (fib 15)",
+                "610",
+            ),
+            (
+                "(define (even? n) (if (fxzero? n) #t (odd? (fxsub1 n)))) (define (odd? n) (if (fxzero? n) #f (even? (fxsub1 n)))) (cons (even? 24) (odd? 24))",
+                "(#t . #f)",
+            ),
+            (
+                "(define (count n) (let ((v (make-vector n))) v)) (vector-length (count 4))",
+                "4",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn tail_calls() {
+        cases(&[
+            (
+                "(define (loop n acc) (if (fxzero? n) acc (loop (fxsub1 n) (fx+ acc 1)))) (loop 1000000 0)",
+                "1000000",
+            ),
+            (
+                "(define (even? n) (if (fxzero? n) #t (odd? (fxsub1 n)))) (define (odd? n) (if (fxzero? n) #f (even? (fxsub1 n)))) (even? 1000001)",
+                "#f",
+            ),
+            (
+                "(define (swap a b n) (if (fxzero? n) (cons a b) (swap b a (fxsub1 n)))) (swap 1 2 3)",
+                "(2 . 1)",
+            ),
+            (
+                "(define (f n) (let ((m (fxsub1 n))) (cond ((fxzero? m) 0) (else (f m))))) (f 100000)",
+                "0",
+            ),
+            (
+                "(define (f n) (and #t (or (fxzero? n) (f (fxsub1 n))))) (f 1000000)",
+                "#t",
+            ),
+            (
+                "(define (f n) (when (fx> n 0) (unless (fxzero? n) (f (fxsub1 n))))) (f 1000000)",
+                "#f",
+            ),
+            (
+                "(define (f n) (let* ((m (fxsub1 n)) (k (fxsub1 m))) (if (fx<= k 0) (cons m k) (f m)))) (f 1000000)",
+                "(1 . 0)",
+            ),
+            (
+                "(define (loop n acc) (if (fxzero? n) acc (loop (fxsub1 n) (fx+ acc 1)))) (fx+ (loop 1000000 0) 1)",
+                "1000001",
+            ),
+            (
+                "(let ((f (lambda (f n) (if (fxzero? n) 0 (f f (fxsub1 n)))))) (f f 1000000))",
+                "0",
+            ),
+            (
+                "(define (count k) (lambda (n acc) (if (fxzero? n) acc ((count k) (fxsub1 n) (fx+ acc k))))) ((count 2) 1000000 0)",
+                "2000000",
+            ),
+            (
+                "(let ((even? (lambda (even? odd? n) (if (fxzero? n) #t (odd? even? odd? (fxsub1 n))))) (odd? (lambda (even? odd? n) (if (fxzero? n) #f (even? even? odd? (fxsub1 n)))))) (even? even? odd? 1000000))",
+                "#t",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn errors() {
+        for input in &["(f 1)", "(define (f) (g)) (f)", "(lambda () x)"] {
+            assert!(matches!(
+                compile(input.parse().unwrap(), Target::host(), Safety::Safe),
+                Err(CompilationError::UnboundVariable(_))
+            ));
+        }
+
+        for input in &["(define (f x) x) (f)", "(define (f) 1) (f 2)"] {
+            assert!(matches!(
+                compile(input.parse().unwrap(), Target::host(), Safety::Safe),
+                Err(CompilationError::ArityMismatch(_))
+            ));
+        }
+
+        // A procedure may only be defined once at top level.
+        let cases = [
+            ("(define (f) 1) (define (f) 2) (f)", 24..25),
+            (
+                "(define (f x) x) (define (g) 1) (f 1) (define (f) 2) (g)",
+                47..48,
+            ),
+        ];
+        for (input, span) in &cases {
+            match run(input, Target::host(), Safety::Safe) {
+                Err(CompilationError::ParseError(error)) => {
+                    assert_eq!(error.to_string(), "procedure is already defined");
+                    assert_eq!(error.span, *span);
+                }
+                _ => panic!("{:?} compiled", input),
+            }
+        }
+    }
+}
+
+// Step 9: Closures
+mod closures {
+    use super::*;
+
+    #[test]
+    fn lambda() {
+        cases(&[
+            ("(lambda (x) x)", "#<procedure>"),
+            ("(define (f) 1) f", "#<procedure>"),
+            ("((lambda () 12))", "12"),
+            ("((lambda (x) (fx+ x 1)) 41)", "42"),
+            ("((lambda (x y) (fx- x y)) 10 3)", "7"),
+            ("(let ((f (lambda (x y) (fx- x y)))) (f 10 3))", "7"),
+            (
+                "(let ((f (lambda (a b c d e) (fx+ a (fx+ b (fx+ c (fx+ d e))))))) (f 1 2 3 4 5))",
+                "15",
+            ),
+            ("(let ((f (lambda () (cons 1 2)))) (pair? (f)))", "#t"),
+        ]);
+    }
+
+    #[test]
+    fn free_variables() {
+        cases(&[
+            ("(let ((x 5)) (let ((f (lambda (y) (fx+ x y)))) (f 2)))", "7"),
+            (
+                "(let ((make-adder (lambda (n) (lambda (m) (fx+ n m))))) ((make-adder 3) 4))",
+                "7",
+            ),
+            (
+                "(let ((a 1) (b 2)) (let ((f (lambda () (lambda () (fx+ a b))))) ((f))))",
+                "3",
+            ),
+            (
+                "(let ((x 1)) (let ((f (lambda () x))) (let ((x 2)) (f))))",
+                "1",
+            ),
+            (
+                "(let ((v (make-vector 1))) (let* ((f (lambda (x) (vector-set! v 0 x))) (_ (f 5))) v))",
+                "#(5)",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn higher_order() {
+        cases(&[
+            (
+                "(define (compose f g) (lambda (x) (f (g x)))) (define (add1 x) (fxadd1 x)) ((compose add1 add1) 5)",
+                "7",
+            ),
+            (
+                "(define (map f l) (if (null? l) () (cons (f (car l)) (map f (cdr l))))) (let ((k 10)) (map (lambda (x) (fx* x k)) (cons 1 (cons 2 (cons 3 ())))))",
+                "(10 20 30)",
+            ),
+            (
+                "(define (apply-twice f x) (f (f x))) (apply-twice (lambda (x) (fx* x x)) 3)",
+                "81",
+            ),
+            (
+                "(let ((fact (lambda (fact n) (if (fxzero? n) 1 (fx* n (fact fact (fxsub1 n))))))) (fact fact 5))",
+                "120",
+            ),
+            (
+                "(define (f) 1) (let ((f (lambda () 2))) (f))",
+                "2",
+            ),
+        ]);
+    }
+}
+
+// Step 10: Programs
+mod programs {
+    use super::*;
+
+    #[test]
+    fn begin() {
+        cases(&[
+            ("(begin 1)", "1"),
+            ("(begin 1 2 3)", "3"),
+            (
+                "(let ((p (cons 1 2))) (begin (set-car! p 3) (set-cdr! p 4) p))",
+                "(3 . 4)",
+            ),
+            (
+                "(let ((v (make-vector 2))) (begin (vector-set! v 0 1) (vector-set! v 1 2) v))",
+                "#(1 2)",
+            ),
+            ("(fxadd1 (begin (cons 1 2) 41))", "42"),
+            (
+                "(define (count n) (if (fxzero? n) 0 (begin (cons n n) (count (fxsub1 n))))) (count 100000)",
+                "0",
+            ),
+            (
+                "(let ((f (lambda (v) (begin (vector-set! v 0 #t) v)))) (f (make-vector 1)))",
+                "#(#t)",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn forms() {
+        cases(&[
+            ("", "#f"),
+            ("(fxadd1 2) 5", "5"),
+            ("1 2 (fx+ 3 4)", "7"),
+            ("(define (f x) (fx* x 2)) (f 1) (f 21)", "42"),
+            ("(f 1) (define (f x) (fx* x 3))", "3"),
+            ("(define (f) 1)", "#f"),
+            (
+                "(define (g v) (vector-set! v 0 (cons 1 2))) (labels ((h (code (v) (vector-ref v 0)))) 0) (let ((v (make-vector 1))) (begin (g v) (h v)))",
+                "(1 . 2)",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn comments() {
+        cases(&[
+            ("; nothing but a comment", "#f"),
+            ("(fx+ 1 ; one\n 2) ; three", "3"),
+            ("#| a #| nested |# comment |# 42", "42"),
+            ("(cons 1 #;2 3)", "(1 . 3)"),
+            ("1 #;(define (f) 2) #; #; 3 4", "1"),
+            (
+                "(define (f x) #| the identity |# x) ; end\n(f #;(f 1) 5)",
+                "5",
+            ),
+        ]);
+    }
+}
+
+// Step 11: Assignment
+mod assignment {
+    use super::*;
+
+    #[test]
+    fn local_variables() {
+        cases(&[
+            ("(let ((x 1)) (begin (set! x 2) x))", "2"),
+            ("(let ((x 1)) (begin (set! x (fx+ x 41)) x))", "42"),
+            ("(let* ((x 1) (y x)) (begin (set! x 2) (cons x y)))", "(2 . 1)"),
+            ("(let ((x 1)) (begin (let ((x 2)) (set! x 3)) x))", "1"),
+            ("(define (f x) (begin (set! x (fx* x 2)) x)) (f 21)", "42"),
+            (
+                "(define (loop i sum) (if (fxzero? i) sum (begin (set! sum (fx+ sum i)) (loop (fxsub1 i) sum)))) (loop 10000 0)",
+                "50005000",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn captured_variables() {
+        cases(&[
+            (
+                "(let ((n 0)) (let ((f (lambda () (set! n (fxadd1 n))))) (begin (f) (f) (f) n)))",
+                "3",
+            ),
+            (
+                "(define (make-counter) (let ((n 0)) (lambda () (begin (set! n (fxadd1 n)) n)))) (let ((c (make-counter)) (d (make-counter))) (begin (c) (c) (d) (cons (c) (d))))",
+                "(3 . 2)",
+            ),
+            (
+                "(let ((x 1)) (let ((get (lambda () x)) (set (lambda (v) (set! x v)))) (begin (set 5) (get))))",
+                "5",
+            ),
+            (
+                "(let ((f (lambda (x) (cons (lambda () x) (lambda (v) (set! x v)))))) (let ((p (f 1))) (begin ((cdr p) 7) ((car p)))))",
+                "7",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn errors() {
+        for input in &["(set! x 1)", "(define (f) 1) (set! f 2)"] {
+            assert!(run(input, Target::host(), Safety::Safe).is_err());
+        }
+        assert!(matches!(
+            run("(define (f) 1) (set! f 2)", Target::host(), Safety::Safe),
+            Err(CompilationError::InvalidAssignment(_))
+        ));
+    }
+}
+
+// Step 12: Macros
+mod macros {
+    use super::*;
+
+    #[test]
+    fn syntax_rules() {
+        cases(&[
+            (
+                "(define-syntax swap! (syntax-rules () ((_ a b) (let ((tmp a)) (begin (set! a b) (set! b tmp)))))) (let ((x 1) (y 2)) (begin (swap! x y) (cons x y)))",
+                "(2 . 1)",
+            ),
+            (
+                "(define-syntax my-list (syntax-rules () ((_) ()) ((_ x rest ...) (cons x (my-list rest ...))))) (my-list 1 2 3)",
+                "(1 2 3)",
+            ),
+            (
+                "(define-syntax pairs (syntax-rules () ((_ (a b) ...) (vector (cons a b) ...)))) (define-syntax vector (syntax-rules () ((_ x ...) (let ((v (make-vector (length x ...)))) (fill v 0 x ...))))) (define-syntax length (syntax-rules () ((_) 0) ((_ x rest ...) (fxadd1 (length rest ...))))) (define-syntax fill (syntax-rules () ((_ v i) v) ((_ v i x rest ...) (begin (vector-set! v i x) (fill v (fxadd1 i) rest ...))))) (pairs (1 2) (3 4))",
+                "#((1 . 2) (3 . 4))",
+            ),
+            (
+                "(define-syntax my-if (syntax-rules (then else) ((_ c then t else e) (if c t e)))) (my-if #f then 1 else 2)",
+                "2",
+            ),
+            (
+                "(define-syntax while (syntax-rules () ((_ c body ...) (let ((loop (lambda (loop) (when c (begin body ... (loop loop)))))) (loop loop))))) (let ((i 0) (sum 0)) (begin (while (fx< i 10) (set! sum (fx+ sum i)) (set! i (fxadd1 i))) sum))",
+                "45",
+            ),
+            (
+                "(define-syntax define-getter (syntax-rules () ((_ name value) (define (name) value)))) (define-getter answer 42) (answer)",
+                "42",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn hygiene() {
+        cases(&[
+            (
+                "(define-syntax my-or (syntax-rules () ((_) #f) ((_ e) e) ((_ e r ...) (let ((t e)) (if t t (my-or r ...)))))) (let ((t 5)) (my-or #f t))",
+                "5",
+            ),
+            (
+                "(define-syntax my-or (syntax-rules () ((_ a b) (let ((t a)) (if t t b))))) (let ((if (lambda (x y z) 0))) (my-or #f 2))",
+                "2",
+            ),
+            (
+                "(define (double x) (fx* x 2)) (define-syntax twice (syntax-rules () ((_ e) (double e)))) (let ((double (lambda (x) 0))) (twice 21))",
+                "42",
+            ),
+            (
+                "(let ((x 10)) (let-syntax ((get-x (syntax-rules () ((_) x)))) (let ((x 20)) (get-x))))",
+                "10",
+            ),
+            (
+                "(define-syntax kw (syntax-rules (=>) ((_ a => b) b) ((_ a x b) a))) (cons (kw 1 => 2) (let ((=> 0)) (kw 1 => 2)))",
+                "(2 . 1)",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn local_macros() {
+        cases(&[
+            (
+                "(let-syntax ((add (syntax-rules () ((_ a b) (fx+ a b))))) (add 1 2))",
+                "3",
+            ),
+            (
+                "(letrec-syntax ((even? (syntax-rules () ((_) #t) ((_ x r ...) (odd? r ...)))) (odd? (syntax-rules () ((_) #f) ((_ x r ...) (even? r ...))))) (cons (even? 1 2 3 4) (odd? 1 2)))",
+                "(#t . #f)",
+            ),
+            (
+                "(define-syntax m (syntax-rules () ((_) 1))) (let-syntax ((m (syntax-rules () ((_) (fx+ (m) 1))))) (m))",
+                "2",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn syntax_case() {
+        cases(&[
+            (
+                "(define-syntax swap! (lambda (x) (syntax-case x () ((_ a b) (syntax (let ((tmp a)) (begin (set! a b) (set! b tmp)))))))) (let ((tmp 1) (y 2)) (begin (swap! tmp y) (cons tmp y)))",
+                "(2 . 1)",
+            ),
+            (
+                "(define-syntax fact (lambda (x) (syntax-case x () ((_ n) (let f ((n (syntax->datum (syntax n)))) (if (= n 0) 1 (* n (f (- n 1))))))))) (fact 10)",
+                "3628800",
+            ),
+            (
+                "(define-syntax unroll (lambda (x) (syntax-case x () ((_ n e) (let loop ((i (syntax->datum (syntax n))) (body (syntax 0))) (if (= i 0) body (with-syntax ((body body)) (loop (- i 1) (syntax (fx+ e body)))))))))) (unroll 4 3)",
+                "12",
+            ),
+            (
+                "(define-syntax squares (lambda (x) (syntax-case x () ((_ n) (list->vector (map (lambda (i) (* i i)) (let loop ((i (syntax->datum (syntax n))) (acc (list))) (if (= i 0) acc (loop (- i 1) (cons i acc)))))))))) (squares 4)",
+                "#(1 4 9 16)",
+            ),
+            (
+                "(define-syntax aif (lambda (x) (syntax-case x () ((k c then else) (with-syntax ((it (datum->syntax (syntax k) (quote it)))) (syntax (let ((it c)) (if it then else)))))))) (aif (car (cons 7 8)) (fx+ it 1) 0)",
+                "8",
+            ),
+            (
+                "(define-syntax my-cond (lambda (x) (syntax-case x (else) ((_ (else e)) (syntax e)) ((_ (c e) rest ...) (syntax (if c e (my-cond rest ...))))))) (let ((x 5)) (my-cond ((fx< x 3) 1) ((fx< x 6) 2) (else 3)))",
+                "2",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn constants() {
+        cases(&[
+            ("(define-syntax m (lambda (x) 1.5)) (m)", "1.5"),
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((_ n) (datum->syntax (syntax _) (syntax->datum (syntax n))))))) (m 2.5)",
+                "2.5",
+            ),
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((_ n) (if (eqv? (syntax->datum (syntax n)) 2.5) (syntax 1) (syntax 0)))))) (cons (m 2.5) (m -0.5))",
+                "(1 . 0)",
+            ),
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((_ n) (datum->syntax (syntax _) (syntax->datum (syntax n))))))) (m 1/2)",
+                "1/2",
+            ),
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((_ n) (datum->syntax (syntax _) (syntax->datum (syntax n))))))) (m 99999999999999999999)",
+                "99999999999999999999",
+            ),
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((_ n) (if (integer? (syntax->datum (syntax n))) (syntax n) (syntax 0)))))) (cons (m -99999999999999999999) (m 1.5))",
+                "(-99999999999999999999 . 0)",
+            ),
+            (
+                "(define-syntax m (lambda (x) (syntax-case x () ((_ n) (if (eqv? (syntax->datum (syntax n)) 1/2) (syntax 1) (syntax 0)))))) (cons (m 1/2) (m 1/3))",
+                "(1 . 0)",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn errors() {
+        for input in &[
+            "(define-syntax m (syntax-rules () ((_ x) x))) (m)",
+            "(define-syntax m (syntax-rules () ((_ x) (m x)))) (m 1)",
+            "(define-syntax m (syntax-rules () ((_ x) x))) (cons m 1)",
+            "(let-syntax ((m 1)) 1)",
+            "(define-syntax m (lambda (x) (syntax-case x () ((_) (syntax (fxadd1 1 2)))))) (m)",
+            "(define-syntax m (lambda (x) (car x))) (m)",
+            "(define-syntax m (lambda (x) (let f () (f)))) (m)",
+        ] {
+            assert!(matches!(
+                run(input, Target::host(), Safety::Safe),
+                Err(CompilationError::ParseError(_))
+            ));
+        }
+    }
+}
+
+// Step 13: Bignums
+mod bignums {
+    use super::*;
+
+    #[test]
+    fn literals() {
+        cases(&[
+            ("2305843009213693952", "2305843009213693952"),
+            ("-2305843009213693953", "-2305843009213693953"),
+            ("9223372036854775808", "9223372036854775808"),
+            (
+                "-123456789012345678901234567890",
+                "-123456789012345678901234567890",
+            ),
+            (
+                "(cons 100000000000000000000 #(-100000000000000000000))",
+                "(100000000000000000000 . #(-100000000000000000000))",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn overflow() {
+        cases(&[
+            ("(fxadd1 2305843009213693951)", "2305843009213693952"),
+            ("(fxsub1 -2305843009213693952)", "-2305843009213693953"),
+            (
+                "(fx+ 2305843009213693951 2305843009213693951)",
+                "4611686018427387902",
+            ),
+            ("(fx- -2305843009213693952 1)", "-2305843009213693953"),
+            ("(fx* 4294967296 4294967296)", "18446744073709551616"),
+            ("(fx* -3037000500 3037000500)", "-9223372037000250000"),
+        ]);
+    }
+
+    #[test]
+    fn generic_arithmetic() {
+        cases(&[
+            ("(+ 1 2)", "3"),
+            ("(- 1 2)", "-1"),
+            ("(* -6 7)", "-42"),
+            ("(quotient 17 5)", "3"),
+            ("(quotient -17 5)", "-3"),
+            ("(remainder 17 -5)", "2"),
+            ("(remainder -17 5)", "-2"),
+            ("(quotient -2305843009213693952 -1)", "2305843009213693952"),
+            (
+                "(+ 123456789012345678901234567890 987654321098765432109876543210)",
+                "1111111110111111111011111111100",
+            ),
+            ("(- 100000000000000000000 100000000000000000001)", "-1"),
+            (
+                "(* 123456789012345678901234567890 -987654321)",
+                "-121932631124828532112482853211126352690",
+            ),
+            (
+                "(quotient 121932631124828532112482853211126352690 987654321)",
+                "123456789012345678901234567890",
+            ),
+            (
+                "(remainder -123456789012345678901234567890 1000000007)",
+                "-197434842",
+            ),
+            ("(fixnum? (- 2305843009213693952 1))", "#t"),
+            ("(fixnum? (+ 2305843009213693951 1))", "#f"),
+        ]);
+    }
+
+    #[test]
+    fn factorial() {
+        test(
+            "(define (factorial n) \
+               (if (fxzero? n) 1 (* n (factorial (fxsub1 n))))) \
+             (factorial 30)",
+            "265252859812191058636308480000000",
+        );
+    }
+}
+
+// Step 14: Flonums
+mod flonums {
+    use super::*;
+
+    #[test]
+    fn literals() {
+        cases(&[
+            ("3.14", "3.14"),
+            ("-0.5", "-0.5"),
+            ("1.", "1.0"),
+            ("-0.0", "-0.0"),
+            ("100.0", "100.0"),
+            ("1e10", "10000000000.0"),
+            ("1e22", "1e22"),
+            ("1.5e-7", "1.5e-7"),
+            ("0.1", "0.1"),
+            ("+inf.0", "+inf.0"),
+            ("-inf.0", "-inf.0"),
+            ("+nan.0", "+nan.0"),
+            ("(cons 1.5 #(2.5))", "(1.5 . #(2.5))"),
+        ]);
+    }
+
+    #[test]
+    fn primitives() {
+        cases(&[
+            ("(fl+ 1.5 2.25)", "3.75"),
+            ("(fl- 0.1 0.3)", "-0.19999999999999998"),
+            ("(fl* 1e200 1e200)", "+inf.0"),
+            ("(fl/ 1.0 3.0)", "0.3333333333333333"),
+            ("(fl/ 0.0 0.0)", "+nan.0"),
+            ("(flsqrt 2.0)", "1.4142135623730951"),
+            ("(flsqrt -1.0)", "+nan.0"),
+            ("(fl= 1.0 1.0)", "#t"),
+            ("(fl= 1.0 2.0)", "#f"),
+            ("(fl< 1.0 2.0)", "#t"),
+            ("(fl< 2.0 1.0)", "#f"),
+            ("(fl<= 2.0 2.0)", "#t"),
+            ("(fl> 2.0 1.0)", "#t"),
+            ("(fl>= 1.0 2.0)", "#f"),
+            ("(let ((nan (fl/ 0.0 0.0))) (fl= nan nan))", "#f"),
+            ("(let ((nan (fl/ 0.0 0.0))) (fl< nan 1.0))", "#f"),
+            ("(let ((nan (fl/ 0.0 0.0))) (fl>= 1.0 nan))", "#f"),
+            ("(flonum? 1.0)", "#t"),
+            ("(flonum? 1)", "#f"),
+            ("(flonum? 10000000000000000000000)", "#f"),
+            ("(flonum? #t)", "#f"),
+            ("(flonum? (cons 1 2))", "#f"),
+        ]);
+    }
+
+    #[test]
+    fn generic_arithmetic() {
+        cases(&[
+            ("(+ 1 0.5)", "1.5"),
+            ("(- 0.5 1)", "-0.5"),
+            ("(* 10000000000000000000000 1.0)", "1e22"),
+            ("(* 2.5 4)", "10.0"),
+            ("(quotient 7.0 2)", "3.0"),
+            ("(remainder -7.5 2)", "-1.5"),
+        ]);
+    }
+
+    #[test]
+    fn conversions() {
+        cases(&[
+            ("(exact->inexact 3)", "3.0"),
+            (
+                "(exact->inexact 12345678901234567890123)",
+                "1.2345678901234568e22",
+            ),
+            ("(exact->inexact 2.5)", "2.5"),
+            ("(inexact->exact -2.5e3)", "-2500"),
+            ("(inexact->exact 1e20)", "100000000000000000000"),
+            ("(inexact->exact -0.0)", "0"),
+            ("(inexact->exact 7)", "7"),
+            ("(fixnum? (inexact->exact 4.0))", "#t"),
+        ]);
+    }
+}
+
+// Step 15: Rationals
+mod rationals {
+    use super::*;
+
+    #[test]
+    fn literals() {
+        cases(&[
+            ("1/3", "1/3"),
+            ("-6/4", "-3/2"),
+            ("4/2", "2"),
+            ("0/5", "0"),
+            ("#(1/2 2/4)", "#(1/2 1/2)"),
+            ("1/18446744073709551616", "1/18446744073709551616"),
+        ]);
+    }
+
+    #[test]
+    fn arithmetic() {
+        cases(&[
+            ("(+ 1/3 1/6)", "1/2"),
+            ("(- 1 1/3)", "2/3"),
+            ("(- 1/3 1/3)", "0"),
+            ("(* 2/3 3/2)", "1"),
+            ("(fixnum? (* 1/3 3))", "#t"),
+            ("(/ 1 3)", "1/3"),
+            ("(/ 6 3)", "2"),
+            ("(/ -1 -3)", "1/3"),
+            ("(/ 1 -3)", "-1/3"),
+            ("(/ 1/2 1/4)", "2"),
+            ("(/ 100000000000000000000 300000000000000000000)", "1/3"),
+            (
+                "(+ 1/18446744073709551616 1)",
+                "18446744073709551617/18446744073709551616",
+            ),
+            ("(+ 1/2 0.25)", "0.75"),
+            ("(/ 1.0 4)", "0.25"),
+        ]);
+    }
+
+    #[test]
+    fn parts() {
+        cases(&[
+            ("(numerator 6/4)", "3"),
+            ("(denominator 6/4)", "2"),
+            ("(numerator -5)", "-5"),
+            ("(denominator -5)", "1"),
+            ("(numerator 0.75)", "3.0"),
+            ("(denominator 0.75)", "4.0"),
+        ]);
+    }
+
+    #[test]
+    fn conversions() {
+        cases(&[
+            ("(exact->inexact 1/3)", "0.3333333333333333"),
+            ("(inexact->exact 0.5)", "1/2"),
+            (
+                "(inexact->exact -0.1)",
+                "-3602879701896397/36028797018963968",
+            ),
+        ]);
+    }
+}
+
+// Step 16: Runtime Type Checks
+mod type_checks {
+    use super::*;
+
+    #[test]
+    fn unary() {
+        error_cases(&[
+            ("(fxadd1 #t)", "fxadd1: expected fixnum, got #t"),
+            ("(fxsub1 #\\a)", "fxsub1: expected fixnum, got #\\a"),
+            ("(fxzero? 1.5)", "fxzero?: expected fixnum, got 1.5"),
+            (
+                "(char->fixnum 65)",
+                "char->fixnum: expected character, got 65",
+            ),
+            ("(car (make-vector 1))", "car: expected pair, got #(0)"),
+            ("(cdr 1/2)", "cdr: expected pair, got 1/2"),
+            (
+                "(vector-length \"ab\")",
+                "vector-length: expected vector, got \"ab\"",
+            ),
+            (
+                "(string-length (cons 1 2))",
+                "string-length: expected string, got (1 . 2)",
+            ),
+            ("(flsqrt 4)", "flsqrt: expected flonum, got 4"),
+            (
+                "(make-vector #f)",
+                "make-vector: expected non-negative fixnum, got #f",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn binary() {
+        error_cases(&[
+            ("(fx+ 1 #f)", "fx+: expected fixnum, got #f"),
+            (
+                "(fx< 100000000000000000000 1)",
+                "fx<: expected fixnum, got 100000000000000000000",
+            ),
+            ("(char=? #\\a 97)", "char=?: expected character, got 97"),
+            ("(fl* 2.0 3)", "fl*: expected flonum, got 3"),
+            ("(fl< (cons 1 2) 1.0)", "fl<: expected flonum, got (1 . 2)"),
+            ("(set-cdr! #t 1)", "set-cdr!: expected pair, got #t"),
+            (
+                "(vector-ref (make-vector 2) 1.0)",
+                "vector-ref: expected fixnum, got 1.0",
+            ),
+            (
+                "(string-ref #(#\\a) 0)",
+                "string-ref: expected string, got #(#\\a)",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn ternary() {
+        error_cases(&[
+            (
+                "(vector-set! \"ab\" 0 1)",
+                "vector-set!: expected vector, got \"ab\"",
+            ),
+            (
+                "(string-set! (make-string 1) 0 65)",
+                "string-set!: expected character, got 65",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn generic_arithmetic() {
+        error_cases(&[
+            ("(+ 1 #t)", "+: expected number, got #t"),
+            ("(- (cons 1 2) 1)", "-: expected number, got (1 . 2)"),
+            ("(* 1.5 \"a\")", "*: expected number, got \"a\""),
+            ("(/ #\\a 2)", "/: expected number, got #\\a"),
+            ("(quotient 1/2 3)", "quotient: expected integer, got 1/2"),
+            ("(remainder 7 #f)", "remainder: expected number, got #f"),
+            (
+                "(exact->inexact #f)",
+                "exact->inexact: expected number, got #f",
+            ),
+            ("(denominator #t)", "denominator: expected number, got #t"),
+        ]);
+    }
+
+    #[test]
+    fn well_typed() {
+        cases(&[
+            ("(fxadd1 41)", "42"),
+            ("(let ((f (lambda (x) (fx+ x 1)))) (f 1))", "2"),
+            ("(vector-ref #(1 2 3) 2)", "3"),
+            ("(char<? #\\a #\\b)", "#t"),
+            ("(fl+ 1.0 2.5)", "3.5"),
+        ]);
+    }
+
+    #[test]
+    fn first_argument_reported() {
+        test_error("(fx+ #t #f)", "fx+: expected fixnum, got #t");
+        test_error(
+            "(let ((f (lambda (v i) (vector-ref v i)))) (f (make-vector 3) (cons 1 2)))",
+            "vector-ref: expected fixnum, got (1 . 2)",
+        );
+    }
+}
+
+// Step 17: Safety
+mod safety {
+    use super::*;
+
+    #[test]
+    fn bounds() {
+        error_cases(&[
+            (
+                "(vector-ref (make-vector 3) 3)",
+                "vector-ref: index 3 is out of range for #(0 0 0)",
+            ),
+            (
+                "(vector-ref #(1 2) -1)",
+                "vector-ref: index -1 is out of range for #(1 2)",
+            ),
+            (
+                "(vector-set! #() 0 1)",
+                "vector-set!: index 0 is out of range for #()",
+            ),
+            (
+                "(string-ref \"abc\" 5)",
+                "string-ref: index 5 is out of range for \"abc\"",
+            ),
+            (
+                "(string-set! \"ab\" 2 #\\c)",
+                "string-set!: index 2 is out of range for \"ab\"",
+            ),
+        ]);
+        cases(&[
+            ("(vector-ref #(1 2 3) 0)", "1"),
+            ("(string-ref \"abc\" 2)", "#\\c"),
+        ]);
+    }
+
+    #[test]
+    fn lengths() {
+        error_cases(&[
+            (
+                "(let ((p (cons 1 2))) (let ((v (make-vector -2))) (let ((q (cons 3 4))) p)))",
+                "make-vector: expected non-negative fixnum, got -2",
+            ),
+            (
+                "(make-string -9)",
+                "make-string: expected non-negative fixnum, got -9",
+            ),
+            (
+                "(make-vector #t)",
+                "make-vector: expected non-negative fixnum, got #t",
+            ),
+            (
+                "(make-vector 100000000)",
+                "make-vector: length 100000000 does not fit in the heap",
+            ),
+            (
+                "(let ((s (make-string 8000000))) (make-string 8777214))",
+                "make-string: length 8777214 does not fit in the heap",
+            ),
+        ]);
+        cases(&[
+            ("(make-vector 0)", "#()"),
+            ("(string-length (make-string 3))", "3"),
+            ("(vector-length (make-vector 8388607))", "8388607"),
+        ]);
+    }
+
+    #[test]
+    fn arity() {
+        error_cases(&[
+            (
+                "(let ((f (lambda (x) x))) (f 1 2))",
+                "application: expected 1 argument, got 2",
+            ),
+            (
+                "(let ((f (lambda (x y) x))) (f 1))",
+                "application: expected 2 arguments, got 1",
+            ),
+            (
+                "(define (g x) x) (let ((h g)) (h))",
+                "application: expected 1 argument, got 0",
+            ),
+            (
+                "(define (g f) (f)) (g (lambda (x) x))",
+                "application: expected 1 argument, got 0",
+            ),
+        ]);
+        cases(&[
+            ("(let ((f (lambda () 7))) (f))", "7"),
+            ("(define (g x) (fxadd1 x)) (let ((h g)) (h 1))", "2"),
+        ]);
+    }
+
+    #[test]
+    fn procedures() {
+        error_cases(&[
+            (
+                "(let ((f 5)) (f))",
+                "application: expected procedure, got 5",
+            ),
+            (
+                "(let ((f (cons 1 2))) (f 1))",
+                "application: expected procedure, got (1 . 2)",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn unsafe_code() {
+        let cases = [
+            ("(fxzero? #f)", "#f"),
+            ("(let ((f (lambda (x) x))) (f 1 2))", "1"),
+            ("(vector-ref #(1 2 3) 1)", "2"),
+        ];
+
+        for (input, expected) in &cases {
+            let result = execute(input, Safety::Unsafe);
+            assert!(result.status.success());
+            assert_eq!(*expected, String::from_utf8(result.stdout).unwrap().trim());
+        }
+    }
+
+    #[test]
+    fn checks_emitted() {
+        let source = "(define (f v i) (vector-ref v (fxadd1 i))) (f #(1 2) 0)";
+        let safe = run(source, Target::host(), Safety::Safe).unwrap();
+        let unsafe_ = run(source, Target::host(), Safety::Unsafe).unwrap();
+
+        for function in &[
+            "scheme_type_error",
+            "scheme_index_error",
+            "scheme_arity_error",
+        ] {
+            let call = format!("call L{}\n", function);
+            assert!(safe.contains(&call));
+            assert!(!unsafe_.contains(&call));
+        }
+    }
+}
+
+mod targets {
+    use super::*;
+
+    #[test]
+    fn symbols() {
+        let macho = compile("1".parse().unwrap(), Target::MachO, Safety::Safe).unwrap();
+        assert!(macho.contains(".globl _scheme_entry\n_scheme_entry:\n"));
+        assert!(!macho.contains(".type"));
+        assert!(!macho.contains(".note.GNU-stack"));
+
+        let elf = compile("1".parse().unwrap(), Target::Elf, Safety::Safe).unwrap();
+        assert!(
+            elf.contains(".globl scheme_entry\n\t.type scheme_entry, @function\nscheme_entry:\n")
+        );
+        assert!(elf.contains(".size scheme_entry, .-scheme_entry\n"));
+        assert!(elf.contains(".section .note.GNU-stack,\"\",@progbits\n"));
+    }
+}
+
+mod diagnostics {
+    use super::*;
+
+    #[test]
+    fn errors() {
+        let source = "(let ((x 1))\n  (fx+ x #c))";
+        let error = run(source, Target::host(), Safety::Safe).unwrap_err();
+        assert!(matches!(error, CompilationError::LexicalError(_)));
+        assert_eq!(
+            error.render(source),
+            "error: unrecognized token\n \
+             --> 2:10\n  \
+             |\n\
+             2 |   (fx+ x #c))\n  \
+             |          ^^\n"
+        );
+
+        let error = run("(fxadd1 1 2)", Target::host(), Safety::Safe).unwrap_err();
+        assert!(matches!(error, CompilationError::ParseError(_)));
+        assert_eq!(error.to_string(), "`fxadd1` expects 1 argument, found 2");
+
+        let error = run("(let ((x 1)) y)", Target::host(), Safety::Safe).unwrap_err();
+        assert_eq!(
+            error.render("(let ((x 1)) y)"),
+            "error: unbound variable `y`\n"
+        );
+    }
+}